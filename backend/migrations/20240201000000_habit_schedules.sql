@@ -0,0 +1,13 @@
+-- Habit schedules
+-- Habits default to daily; other schedules only count scheduled days/periods
+
+DO $$ BEGIN
+    CREATE TYPE schedule_type AS ENUM ('daily', 'weekdays', 'times_per_week', 'every_n_days');
+EXCEPTION WHEN duplicate_object THEN NULL;
+END $$;
+
+ALTER TABLE habits
+    ADD COLUMN IF NOT EXISTS schedule_type schedule_type NOT NULL DEFAULT 'daily',
+    ADD COLUMN IF NOT EXISTS schedule_weekdays INTEGER[], -- ISO weekdays, 1 = Monday
+    ADD COLUMN IF NOT EXISTS schedule_times_per_week INTEGER,
+    ADD COLUMN IF NOT EXISTS schedule_interval_days INTEGER;
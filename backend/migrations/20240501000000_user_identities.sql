@@ -0,0 +1,24 @@
+-- User identities
+-- Several provider identities can sign in to the same user. The provider
+-- columns on users keep recording the identity the account was created with.
+
+CREATE TABLE IF NOT EXISTS user_identities (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider auth_provider NOT NULL,
+    provider_id VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider, provider_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_identities_user ON user_identities(user_id);
+CREATE INDEX IF NOT EXISTS idx_identities_email ON user_identities(LOWER(email)) WHERE email_verified;
+
+-- Backfill existing accounts. Their emails were never checked, so they are
+-- not treated as verified for automatic linking.
+INSERT INTO user_identities (id, user_id, provider, provider_id, email, email_verified, created_at)
+SELECT gen_random_uuid(), id, provider, provider_id, email, FALSE, created_at
+FROM users
+ON CONFLICT (provider, provider_id) DO NOTHING;
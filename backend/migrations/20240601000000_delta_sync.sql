@@ -0,0 +1,90 @@
+-- Delta sync
+-- Stable client-to-server ID mapping, change timestamps on every synced
+-- table, and tombstones so deletions reach other devices.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS last_sync_at TIMESTAMPTZ;
+
+ALTER TABLE check_ins ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE goal_habits ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS idx_habits_user_updated ON habits(user_id, updated_at);
+CREATE INDEX IF NOT EXISTS idx_checkins_user_updated ON check_ins(user_id, updated_at);
+CREATE INDEX IF NOT EXISTS idx_goals_user_updated ON goals(user_id, updated_at);
+
+DROP TRIGGER IF EXISTS update_check_ins_updated_at ON check_ins;
+CREATE TRIGGER update_check_ins_updated_at BEFORE UPDATE ON check_ins
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
+
+DROP TRIGGER IF EXISTS update_goal_habits_updated_at ON goal_habits;
+CREATE TRIGGER update_goal_habits_updated_at BEFORE UPDATE ON goal_habits
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
+
+DO $$ BEGIN
+    CREATE TYPE sync_entity AS ENUM ('habit', 'check_in', 'goal', 'goal_habit');
+EXCEPTION WHEN duplicate_object THEN NULL;
+END $$;
+
+-- Client-side IDs for records created through sync
+CREATE TABLE IF NOT EXISTS sync_id_map (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    entity_type sync_entity NOT NULL,
+    local_id VARCHAR(255) NOT NULL,
+    server_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, entity_type, local_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_id_map_server ON sync_id_map(user_id, entity_type, server_id);
+
+-- Deleted records, kept so incremental pulls can report them
+CREATE TABLE IF NOT EXISTS sync_tombstones (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    entity_type sync_entity NOT NULL,
+    server_id UUID NOT NULL,
+    goal_id UUID, -- goal_habit tombstones only
+    habit_id UUID, -- goal_habit tombstones only
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_tombstones_user ON sync_tombstones(user_id, deleted_at);
+
+-- Rows removed while their user is being deleted need no tombstone
+CREATE OR REPLACE FUNCTION record_sync_tombstone()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users WHERE id = OLD.user_id) THEN
+        INSERT INTO sync_tombstones (id, user_id, entity_type, server_id, deleted_at)
+        VALUES (gen_random_uuid(), OLD.user_id, TG_ARGV[0]::sync_entity, OLD.id, NOW());
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION record_goal_habit_tombstone()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO sync_tombstones (id, user_id, entity_type, server_id, goal_id, habit_id, deleted_at)
+    SELECT gen_random_uuid(), g.user_id, 'goal_habit', OLD.id, OLD.goal_id, OLD.habit_id, NOW()
+    FROM goals g
+    JOIN users u ON u.id = g.user_id
+    WHERE g.id = OLD.goal_id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS habits_sync_tombstone ON habits;
+CREATE TRIGGER habits_sync_tombstone AFTER DELETE ON habits
+    FOR EACH ROW EXECUTE FUNCTION record_sync_tombstone('habit');
+
+DROP TRIGGER IF EXISTS check_ins_sync_tombstone ON check_ins;
+CREATE TRIGGER check_ins_sync_tombstone AFTER DELETE ON check_ins
+    FOR EACH ROW EXECUTE FUNCTION record_sync_tombstone('check_in');
+
+DROP TRIGGER IF EXISTS goals_sync_tombstone ON goals;
+CREATE TRIGGER goals_sync_tombstone AFTER DELETE ON goals
+    FOR EACH ROW EXECUTE FUNCTION record_sync_tombstone('goal');
+
+DROP TRIGGER IF EXISTS goal_habits_sync_tombstone ON goal_habits;
+CREATE TRIGGER goal_habits_sync_tombstone AFTER DELETE ON goal_habits
+    FOR EACH ROW EXECUTE FUNCTION record_goal_habit_tombstone();
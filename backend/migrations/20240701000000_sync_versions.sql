@@ -0,0 +1,27 @@
+-- Sync versions
+-- Every synced record carries a version that increases on each change, so a
+-- push based on an outdated copy can be detected as a conflict.
+
+ALTER TABLE habits ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE check_ins ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE goals ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+
+CREATE OR REPLACE FUNCTION bump_version()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version = OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS bump_habits_version ON habits;
+CREATE TRIGGER bump_habits_version BEFORE UPDATE ON habits
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
+
+DROP TRIGGER IF EXISTS bump_check_ins_version ON check_ins;
+CREATE TRIGGER bump_check_ins_version BEFORE UPDATE ON check_ins
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
+
+DROP TRIGGER IF EXISTS bump_goals_version ON goals;
+CREATE TRIGGER bump_goals_version BEFORE UPDATE ON goals
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
@@ -0,0 +1,8 @@
+-- Reminder delivery
+-- The scheduler stores each reminder's next fire time so restarts and
+-- multiple API instances don't send duplicates.
+
+ALTER TABLE habit_reminders ADD COLUMN IF NOT EXISTS next_fire_at TIMESTAMPTZ;
+ALTER TABLE habit_reminders ADD COLUMN IF NOT EXISTS last_sent_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_reminders_next_fire ON habit_reminders(next_fire_at) WHERE enabled;
@@ -0,0 +1,5 @@
+-- Per-user time zones
+-- IANA zone name used to work out a user's local "today", reminder fire
+-- times and goal deadlines.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
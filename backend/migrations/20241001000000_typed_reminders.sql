@@ -0,0 +1,19 @@
+-- Typed reminders
+-- Reminder times become TIME columns, and a habit can have several reminders.
+
+ALTER TABLE habit_reminders DROP CONSTRAINT IF EXISTS habit_reminders_habit_id_key;
+CREATE INDEX IF NOT EXISTS idx_reminders_habit ON habit_reminders(habit_id);
+
+-- Values that aren't valid HH:MM times are dropped
+DO $$ BEGIN
+    IF (SELECT data_type FROM information_schema.columns
+        WHERE table_name = 'habit_reminders' AND column_name = 'daily_time') = 'character varying' THEN
+        ALTER TABLE habit_reminders
+            ALTER COLUMN daily_time TYPE TIME USING
+                CASE WHEN daily_time ~ '^([01][0-9]|2[0-3]):[0-5][0-9]$' THEN daily_time::time END,
+            ALTER COLUMN random_window_start TYPE TIME USING
+                CASE WHEN random_window_start ~ '^([01][0-9]|2[0-3]):[0-5][0-9]$' THEN random_window_start::time END,
+            ALTER COLUMN random_window_end TYPE TIME USING
+                CASE WHEN random_window_end ~ '^([01][0-9]|2[0-3]):[0-5][0-9]$' THEN random_window_end::time END;
+    END IF;
+END $$;
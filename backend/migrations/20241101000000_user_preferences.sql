@@ -0,0 +1,15 @@
+-- User preferences and uploaded avatars
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS week_start SMALLINT NOT NULL DEFAULT 1; -- 1 = Monday ... 7 = Sunday
+ALTER TABLE users ADD COLUMN IF NOT EXISTS locale VARCHAR(35) NOT NULL DEFAULT 'en';
+ALTER TABLE users ADD COLUMN IF NOT EXISTS notify_reminders BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS notify_shared_activity BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS notify_email BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Storage key of an uploaded avatar; provider avatars don't replace it
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_key VARCHAR(255);
+
+DO $$ BEGIN
+    ALTER TABLE users ADD CONSTRAINT users_week_start_check CHECK (week_start BETWEEN 1 AND 7);
+EXCEPTION WHEN duplicate_object THEN NULL;
+END $$;
@@ -0,0 +1,7 @@
+-- Invite lifecycle
+-- Inviters can revoke pending invites; a job expires stale ones.
+
+ALTER TYPE invite_status ADD VALUE IF NOT EXISTS 'revoked';
+
+CREATE INDEX IF NOT EXISTS idx_invites_goal ON goal_invites(shared_goal_id, status);
+CREATE INDEX IF NOT EXISTS idx_invites_expiry ON goal_invites(expires_at) WHERE status = 'pending';
@@ -0,0 +1,10 @@
+-- Share roles
+-- Owners choose the role people get when they join a shared goal.
+
+ALTER TABLE shared_goals ADD COLUMN IF NOT EXISTS join_role share_role NOT NULL DEFAULT 'collaborator';
+
+DO $$ BEGIN
+    ALTER TABLE shared_goals ADD CONSTRAINT shared_goals_join_role_check CHECK (join_role <> 'owner');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
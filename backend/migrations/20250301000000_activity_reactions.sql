@@ -0,0 +1,16 @@
+-- Activity reactions
+-- Participants react to shared activity feed items with emoji.
+
+CREATE TABLE IF NOT EXISTS activity_reactions (
+    id UUID PRIMARY KEY,
+    activity_id UUID NOT NULL REFERENCES shared_activities(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    emoji VARCHAR(32) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (activity_id, user_id, emoji)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_reactions_user ON activity_reactions(user_id);
+
+-- Keyset pagination walks the feed by (created_at, id)
+CREATE INDEX IF NOT EXISTS idx_activities_goal_cursor ON shared_activities(shared_goal_id, created_at DESC, id DESC);
@@ -0,0 +1,4 @@
+-- Leaderboard opt-out
+-- Participants can keep themselves out of a shared goal's leaderboard.
+
+ALTER TABLE goal_participants ADD COLUMN IF NOT EXISTS leaderboard_opt_out BOOLEAN NOT NULL DEFAULT FALSE;
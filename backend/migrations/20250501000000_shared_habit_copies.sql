@@ -0,0 +1,9 @@
+-- Shared habit copies
+-- Participants can copy a shared goal's habits into their own account. The
+-- copies stay bound to the shared goal and are linked to it, so their
+-- check-ins count toward its progress.
+
+ALTER TABLE habits ADD COLUMN IF NOT EXISTS shared_goal_id UUID REFERENCES shared_goals(id) ON DELETE SET NULL;
+ALTER TABLE habits ADD COLUMN IF NOT EXISTS source_habit_id UUID REFERENCES habits(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_habits_shared_goal ON habits(shared_goal_id, user_id) WHERE shared_goal_id IS NOT NULL;
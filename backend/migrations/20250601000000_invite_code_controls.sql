@@ -0,0 +1,15 @@
+-- Invite code controls
+-- Owners can rotate a shared goal's invite code, limit how long and how
+-- often it works, or turn joining by code off.
+
+ALTER TABLE shared_goals ADD COLUMN IF NOT EXISTS invite_code_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE shared_goals ADD COLUMN IF NOT EXISTS invite_code_expires_at TIMESTAMPTZ;
+ALTER TABLE shared_goals ADD COLUMN IF NOT EXISTS invite_code_max_uses INTEGER;
+ALTER TABLE shared_goals ADD COLUMN IF NOT EXISTS invite_code_uses INTEGER NOT NULL DEFAULT 0;
+
+DO $$ BEGIN
+    ALTER TABLE shared_goals ADD CONSTRAINT shared_goals_invite_code_max_uses_check
+        CHECK (invite_code_max_uses IS NULL OR invite_code_max_uses >= 1);
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
@@ -0,0 +1,14 @@
+-- Public goal links
+-- A goal's owner can publish a read-only progress page behind a signed
+-- token. Revoking the link stops the token from working.
+
+CREATE TABLE IF NOT EXISTS goal_public_links (
+    id UUID PRIMARY KEY,
+    goal_id UUID NOT NULL REFERENCES goals(id) ON DELETE CASCADE,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+-- At most one live link per goal
+CREATE UNIQUE INDEX IF NOT EXISTS idx_goal_public_links_active ON goal_public_links(goal_id) WHERE revoked_at IS NULL;
@@ -0,0 +1,50 @@
+-- Commit-ordered sync cursors
+-- Synced rows and tombstones record the ID of the transaction that last
+-- wrote them. A pull's cursor is the oldest transaction still running when
+-- it read, so changes committed after the pull's snapshot are picked up by
+-- the next pull even if their transaction started earlier.
+
+CREATE OR REPLACE FUNCTION current_change_xid()
+RETURNS BIGINT AS $$
+    SELECT pg_current_xact_id()::text::bigint;
+$$ LANGUAGE sql VOLATILE;
+
+CREATE OR REPLACE FUNCTION stamp_change_xid()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.change_xid = current_change_xid();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE habits ADD COLUMN IF NOT EXISTS change_xid BIGINT NOT NULL DEFAULT current_change_xid();
+ALTER TABLE check_ins ADD COLUMN IF NOT EXISTS change_xid BIGINT NOT NULL DEFAULT current_change_xid();
+ALTER TABLE goals ADD COLUMN IF NOT EXISTS change_xid BIGINT NOT NULL DEFAULT current_change_xid();
+ALTER TABLE goal_habits ADD COLUMN IF NOT EXISTS change_xid BIGINT NOT NULL DEFAULT current_change_xid();
+ALTER TABLE sync_tombstones ADD COLUMN IF NOT EXISTS change_xid BIGINT NOT NULL DEFAULT current_change_xid();
+
+CREATE INDEX IF NOT EXISTS idx_habits_user_change ON habits(user_id, change_xid);
+CREATE INDEX IF NOT EXISTS idx_checkins_user_change ON check_ins(user_id, change_xid);
+CREATE INDEX IF NOT EXISTS idx_goals_user_change ON goals(user_id, change_xid);
+CREATE INDEX IF NOT EXISTS idx_sync_tombstones_user_change ON sync_tombstones(user_id, change_xid);
+
+DROP TRIGGER IF EXISTS stamp_habits_change_xid ON habits;
+CREATE TRIGGER stamp_habits_change_xid BEFORE UPDATE ON habits
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_xid();
+
+DROP TRIGGER IF EXISTS stamp_check_ins_change_xid ON check_ins;
+CREATE TRIGGER stamp_check_ins_change_xid BEFORE UPDATE ON check_ins
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_xid();
+
+DROP TRIGGER IF EXISTS stamp_goals_change_xid ON goals;
+CREATE TRIGGER stamp_goals_change_xid BEFORE UPDATE ON goals
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_xid();
+
+DROP TRIGGER IF EXISTS stamp_goal_habits_change_xid ON goal_habits;
+CREATE TRIGGER stamp_goal_habits_change_xid BEFORE UPDATE ON goal_habits
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_xid();
+
+-- Tombstones moved to another account by a merge must reach its devices
+DROP TRIGGER IF EXISTS stamp_sync_tombstones_change_xid ON sync_tombstones;
+CREATE TRIGGER stamp_sync_tombstones_change_xid BEFORE UPDATE ON sync_tombstones
+    FOR EACH ROW EXECUTE FUNCTION stamp_change_xid();
@@ -0,0 +1,11 @@
+-- Apple Sign In nonces
+-- Issued before the user signs in with Apple and consumed by the callback,
+-- so a captured id_token can't be replayed
+
+CREATE TABLE IF NOT EXISTS apple_nonces (
+    nonce VARCHAR(128) PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_apple_nonces_expires ON apple_nonces(expires_at);
@@ -0,0 +1,189 @@
+//! Shared goal activity feed
+//!
+//! Handlers report what happened and this module decides which shared goals
+//! hear about it. Habit activity only reaches goals the habit is linked to,
+//! so participants never see habits that weren't shared with them.
+
+use chrono::{Duration, NaiveDate};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    analytics::{schedule::Schedule, streaks},
+    error::ApiResult,
+    models::{CheckIn, Habit},
+};
+
+/// Streak lengths, in schedule periods, announced to the feed
+const STREAK_MILESTONES: [i32; 7] = [7, 14, 30, 50, 100, 200, 365];
+
+/// Post a check-in that was just created or changed, and any streak milestone
+/// it completes, to the shared goals its habit is linked to. `previous_value`
+/// is the value before an update. Only a check-in becoming complete is posted,
+/// once per check-in, and backfilled ones older than yesterday are left out.
+pub async fn check_in_recorded(
+    conn: &mut PgConnection,
+    check_in: &CheckIn,
+    previous_value: Option<i32>,
+    today: NaiveDate,
+) -> ApiResult<()> {
+    if check_in.effective_date < today - Duration::days(1) {
+        return Ok(());
+    }
+
+    let habit = sqlx::query_as::<_, Habit>(
+        r#"SELECT id, user_id, name, description,
+           habit_type, unit, target_value,
+           target_direction,
+           schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+           archived, shared_goal_id, source_habit_id, created_at, updated_at
+           FROM habits WHERE id = $1"#,
+    )
+    .bind(check_in.habit_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let was_completed = previous_value.is_some_and(|value| streaks::is_completed(&habit, value));
+    if was_completed || !streaks::is_completed(&habit, check_in.value) {
+        return Ok(());
+    }
+
+    let shared_goals = linked_shared_goals(conn, &habit).await?;
+    if shared_goals.is_empty() {
+        return Ok(());
+    }
+
+    for &shared_goal_id in &shared_goals {
+        // A check-in can become complete again after being lowered
+        sqlx::query(
+            r#"INSERT INTO shared_activities (id, shared_goal_id, user_id, activity_type, habit_id, check_in_id, created_at)
+               SELECT $1, $2, $3, 'check_in', $4, $5, NOW()
+               WHERE NOT EXISTS (
+                   SELECT 1 FROM shared_activities
+                   WHERE shared_goal_id = $2 AND check_in_id = $5 AND activity_type = 'check_in'
+               )"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(shared_goal_id)
+        .bind(habit.user_id)
+        .bind(habit.id)
+        .bind(check_in.id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let Some((streak, run_start)) = current_streak(conn, &habit, today).await? else {
+        return Ok(());
+    };
+    if !STREAK_MILESTONES.contains(&streak) {
+        return Ok(());
+    }
+
+    let message = format!("{}-{} streak", streak, period_name(&Schedule::for_habit(&habit)));
+    for shared_goal_id in shared_goals {
+        // A weekly streak stays at the same length for several check-ins
+        sqlx::query(
+            r#"INSERT INTO shared_activities (id, shared_goal_id, user_id, activity_type, habit_id, message, created_at)
+               SELECT $1, $2, $3, 'streak_milestone', $4, $5, NOW()
+               WHERE NOT EXISTS (
+                   SELECT 1 FROM shared_activities
+                   WHERE shared_goal_id = $2 AND habit_id = $4
+                     AND activity_type = 'streak_milestone' AND message = $5
+                     AND created_at >= $6::date
+               )"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(shared_goal_id)
+        .bind(habit.user_id)
+        .bind(habit.id)
+        .bind(&message)
+        .bind(run_start)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Announce a new participant
+pub async fn joined_goal(conn: &mut PgConnection, shared_goal_id: Uuid, user_id: Uuid) -> ApiResult<()> {
+    sqlx::query(
+        r#"INSERT INTO shared_activities (id, shared_goal_id, user_id, activity_type, created_at)
+           VALUES ($1, $2, $3, 'joined_goal', NOW())"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(shared_goal_id)
+    .bind(user_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Shared goals the habit is linked to that its owner still takes part in
+async fn linked_shared_goals(conn: &mut PgConnection, habit: &Habit) -> ApiResult<Vec<Uuid>> {
+    let rows: Vec<(Uuid,)> = sqlx::query_as(
+        r#"SELECT sg.id
+           FROM goal_habits gh
+           JOIN shared_goals sg ON sg.goal_id = gh.goal_id
+           JOIN goal_participants gp ON gp.shared_goal_id = sg.id AND gp.user_id = $2
+           WHERE gh.habit_id = $1"#,
+    )
+    .bind(habit.id)
+    .bind(habit.user_id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// The habit's current streak and the first day of the run, if it has one
+async fn current_streak(
+    conn: &mut PgConnection,
+    habit: &Habit,
+    today: NaiveDate,
+) -> ApiResult<Option<(i32, NaiveDate)>> {
+    let check_ins = sqlx::query_as::<_, CheckIn>(
+        r#"SELECT id, habit_id, user_id, value, note, effective_date, created_at
+           FROM check_ins WHERE habit_id = $1
+           ORDER BY effective_date ASC"#,
+    )
+    .bind(habit.id)
+    .fetch_all(conn)
+    .await?;
+
+    let schedule = Schedule::for_habit(habit);
+    let completed = streaks::completed_dates(habit, &check_ins);
+    let anchor = habit.created_at.date_naive();
+    let start = check_ins
+        .iter()
+        .map(|c| c.effective_date)
+        .chain(std::iter::once(anchor))
+        .min()
+        .unwrap_or(today);
+
+    let periods = streaks::periods(&schedule, anchor, &completed, start, today);
+    let streak = streaks::current_streak(&periods, today);
+    if streak == 0 {
+        return Ok(None);
+    }
+
+    // The run is the last `streak` satisfied periods, past any open one
+    let run_start = periods
+        .iter()
+        .rev()
+        .skip_while(|p| !p.is_satisfied())
+        .nth(streak as usize - 1)
+        .map(|p| p.start)
+        .unwrap_or(today);
+
+    Ok(Some((streak, run_start)))
+}
+
+fn period_name(schedule: &Schedule) -> &'static str {
+    match schedule {
+        Schedule::Daily | Schedule::Weekdays(_) => "day",
+        Schedule::TimesPerWeek(_) => "week",
+        Schedule::EveryNDays(_) => "period",
+    }
+}
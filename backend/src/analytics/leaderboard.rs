@@ -0,0 +1,201 @@
+//! Shared goal leaderboards
+//!
+//! Each participant is measured only on their own habits linked to the goal,
+//! over a window ending today in their time zone (or at the deadline, if
+//! that's earlier). Participants who opted out are only shown to themselves.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::{progress, schedule::Schedule, streaks};
+use crate::{
+    error::{ApiError, ApiResult},
+    models::{CheckIn, Habit, Leaderboard, LeaderboardEntry, LeaderboardWindow},
+};
+
+#[derive(Debug, FromRow)]
+struct ParticipantRow {
+    user_id: Uuid,
+    name: Option<String>,
+    avatar_url: Option<String>,
+    leaderboard_opt_out: bool,
+    /// Today in the participant's time zone
+    today: NaiveDate,
+}
+
+#[derive(Debug, FromRow)]
+struct LinkedHabitRow {
+    #[sqlx(flatten)]
+    habit: Habit,
+    weight: f32,
+}
+
+/// Build the leaderboard for a shared goal as seen by `viewer_id`
+pub async fn load_leaderboard(
+    db: &sqlx::PgPool,
+    shared_goal_id: Uuid,
+    window: LeaderboardWindow,
+    viewer_id: Uuid,
+) -> ApiResult<Leaderboard> {
+    let (goal_created_at, deadline): (DateTime<Utc>, NaiveDate) = sqlx::query_as(
+        r#"SELECT g.created_at, g.deadline
+           FROM shared_goals sg
+           JOIN goals g ON g.id = sg.goal_id
+           WHERE sg.id = $1"#,
+    )
+    .bind(shared_goal_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let participants: Vec<ParticipantRow> = sqlx::query_as(
+        r#"SELECT gp.user_id, u.name, u.avatar_url, gp.leaderboard_opt_out,
+                  (NOW() AT TIME ZONE u.timezone)::date AS today
+           FROM goal_participants gp
+           JOIN users u ON u.id = gp.user_id
+           WHERE gp.shared_goal_id = $1 AND (NOT gp.leaderboard_opt_out OR gp.user_id = $2)
+           ORDER BY gp.joined_at ASC"#,
+    )
+    .bind(shared_goal_id)
+    .bind(viewer_id)
+    .fetch_all(db)
+    .await?;
+
+    let linked: Vec<LinkedHabitRow> = sqlx::query_as(
+        r#"SELECT h.id, h.user_id, h.name, h.description,
+                  h.habit_type, h.unit, h.target_value,
+                  h.target_direction,
+                  h.schedule_type, h.schedule_weekdays, h.schedule_times_per_week, h.schedule_interval_days,
+                  h.archived, h.shared_goal_id, h.source_habit_id, h.created_at, h.updated_at,
+                  gh.weight
+           FROM shared_goals sg
+           JOIN goal_habits gh ON gh.goal_id = sg.goal_id
+           JOIN habits h ON h.id = gh.habit_id
+           WHERE sg.id = $1"#,
+    )
+    .bind(shared_goal_id)
+    .fetch_all(db)
+    .await?;
+
+    let habit_ids: Vec<Uuid> = linked.iter().map(|l| l.habit.id).collect();
+    let check_ins = sqlx::query_as::<_, CheckIn>(
+        r#"SELECT id, habit_id, user_id, value, note, effective_date, created_at
+           FROM check_ins WHERE habit_id = ANY($1)
+           ORDER BY effective_date ASC"#,
+    )
+    .bind(&habit_ids)
+    .fetch_all(db)
+    .await?;
+
+    let goal_start = goal_created_at.date_naive();
+    let mut entries: Vec<(bool, LeaderboardEntry)> = participants
+        .into_iter()
+        .map(|p| {
+            let habits: Vec<&LinkedHabitRow> = linked.iter().filter(|l| l.habit.user_id == p.user_id).collect();
+            let entry = participant_entry(&p, &habits, &check_ins, window, goal_start, deadline);
+            (!p.leaderboard_opt_out && entry.linked_habits > 0, entry)
+        })
+        .collect();
+
+    // Ranked participants first, best first
+    entries.sort_by(|(a_ranked, a), (b_ranked, b)| {
+        b_ranked
+            .cmp(a_ranked)
+            .then(b.completion_rate.total_cmp(&a.completion_rate))
+            .then(b.current_streak.cmp(&a.current_streak))
+            .then(b.check_ins.cmp(&a.check_ins))
+    });
+
+    // Ties share a rank
+    let mut previous: Option<((f64, i32, i32), i32)> = None;
+    for (position, (ranked, entry)) in entries.iter_mut().enumerate() {
+        if !*ranked {
+            break;
+        }
+        let key = (entry.completion_rate, entry.current_streak, entry.check_ins);
+        entry.rank = match previous {
+            Some((prev_key, prev_rank)) if prev_key == key => Some(prev_rank),
+            _ => Some(position as i32 + 1),
+        };
+        previous = Some((key, entry.rank.unwrap_or_default()));
+    }
+
+    Ok(Leaderboard {
+        shared_goal_id,
+        window,
+        entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+    })
+}
+
+fn participant_entry(
+    participant: &ParticipantRow,
+    habits: &[&LinkedHabitRow],
+    check_ins: &[CheckIn],
+    window: LeaderboardWindow,
+    goal_start: NaiveDate,
+    deadline: NaiveDate,
+) -> LeaderboardEntry {
+    let today = participant.today;
+    let end = today.min(deadline);
+    let start = match window {
+        LeaderboardWindow::Week => today - Duration::days(6),
+        LeaderboardWindow::Month => today - Duration::days(29),
+        LeaderboardWindow::Goal => goal_start,
+    }
+    .max(goal_start);
+
+    let mut completed_periods = 0;
+    let mut elapsed_periods = 0;
+    let mut weighted_rate = 0.0;
+    let mut total_weight = 0.0;
+    let mut current_streak = 0;
+    let mut longest_streak = 0;
+    let mut check_in_count = 0;
+
+    for linked in habits {
+        let habit = &linked.habit;
+        let weight = linked.weight.max(0.0) as f64;
+        let start = match habit.shared_goal_id {
+            Some(_) => start.max(habit.created_at.date_naive()),
+            None => start,
+        };
+
+        let habit_progress = progress::habit_progress(habit, linked.weight, check_ins, start, end, today);
+        completed_periods += habit_progress.completed_periods;
+        elapsed_periods += habit_progress.elapsed_periods;
+        weighted_rate += weight * streaks::rate(habit_progress.completed_periods, habit_progress.elapsed_periods);
+        total_weight += weight;
+
+        current_streak = current_streak.max(streaks::habit_stats(habit, check_ins, end).current_streak);
+
+        let completed = streaks::completed_dates(habit, check_ins);
+        if start <= end {
+            let periods = streaks::periods(
+                &Schedule::for_habit(habit),
+                habit.created_at.date_naive(),
+                &completed,
+                start,
+                end,
+            );
+            longest_streak = longest_streak.max(streaks::longest_streak(&periods));
+            check_in_count += completed.range(start..=end).count() as i32;
+        }
+    }
+
+    LeaderboardEntry {
+        rank: None,
+        user_id: participant.user_id,
+        name: participant.name.clone(),
+        avatar_url: participant.avatar_url.clone(),
+        start_date: start,
+        end_date: end,
+        linked_habits: habits.len() as i32,
+        completed_periods,
+        elapsed_periods,
+        completion_rate: if total_weight > 0.0 { weighted_rate / total_weight } else { 0.0 },
+        current_streak,
+        longest_streak,
+        check_ins: check_in_count,
+    }
+}
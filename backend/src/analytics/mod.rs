@@ -0,0 +1,9 @@
+//! Analytics derived from check-in data
+//!
+//! Nothing here is stored - values are always computed on demand so that
+//! every client sees the same numbers.
+
+pub mod leaderboard;
+pub mod progress;
+pub mod schedule;
+pub mod streaks;
@@ -0,0 +1,204 @@
+//! Goal progress computed from linked habits
+//!
+//! Each linked habit contributes the share of its scheduled periods between
+//! the goal's creation and its deadline that were satisfied, weighted by
+//! `goal_habits.weight`. Habits copied by shared goal participants count
+//! from the day they were copied.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+use super::{schedule::Schedule, streaks};
+use crate::{
+    db,
+    error::ApiResult,
+    models::{CheckIn, Goal, GoalDay, GoalHabitProgress, GoalProgress, Habit},
+};
+
+/// Projected completion (in percent) needed to count as on track
+pub const DEFAULT_ON_TRACK_PERCENTAGE: f64 = 80.0;
+
+/// Progress of a single habit over `start..=deadline`, as of `today`
+pub fn habit_progress(
+    habit: &Habit,
+    weight: f32,
+    check_ins: &[CheckIn],
+    start: NaiveDate,
+    deadline: NaiveDate,
+    today: NaiveDate,
+) -> GoalHabitProgress {
+    let schedule = Schedule::for_habit(habit);
+    let anchor = habit.created_at.date_naive();
+    let completed = streaks::completed_dates(habit, check_ins);
+    let as_of = today.min(deadline);
+
+    let (completed_periods, elapsed_periods) = if as_of < start {
+        (0, 0)
+    } else {
+        let periods = streaks::periods(&schedule, anchor, &completed, start, as_of);
+        streaks::completion(&periods, as_of)
+    };
+    let total_periods =
+        streaks::periods(&schedule, anchor, &Default::default(), start, deadline).len() as i32;
+
+    GoalHabitProgress {
+        habit_id: habit.id,
+        user_id: habit.user_id,
+        habit_name: habit.name.clone(),
+        weight,
+        completed_periods,
+        elapsed_periods,
+        total_periods,
+        percentage: streaks::rate(completed_periods, total_periods) * 100.0,
+        projected_percentage: streaks::rate(completed_periods, elapsed_periods) * 100.0,
+    }
+}
+
+/// Combine per-habit progress into weighted goal progress
+pub fn goal_progress(
+    goal: &Goal,
+    habits: Vec<GoalHabitProgress>,
+    today: NaiveDate,
+    on_track_percentage: f64,
+) -> GoalProgress {
+    let total_weight: f64 = habits.iter().map(|h| h.weight.max(0.0) as f64).sum();
+    let weighted = |f: fn(&GoalHabitProgress) -> f64| {
+        if total_weight > 0.0 {
+            habits.iter().map(|h| h.weight.max(0.0) as f64 * f(h)).sum::<f64>() / total_weight
+        } else {
+            0.0
+        }
+    };
+
+    let percentage = weighted(|h| h.percentage);
+    let projected_percentage = weighted(|h| h.projected_percentage);
+
+    GoalProgress {
+        goal_id: goal.id,
+        start_date: goal.created_at.date_naive(),
+        deadline: goal.deadline,
+        days_remaining: (goal.deadline - today).num_days().max(0),
+        percentage,
+        projected_percentage,
+        on_track: !habits.is_empty() && projected_percentage >= on_track_percentage,
+        habits,
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct LinkedHabitRow {
+    #[sqlx(flatten)]
+    habit: Habit,
+    weight: f32,
+}
+
+/// Load linked habits and check-ins for a goal and compute its progress
+pub async fn load_goal_progress(
+    db: &sqlx::PgPool,
+    goal: &Goal,
+    on_track_percentage: f64,
+) -> ApiResult<GoalProgress> {
+    let today = db::local_today(db, goal.user_id).await?;
+    let (linked, check_ins) = load_linked_habits(db, goal, None).await?;
+
+    Ok(linked_progress(goal, &linked, &check_ins, today, on_track_percentage))
+}
+
+/// Progress along with a day-by-day count of completed habits, counting
+/// only the owner's habits. Shared goal participants' copies stay out, since
+/// they never agreed to the owner publishing them.
+pub async fn load_goal_days(
+    db: &sqlx::PgPool,
+    goal: &Goal,
+    on_track_percentage: f64,
+) -> ApiResult<(GoalProgress, Vec<GoalDay>)> {
+    let today = db::local_today(db, goal.user_id).await?;
+    let (linked, check_ins) = load_linked_habits(db, goal, Some(goal.user_id)).await?;
+
+    let habits: Vec<&Habit> = linked.iter().map(|l| &l.habit).collect();
+    let days = day_grid(&habits, &check_ins, goal.created_at.date_naive(), today.min(goal.deadline));
+
+    Ok((linked_progress(goal, &linked, &check_ins, today, on_track_percentage), days))
+}
+
+/// How many of `habits` were completed on each day of `start..=end`
+fn day_grid(habits: &[&Habit], check_ins: &[CheckIn], start: NaiveDate, end: NaiveDate) -> Vec<GoalDay> {
+    let mut completed: BTreeMap<NaiveDate, i32> = BTreeMap::new();
+    for habit in habits {
+        for date in streaks::completed_dates(habit, check_ins) {
+            *completed.entry(date).or_default() += 1;
+        }
+    }
+
+    start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .map(|date| GoalDay {
+            date,
+            completed: completed.get(&date).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+fn linked_progress(
+    goal: &Goal,
+    linked: &[LinkedHabitRow],
+    check_ins: &[CheckIn],
+    today: NaiveDate,
+    on_track_percentage: f64,
+) -> GoalProgress {
+    let start = goal.created_at.date_naive();
+    let habits = linked
+        .iter()
+        .map(|l| {
+            let start = match l.habit.shared_goal_id {
+                Some(_) => start.max(l.habit.created_at.date_naive()),
+                None => start,
+            };
+            habit_progress(&l.habit, l.weight, check_ins, start, goal.deadline, today)
+        })
+        .collect();
+
+    goal_progress(goal, habits, today, on_track_percentage)
+}
+
+/// The goal's linked habits, only those tracked by `user_id` when given, and
+/// their check-ins while the goal runs
+async fn load_linked_habits(
+    db: &sqlx::PgPool,
+    goal: &Goal,
+    user_id: Option<Uuid>,
+) -> ApiResult<(Vec<LinkedHabitRow>, Vec<CheckIn>)> {
+    let linked: Vec<LinkedHabitRow> = sqlx::query_as(
+        r#"SELECT h.id, h.user_id, h.name, h.description,
+                  h.habit_type, h.unit, h.target_value,
+                  h.target_direction,
+                  h.schedule_type, h.schedule_weekdays, h.schedule_times_per_week, h.schedule_interval_days,
+                  h.archived, h.shared_goal_id, h.source_habit_id, h.created_at, h.updated_at,
+                  gh.weight
+           FROM goal_habits gh
+           JOIN habits h ON h.id = gh.habit_id
+           WHERE gh.goal_id = $1 AND ($2::uuid IS NULL OR h.user_id = $2)
+           ORDER BY h.created_at ASC"#,
+    )
+    .bind(goal.id)
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let habit_ids: Vec<Uuid> = linked.iter().map(|l| l.habit.id).collect();
+    let check_ins = sqlx::query_as::<_, CheckIn>(
+        r#"SELECT id, habit_id, user_id, value, note, effective_date, created_at
+           FROM check_ins
+           WHERE habit_id = ANY($1) AND effective_date BETWEEN $2 AND $3"#,
+    )
+    .bind(&habit_ids)
+    .bind(goal.created_at.date_naive())
+    .bind(goal.deadline)
+    .fetch_all(db)
+    .await?;
+
+    Ok((linked, check_ins))
+}
@@ -0,0 +1,172 @@
+//! Habit schedules
+//!
+//! A schedule splits the calendar into periods. Each period has to collect a
+//! number of completions to count as satisfied; days that fall outside every
+//! period (e.g. a Tuesday for a Mon/Wed/Fri habit) neither help nor hurt.
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::models::{Habit, ScheduleType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Daily,
+    /// ISO weekdays (1 = Monday ... 7 = Sunday)
+    Weekdays(Vec<u32>),
+    /// Monday-based weeks needing this many completions
+    TimesPerWeek(u32),
+    /// Blocks of this many days, anchored at the habit's start date
+    EveryNDays(u32),
+}
+
+impl Schedule {
+    /// Build a schedule from its stored parts, rejecting invalid combinations
+    pub fn from_parts(
+        schedule_type: &ScheduleType,
+        weekdays: Option<&[i32]>,
+        times_per_week: Option<i32>,
+        interval_days: Option<i32>,
+    ) -> Result<Self, String> {
+        match schedule_type {
+            ScheduleType::Daily => Ok(Schedule::Daily),
+            ScheduleType::Weekdays => {
+                let days = weekdays
+                    .filter(|d| !d.is_empty())
+                    .ok_or("schedule_weekdays is required for a weekdays schedule")?;
+                if days.iter().any(|d| !(1..=7).contains(d)) {
+                    return Err("schedule_weekdays must be between 1 (Monday) and 7 (Sunday)".into());
+                }
+                let mut days: Vec<u32> = days.iter().map(|&d| d as u32).collect();
+                days.sort_unstable();
+                days.dedup();
+                Ok(Schedule::Weekdays(days))
+            }
+            ScheduleType::TimesPerWeek => match times_per_week {
+                Some(n @ 1..=7) => Ok(Schedule::TimesPerWeek(n as u32)),
+                _ => Err("schedule_times_per_week must be between 1 and 7".into()),
+            },
+            ScheduleType::EveryNDays => match interval_days {
+                Some(n) if n >= 1 => Ok(Schedule::EveryNDays(n as u32)),
+                _ => Err("schedule_interval_days must be at least 1".into()),
+            },
+        }
+    }
+
+    /// Schedule stored on a habit; invalid stored data falls back to daily
+    pub fn for_habit(habit: &Habit) -> Self {
+        Self::from_parts(
+            &habit.schedule_type,
+            habit.schedule_weekdays.as_deref(),
+            habit.schedule_times_per_week,
+            habit.schedule_interval_days,
+        )
+        .unwrap_or(Schedule::Daily)
+    }
+
+    /// Completions needed for a period to be satisfied
+    pub fn required(&self) -> i32 {
+        match self {
+            Schedule::TimesPerWeek(n) => *n as i32,
+            _ => 1,
+        }
+    }
+
+    /// Inclusive bounds of the period containing `day`, or `None` if the
+    /// habit isn't scheduled on that day
+    pub fn period_of(&self, day: NaiveDate, anchor: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        match self {
+            Schedule::Daily => Some((day, day)),
+            Schedule::Weekdays(days) => days
+                .contains(&day.weekday().number_from_monday())
+                .then_some((day, day)),
+            Schedule::TimesPerWeek(_) => {
+                let start = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+                Some((start, start + Duration::days(6)))
+            }
+            Schedule::EveryNDays(n) => {
+                let n = *n as i64;
+                let offset = (day - anchor).num_days().rem_euclid(n);
+                let start = day - Duration::days(offset);
+                Some((start, start + Duration::days(n - 1)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::analytics::streaks;
+
+    /// June 2nd 2025 is a Monday
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    fn dates(days: &[u32]) -> BTreeSet<NaiveDate> {
+        days.iter().map(|&day| date(day)).collect()
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        assert!(Schedule::from_parts(&ScheduleType::Weekdays, None, None, None).is_err());
+        assert!(Schedule::from_parts(&ScheduleType::Weekdays, Some(&[]), None, None).is_err());
+        assert!(Schedule::from_parts(&ScheduleType::Weekdays, Some(&[0, 3]), None, None).is_err());
+        assert!(Schedule::from_parts(&ScheduleType::TimesPerWeek, None, Some(8), None).is_err());
+        assert!(Schedule::from_parts(&ScheduleType::EveryNDays, None, None, Some(0)).is_err());
+
+        assert_eq!(
+            Schedule::from_parts(&ScheduleType::Weekdays, Some(&[5, 1, 3, 1]), None, None),
+            Ok(Schedule::Weekdays(vec![1, 3, 5])),
+        );
+    }
+
+    #[test]
+    fn periods_follow_the_schedule() {
+        let anchor = date(2);
+
+        let weekdays = Schedule::Weekdays(vec![1, 3, 5]);
+        assert_eq!(weekdays.period_of(date(4), anchor), Some((date(4), date(4))));
+        assert_eq!(weekdays.period_of(date(3), anchor), None);
+
+        // Weeks start on Monday whatever the anchor
+        let weekly = Schedule::TimesPerWeek(3);
+        assert_eq!(weekly.period_of(date(5), date(4)), Some((date(2), date(8))));
+        assert_eq!(weekly.period_of(date(9), date(4)), Some((date(9), date(15))));
+
+        let every_three = Schedule::EveryNDays(3);
+        assert_eq!(every_three.period_of(date(6), anchor), Some((date(5), date(7))));
+        let may_30 = NaiveDate::from_ymd_opt(2025, 5, 30).unwrap();
+        assert_eq!(every_three.period_of(date(1), anchor), Some((may_30, date(1))));
+    }
+
+    #[test]
+    fn unscheduled_days_do_not_break_a_weekday_streak() {
+        let schedule = Schedule::Weekdays(vec![1, 3, 5]);
+        let periods = streaks::periods(&schedule, date(2), &dates(&[2, 4, 6, 9]), date(2), date(10));
+
+        assert_eq!(periods.len(), 4);
+        assert_eq!(streaks::current_streak(&periods, date(10)), 4);
+    }
+
+    #[test]
+    fn weekly_streaks_count_weeks_with_enough_completions() {
+        let schedule = Schedule::TimesPerWeek(2);
+        let completed = dates(&[2, 6, 10, 11, 17]);
+        let periods = streaks::periods(&schedule, date(2), &completed, date(2), date(18));
+
+        assert_eq!(periods.len(), 3);
+        assert!(periods[0].is_satisfied() && periods[1].is_satisfied());
+        assert!(!periods[2].is_satisfied());
+
+        // The current week can still be completed
+        assert_eq!(streaks::current_streak(&periods, date(18)), 2);
+        assert_eq!(streaks::completion(&periods, date(18)), (2, 2));
+
+        // Once it has ended without enough completions the streak is over
+        let periods = streaks::periods(&schedule, date(2), &completed, date(2), date(23));
+        assert_eq!(streaks::current_streak(&periods, date(23)), 0);
+    }
+}
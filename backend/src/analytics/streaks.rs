@@ -0,0 +1,267 @@
+//! Streak and completion calculations
+//!
+//! Streaks count consecutive satisfied schedule periods, so a day the habit
+//! isn't scheduled never breaks a streak.
+
+use std::collections::BTreeSet;
+
+use chrono::{Duration, NaiveDate};
+
+use super::schedule::Schedule;
+use crate::models::{CheckIn, Habit, HabitStats, TargetDirection};
+
+/// A schedule period and the completions that fell inside it
+#[derive(Debug, Clone)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub completed: i32,
+    pub required: i32,
+}
+
+impl Period {
+    pub fn is_satisfied(&self) -> bool {
+        self.completed >= self.required
+    }
+
+    /// A period that hasn't ended yet may still be satisfied later
+    fn is_open(&self, as_of: NaiveDate) -> bool {
+        self.end >= as_of && !self.is_satisfied()
+    }
+}
+
+/// Check if a value meets the habit's target criteria
+pub fn is_completed(habit: &Habit, value: i32) -> bool {
+    if value == 0 {
+        return false;
+    }
+
+    match habit.target_value {
+        None => value > 0,
+        Some(target) => match habit.target_direction {
+            TargetDirection::AtLeast => value >= target,
+            TargetDirection::AtMost => value <= target,
+            TargetDirection::Exactly => value == target,
+        },
+    }
+}
+
+/// Dates on which the habit's target was met
+pub fn completed_dates(habit: &Habit, check_ins: &[CheckIn]) -> BTreeSet<NaiveDate> {
+    check_ins
+        .iter()
+        .filter(|c| c.habit_id == habit.id && is_completed(habit, c.value))
+        .map(|c| c.effective_date)
+        .collect()
+}
+
+/// Scheduled periods touching `start..=as_of`, with completions up to `as_of`
+pub fn periods(
+    schedule: &Schedule,
+    anchor: NaiveDate,
+    completed: &BTreeSet<NaiveDate>,
+    start: NaiveDate,
+    as_of: NaiveDate,
+) -> Vec<Period> {
+    let mut periods: Vec<Period> = Vec::new();
+    let mut day = start;
+
+    while day <= as_of {
+        if let Some((p_start, p_end)) = schedule.period_of(day, anchor) {
+            if periods.last().is_none_or(|p| p.start != p_start) {
+                let done = completed.range(p_start..=p_end.min(as_of)).count() as i32;
+                periods.push(Period {
+                    start: p_start,
+                    end: p_end,
+                    completed: done,
+                    required: schedule.required(),
+                });
+            }
+        }
+        day += Duration::days(1);
+    }
+
+    periods
+}
+
+/// Consecutive satisfied periods ending at `as_of`; an unfinished current
+/// period doesn't break the streak
+pub fn current_streak(periods: &[Period], as_of: NaiveDate) -> i32 {
+    let mut iter = periods.iter().rev().peekable();
+    if iter.peek().is_some_and(|p| p.is_open(as_of)) {
+        iter.next();
+    }
+
+    iter.take_while(|p| p.is_satisfied()).count() as i32
+}
+
+/// Longest run of consecutive satisfied periods
+pub fn longest_streak(periods: &[Period]) -> i32 {
+    let mut longest = 0;
+    let mut run = 0;
+
+    for period in periods {
+        run = if period.is_satisfied() { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+
+    longest
+}
+
+/// Satisfied and elapsed periods; an unfinished current period only counts
+/// once it is satisfied
+pub fn completion(periods: &[Period], as_of: NaiveDate) -> (i32, i32) {
+    let done = periods.iter().filter(|p| p.is_satisfied()).count() as i32;
+    let total = periods.iter().filter(|p| !p.is_open(as_of)).count() as i32;
+
+    (done, total)
+}
+
+/// Completion ratio in `0.0..=1.0`
+pub fn rate(done: i32, total: i32) -> f64 {
+    if total > 0 {
+        done as f64 / total as f64
+    } else {
+        0.0
+    }
+}
+
+/// Compute full statistics for a habit as of `today`
+pub fn habit_stats(habit: &Habit, check_ins: &[CheckIn], today: NaiveDate) -> HabitStats {
+    let schedule = Schedule::for_habit(habit);
+    let completed = completed_dates(habit, check_ins);
+    let anchor = habit.created_at.date_naive();
+
+    // Check-ins can predate the server-side habit (e.g. imported via sync)
+    let start = check_ins
+        .iter()
+        .map(|c| c.effective_date)
+        .chain(std::iter::once(anchor))
+        .min()
+        .unwrap_or(today);
+
+    let periods = periods(&schedule, anchor, &completed, start, today);
+    let (completed_periods, total_periods) = completion(&periods, today);
+
+    HabitStats {
+        habit_id: habit.id,
+        schedule_type: habit.schedule_type.clone(),
+        current_streak: current_streak(&periods, today),
+        longest_streak: longest_streak(&periods),
+        completed_periods,
+        total_periods,
+        completion_rate: rate(completed_periods, total_periods),
+        last_completed_date: completed.range(..=today).next_back().copied(),
+        is_active_today: completed.contains(&today),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::models::{HabitType, ScheduleType};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    /// A daily habit created on June 1st
+    fn habit(target_value: Option<i32>, target_direction: TargetDirection) -> Habit {
+        let created_at = Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+        Habit {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "Walk".to_string(),
+            description: None,
+            habit_type: HabitType::Numeric,
+            unit: None,
+            target_value,
+            target_direction,
+            schedule_type: ScheduleType::Daily,
+            schedule_weekdays: None,
+            schedule_times_per_week: None,
+            schedule_interval_days: None,
+            archived: false,
+            shared_goal_id: None,
+            source_habit_id: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn check_ins(habit: &Habit, days: &[(u32, i32)]) -> Vec<CheckIn> {
+        days.iter()
+            .map(|&(day, value)| CheckIn {
+                id: Uuid::new_v4(),
+                habit_id: habit.id,
+                user_id: habit.user_id,
+                value,
+                note: None,
+                effective_date: date(day),
+                created_at: habit.created_at,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn completion_follows_the_target_direction() {
+        let plain = habit(None, TargetDirection::AtLeast);
+        assert!(is_completed(&plain, 1));
+        assert!(!is_completed(&plain, 0));
+
+        let at_least = habit(Some(5), TargetDirection::AtLeast);
+        assert!(is_completed(&at_least, 5));
+        assert!(!is_completed(&at_least, 4));
+
+        let at_most = habit(Some(2), TargetDirection::AtMost);
+        assert!(is_completed(&at_most, 2));
+        assert!(!is_completed(&at_most, 3));
+        assert!(!is_completed(&at_most, 0));
+
+        let exactly = habit(Some(3), TargetDirection::Exactly);
+        assert!(is_completed(&exactly, 3));
+        assert!(!is_completed(&exactly, 4));
+    }
+
+    #[test]
+    fn an_unfinished_today_does_not_break_the_streak() {
+        let habit = habit(None, TargetDirection::AtLeast);
+        let check_ins = check_ins(&habit, &[(1, 1), (2, 1), (3, 1), (5, 1), (6, 1), (7, 1), (8, 1)]);
+
+        let stats = habit_stats(&habit, &check_ins, date(9));
+        assert_eq!(stats.current_streak, 4);
+        assert_eq!(stats.longest_streak, 4);
+        assert_eq!((stats.completed_periods, stats.total_periods), (7, 8));
+        assert_eq!(stats.last_completed_date, Some(date(8)));
+        assert!(!stats.is_active_today);
+
+        // Once today is over without a check-in the streak is gone
+        assert_eq!(habit_stats(&habit, &check_ins, date(10)).current_streak, 0);
+    }
+
+    #[test]
+    fn check_ins_missing_the_target_break_the_streak() {
+        let habit = habit(Some(3), TargetDirection::AtLeast);
+        let check_ins = check_ins(&habit, &[(1, 3), (2, 3), (3, 1), (4, 4)]);
+
+        let stats = habit_stats(&habit, &check_ins, date(4));
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.longest_streak, 2);
+        assert!(stats.is_active_today);
+        assert_eq!(stats.completion_rate, 0.75);
+    }
+
+    #[test]
+    fn check_ins_before_the_habit_was_created_count() {
+        let habit = habit(None, TargetDirection::AtLeast);
+        let mut check_ins = check_ins(&habit, &[(1, 1), (2, 1), (2, 1)]);
+        check_ins[2].effective_date = NaiveDate::from_ymd_opt(2025, 5, 31).unwrap();
+
+        let stats = habit_stats(&habit, &check_ins, date(2));
+        assert_eq!(stats.current_streak, 3);
+        assert_eq!(stats.total_periods, 3);
+    }
+}
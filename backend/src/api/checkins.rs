@@ -7,15 +7,20 @@ use axum::{
 };
 use chrono::NaiveDate;
 use serde::Deserialize;
+use sqlx::FromRow;
 use uuid::Uuid;
 
 use crate::{
+    activity,
     auth::middleware::AuthUser,
+    db,
     error::{ApiError, ApiResult},
     models::*,
     AppState,
 };
 
+use super::pagination::{Pagination, Sort, SortOrder};
+
 pub fn routes() -> Router {
     Router::new()
         .route("/", get(list_checkins).post(create_checkin))
@@ -23,36 +28,77 @@ pub fn routes() -> Router {
         .route("/date/:date", get(get_checkins_for_date))
 }
 
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CheckInSort {
+    /// Latest day first by default
+    #[default]
+    EffectiveDate,
+    CreatedAt,
+}
+
+impl CheckInSort {
+    fn sort(self, order: Option<SortOrder>) -> Sort {
+        let (name, column, sql_type, default) = match self {
+            CheckInSort::EffectiveDate => ("effective_date", "c.effective_date", "date", SortOrder::Desc),
+            CheckInSort::CreatedAt => ("created_at", "c.created_at", "timestamptz", SortOrder::Desc),
+        };
+        Sort::new(name, column, sql_type, order.unwrap_or(default))
+    }
+
+    fn key(self, check_in: &CheckIn) -> String {
+        match self {
+            CheckInSort::EffectiveDate => check_in.effective_date.to_string(),
+            CheckInSort::CreatedAt => check_in.created_at.to_rfc3339(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CheckInQuery {
     pub habit_id: Option<Uuid>,
     pub start_date: Option<NaiveDate>,
     pub end_date: Option<NaiveDate>,
+    #[serde(default)]
+    sort: CheckInSort,
+    order: Option<SortOrder>,
 }
 
 async fn list_checkins(
     Extension(state): Extension<AppState>,
     user: AuthUser,
     Query(query): Query<CheckInQuery>,
-) -> ApiResult<Json<Vec<CheckIn>>> {
-    let checkins = sqlx::query_as::<_, CheckIn>(
+    pagination: Pagination,
+) -> ApiResult<Json<Page<CheckIn>>> {
+    let sort = query.sort.sort(query.order);
+    let (after_key, after_id) = pagination.after(&sort)?;
+
+    let sql = format!(
         r#"SELECT c.id, c.habit_id, c.user_id, c.value, c.note, c.effective_date, c.created_at
            FROM check_ins c
-           JOIN habits h ON h.id = c.habit_id
            WHERE c.user_id = $1
              AND ($2::uuid IS NULL OR c.habit_id = $2)
              AND ($3::date IS NULL OR c.effective_date >= $3)
              AND ($4::date IS NULL OR c.effective_date <= $4)
-           ORDER BY c.effective_date DESC, c.created_at DESC"#,
-    )
-    .bind(user.user_id)
-    .bind(query.habit_id)
-    .bind(query.start_date)
-    .bind(query.end_date)
-    .fetch_all(&state.db)
-    .await?;
+             AND {after}
+           ORDER BY {order_by}
+           LIMIT $7"#,
+        after = sort.after("c.id", 5, 6),
+        order_by = sort.order_by("c.id"),
+    );
 
-    Ok(Json(checkins))
+    let checkins = sqlx::query_as::<_, CheckIn>(&sql)
+        .bind(user.user_id)
+        .bind(query.habit_id)
+        .bind(query.start_date)
+        .bind(query.end_date)
+        .bind(after_key)
+        .bind(after_id)
+        .bind(pagination.fetch_limit())
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(pagination.page(checkins, &sort, |c| (query.sort.key(c), c.id))))
 }
 
 async fn get_checkins_for_date(
@@ -74,6 +120,14 @@ async fn get_checkins_for_date(
     Ok(Json(checkins))
 }
 
+#[derive(Debug, FromRow)]
+struct ChangedCheckIn {
+    #[sqlx(flatten)]
+    check_in: CheckIn,
+    /// The value before the change, unless the check-in was just created
+    previous_value: Option<i32>,
+}
+
 async fn create_checkin(
     Extension(state): Extension<AppState>,
     user: AuthUser,
@@ -92,14 +146,27 @@ async fn create_checkin(
         return Err(ApiError::NotFound);
     }
 
+    let today = db::local_today(&state.db, user.user_id).await?;
+    if body.effective_date > today {
+        return Err(ApiError::BadRequest("Cannot check in for a future date".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
     // Upsert check-in (one per habit per day)
-    let checkin = sqlx::query_as::<_, CheckIn>(
-        r#"INSERT INTO check_ins (id, habit_id, user_id, value, note, effective_date, created_at)
+    let upserted = sqlx::query_as::<_, ChangedCheckIn>(
+        r#"WITH previous AS (
+               SELECT value FROM check_ins
+               WHERE habit_id = $2 AND effective_date = $6
+               FOR UPDATE
+           )
+           INSERT INTO check_ins (id, habit_id, user_id, value, note, effective_date, created_at)
            VALUES ($1, $2, $3, $4, $5, $6, NOW())
            ON CONFLICT (habit_id, effective_date) DO UPDATE SET
                value = EXCLUDED.value,
                note = COALESCE(EXCLUDED.note, check_ins.note)
-           RETURNING id, habit_id, user_id, value, note, effective_date, created_at"#,
+           RETURNING id, habit_id, user_id, value, note, effective_date, created_at,
+                     (SELECT value FROM previous) AS previous_value"#,
     )
     .bind(Uuid::new_v4())
     .bind(body.habit_id)
@@ -107,10 +174,14 @@ async fn create_checkin(
     .bind(body.value)
     .bind(&body.note)
     .bind(body.effective_date)
-    .fetch_one(&state.db)
+    .fetch_one(&mut *tx)
     .await?;
 
-    Ok(Json(checkin))
+    activity::check_in_recorded(&mut tx, &upserted.check_in, upserted.previous_value, today).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(upserted.check_in))
 }
 
 async fn update_checkin(
@@ -119,22 +190,36 @@ async fn update_checkin(
     Path(id): Path<Uuid>,
     Json(body): Json<UpdateCheckInRequest>,
 ) -> ApiResult<Json<CheckIn>> {
-    let checkin = sqlx::query_as::<_, CheckIn>(
-        r#"UPDATE check_ins SET
-           value = COALESCE($3, value),
-           note = COALESCE($4, note)
-           WHERE id = $1 AND user_id = $2
-           RETURNING id, habit_id, user_id, value, note, effective_date, created_at"#,
+    let today = db::local_today(&state.db, user.user_id).await?;
+    let mut tx = state.db.begin().await?;
+
+    let updated = sqlx::query_as::<_, ChangedCheckIn>(
+        r#"WITH previous AS (
+               SELECT id, value FROM check_ins
+               WHERE id = $1 AND user_id = $2
+               FOR UPDATE
+           )
+           UPDATE check_ins c SET
+           value = COALESCE($3, c.value),
+           note = COALESCE($4, c.note)
+           FROM previous p
+           WHERE c.id = p.id
+           RETURNING c.id, c.habit_id, c.user_id, c.value, c.note, c.effective_date, c.created_at,
+                     p.value AS previous_value"#,
     )
     .bind(id)
     .bind(user.user_id)
     .bind(body.value)
     .bind(&body.note)
-    .fetch_optional(&state.db)
+    .fetch_optional(&mut *tx)
     .await?
     .ok_or(ApiError::NotFound)?;
 
-    Ok(Json(checkin))
+    activity::check_in_recorded(&mut tx, &updated.check_in, updated.previous_value, today).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(updated.check_in))
 }
 
 async fn delete_checkin(
@@ -154,3 +239,86 @@ async fn delete_checkin(
 
     Ok(Json(serde_json::json!({ "deleted": true })))
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support::{app_state, create_goal, create_habit, create_user, share_goal};
+
+    /// A habit with a target of 3, linked to a shared goal
+    async fn shared_habit(db: &PgPool, user: &AuthUser) -> Uuid {
+        let habit_id = create_habit(db, user.user_id, "Pages", 10).await;
+        sqlx::query("UPDATE habits SET target_value = 3 WHERE id = $1")
+            .bind(habit_id)
+            .execute(db)
+            .await
+            .unwrap();
+
+        let goal_id = create_goal(db, user.user_id, "Read more", &[habit_id]).await;
+        share_goal(db, user.user_id, goal_id).await;
+
+        habit_id
+    }
+
+    async fn record(db: &PgPool, user: &AuthUser, habit_id: Uuid, value: i32) -> CheckIn {
+        let today = db::local_today(db, user.user_id).await.unwrap();
+        let body = CreateCheckInRequest { habit_id, value, note: None, effective_date: today };
+        let Json(check_in) = create_checkin(Extension(app_state(db.clone())), user.clone(), Json(body))
+            .await
+            .unwrap();
+        check_in
+    }
+
+    async fn update(db: &PgPool, user: &AuthUser, id: Uuid, value: i32) {
+        let body = UpdateCheckInRequest { value: Some(value), note: None };
+        let _ = update_checkin(Extension(app_state(db.clone())), user.clone(), Path(id), Json(body))
+            .await
+            .unwrap();
+    }
+
+    async fn feed_posts(db: &PgPool, check_in_id: Uuid) -> i64 {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM shared_activities WHERE check_in_id = $1 AND activity_type = 'check_in'",
+        )
+        .bind(check_in_id)
+        .fetch_one(db)
+        .await
+        .unwrap();
+        count
+    }
+
+    #[sqlx::test]
+    async fn updating_a_check_in_to_complete_posts_it_once(db: PgPool) {
+        let user = create_user(&db, "reader@example.com").await;
+        let habit_id = shared_habit(&db, &user).await;
+
+        let check_in = record(&db, &user, habit_id, 1).await;
+        assert_eq!(feed_posts(&db, check_in.id).await, 0);
+
+        update(&db, &user, check_in.id, 3).await;
+        assert_eq!(feed_posts(&db, check_in.id).await, 1);
+
+        update(&db, &user, check_in.id, 4).await;
+        update(&db, &user, check_in.id, 1).await;
+        update(&db, &user, check_in.id, 3).await;
+        assert_eq!(feed_posts(&db, check_in.id).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn checking_in_again_to_complete_posts_it_once(db: PgPool) {
+        let user = create_user(&db, "reader@example.com").await;
+        let habit_id = shared_habit(&db, &user).await;
+
+        let check_in = record(&db, &user, habit_id, 2).await;
+        assert_eq!(feed_posts(&db, check_in.id).await, 0);
+
+        let again = record(&db, &user, habit_id, 3).await;
+        assert_eq!(again.id, check_in.id);
+        assert_eq!(feed_posts(&db, check_in.id).await, 1);
+
+        record(&db, &user, habit_id, 5).await;
+        assert_eq!(feed_posts(&db, check_in.id).await, 1);
+    }
+}
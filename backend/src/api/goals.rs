@@ -1,42 +1,103 @@
 //! Goals API
 
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     routing::get,
     Extension, Json, Router,
 };
+use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
-    auth::middleware::AuthUser,
+    analytics::progress,
+    auth::{jwt, middleware::AuthUser},
     error::{ApiError, ApiResult},
     models::*,
     AppState,
 };
 
+use super::pagination::{Pagination, Sort, SortOrder};
+
 pub fn routes() -> Router {
     Router::new()
         .route("/", get(list_goals).post(create_goal))
         .route("/:id", get(get_goal).put(update_goal).delete(delete_goal))
+        .route("/:id/progress", get(get_goal_progress))
         .route("/:id/habits", get(get_goal_habits).post(link_habit))
         .route("/:id/habits/:habit_id", axum::routing::delete(unlink_habit))
+        .route(
+            "/:id/public-link",
+            get(get_public_link).post(create_public_link).delete(revoke_public_link),
+        )
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GoalSort {
+    /// Soonest first by default
+    #[default]
+    Deadline,
+    CreatedAt,
+    Name,
+}
+
+impl GoalSort {
+    fn sort(self, order: Option<SortOrder>) -> Sort {
+        let (name, column, sql_type, default) = match self {
+            GoalSort::Deadline => ("deadline", "deadline", "date", SortOrder::Asc),
+            GoalSort::CreatedAt => ("created_at", "created_at", "timestamptz", SortOrder::Desc),
+            GoalSort::Name => ("name", "name", "text", SortOrder::Asc),
+        };
+        Sort::new(name, column, sql_type, order.unwrap_or(default))
+    }
+
+    fn key(self, goal: &Goal) -> String {
+        match self {
+            GoalSort::Deadline => goal.deadline.to_string(),
+            GoalSort::CreatedAt => goal.created_at.to_rfc3339(),
+            GoalSort::Name => goal.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GoalListQuery {
+    status: Option<GoalStatus>,
+    #[serde(default)]
+    sort: GoalSort,
+    order: Option<SortOrder>,
 }
 
 async fn list_goals(
     Extension(state): Extension<AppState>,
     user: AuthUser,
-) -> ApiResult<Json<Vec<Goal>>> {
-    let goals = sqlx::query_as::<_, Goal>(
+    Query(query): Query<GoalListQuery>,
+    pagination: Pagination,
+) -> ApiResult<Json<Page<Goal>>> {
+    let sort = query.sort.sort(query.order);
+    let (after_key, after_id) = pagination.after(&sort)?;
+
+    let sql = format!(
         r#"SELECT id, user_id, name, description, deadline,
            status, is_shared, created_at, updated_at
-           FROM goals WHERE user_id = $1
-           ORDER BY deadline ASC"#,
-    )
-    .bind(user.user_id)
-    .fetch_all(&state.db)
-    .await?;
+           FROM goals
+           WHERE user_id = $1 AND ($2::goal_status IS NULL OR status = $2) AND {after}
+           ORDER BY {order_by}
+           LIMIT $5"#,
+        after = sort.after("id", 3, 4),
+        order_by = sort.order_by("id"),
+    );
+
+    let goals = sqlx::query_as::<_, Goal>(&sql)
+        .bind(user.user_id)
+        .bind(&query.status)
+        .bind(after_key)
+        .bind(after_id)
+        .bind(pagination.fetch_limit())
+        .fetch_all(&state.db)
+        .await?;
 
-    Ok(Json(goals))
+    Ok(Json(pagination.page(goals, &sort, |g| (query.sort.key(g), g.id))))
 }
 
 async fn create_goal(
@@ -94,6 +155,28 @@ async fn get_goal(
     Ok(Json(goal))
 }
 
+async fn get_goal_progress(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<GoalProgress>> {
+    let goal = sqlx::query_as::<_, Goal>(
+        r#"SELECT id, user_id, name, description, deadline,
+           status, is_shared, created_at, updated_at
+           FROM goals WHERE id = $1 AND user_id = $2"#,
+    )
+    .bind(id)
+    .bind(user.user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let progress =
+        progress::load_goal_progress(&state.db, &goal, progress::DEFAULT_ON_TRACK_PERCENTAGE).await?;
+
+    Ok(Json(progress))
+}
+
 async fn update_goal(
     Extension(state): Extension<AppState>,
     user: AuthUser,
@@ -231,3 +314,92 @@ async fn unlink_habit(
 
     Ok(Json(serde_json::json!({ "unlinked": true })))
 }
+
+async fn get_public_link(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(goal_id): Path<Uuid>,
+) -> ApiResult<Json<PublicLinkResponse>> {
+    let link = sqlx::query_as::<_, GoalPublicLink>(
+        r#"SELECT l.id, l.goal_id, l.created_by, l.created_at, l.revoked_at
+           FROM goal_public_links l
+           JOIN goals g ON g.id = l.goal_id
+           WHERE l.goal_id = $1 AND g.user_id = $2 AND l.revoked_at IS NULL"#,
+    )
+    .bind(goal_id)
+    .bind(user.user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    public_link_response(&link, &state.jwt_secret).map(Json)
+}
+
+/// Publish a read-only link to the goal's progress. Any previous link is
+/// revoked, so this also rotates a leaked link.
+async fn create_public_link(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(goal_id): Path<Uuid>,
+) -> ApiResult<Json<PublicLinkResponse>> {
+    let mut tx = state.db.begin().await?;
+
+    let goal_exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM goals WHERE id = $1 AND user_id = $2 FOR UPDATE")
+        .bind(goal_id)
+        .bind(user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    if goal_exists.is_none() {
+        return Err(ApiError::NotFound);
+    }
+
+    sqlx::query("UPDATE goal_public_links SET revoked_at = NOW() WHERE goal_id = $1 AND revoked_at IS NULL")
+        .bind(goal_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let link = sqlx::query_as::<_, GoalPublicLink>(
+        r#"INSERT INTO goal_public_links (id, goal_id, created_by, created_at)
+           VALUES ($1, $2, $3, NOW())
+           RETURNING id, goal_id, created_by, created_at, revoked_at"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(goal_id)
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    public_link_response(&link, &state.jwt_secret).map(Json)
+}
+
+async fn revoke_public_link(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(goal_id): Path<Uuid>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = sqlx::query(
+        r#"UPDATE goal_public_links l SET revoked_at = NOW()
+           FROM goals g
+           WHERE g.id = l.goal_id AND l.goal_id = $1 AND g.user_id = $2 AND l.revoked_at IS NULL"#,
+    )
+    .bind(goal_id)
+    .bind(user.user_id)
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(serde_json::json!({ "revoked": true })))
+}
+
+fn public_link_response(link: &GoalPublicLink, secret: &str) -> ApiResult<PublicLinkResponse> {
+    Ok(PublicLinkResponse {
+        token: jwt::generate_public_link_token(link.id, link.created_at, secret)?,
+        created_at: link.created_at,
+    })
+}
@@ -1,44 +1,108 @@
 //! Habit CRUD API
 
 use axum::{
-    extract::Path,
-    routing::get,
+    extract::{Path, Query},
+    routing::{get, put},
     Extension, Json, Router,
 };
-use sqlx::Row;
+use serde::Deserialize;
+use sqlx::PgConnection;
 use uuid::Uuid;
 
 use crate::{
+    analytics::{schedule::Schedule, streaks},
     auth::middleware::AuthUser,
+    db,
     error::{ApiError, ApiResult},
     models::*,
     AppState,
 };
 
+use super::pagination::{Pagination, Sort, SortOrder};
+
 pub fn routes() -> Router {
     Router::new()
         .route("/", get(list_habits).post(create_habit))
         .route("/:id", get(get_habit).put(update_habit).delete(delete_habit))
-        .route("/:id/reminder", axum::routing::put(update_reminder))
+        .route("/:id/stats", get(get_habit_stats))
+        .route(
+            "/:id/reminder",
+            get(list_reminders).post(add_reminder).put(replace_reminders).delete(delete_reminders),
+        )
+        .route("/:id/reminder/:reminder_id", put(update_reminder).delete(delete_reminder))
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HabitSort {
+    /// Newest first by default
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Name,
+}
+
+impl HabitSort {
+    fn sort(self, order: Option<SortOrder>) -> Sort {
+        let (name, column, sql_type, default) = match self {
+            HabitSort::CreatedAt => ("created_at", "created_at", "timestamptz", SortOrder::Desc),
+            HabitSort::UpdatedAt => ("updated_at", "updated_at", "timestamptz", SortOrder::Desc),
+            HabitSort::Name => ("name", "name", "text", SortOrder::Asc),
+        };
+        Sort::new(name, column, sql_type, order.unwrap_or(default))
+    }
+
+    fn key(self, habit: &Habit) -> String {
+        match self {
+            HabitSort::CreatedAt => habit.created_at.to_rfc3339(),
+            HabitSort::UpdatedAt => habit.updated_at.to_rfc3339(),
+            HabitSort::Name => habit.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HabitListQuery {
+    /// Archived habits instead of active ones
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    sort: HabitSort,
+    order: Option<SortOrder>,
 }
 
 async fn list_habits(
     Extension(state): Extension<AppState>,
     user: AuthUser,
-) -> ApiResult<Json<Vec<Habit>>> {
-    let habits = sqlx::query_as::<_, Habit>(
-        r#"SELECT id, user_id, name, description, 
+    Query(query): Query<HabitListQuery>,
+    pagination: Pagination,
+) -> ApiResult<Json<Page<Habit>>> {
+    let sort = query.sort.sort(query.order);
+    let (after_key, after_id) = pagination.after(&sort)?;
+
+    let sql = format!(
+        r#"SELECT id, user_id, name, description,
            habit_type, unit, target_value,
            target_direction,
-           archived, created_at, updated_at
-           FROM habits WHERE user_id = $1 AND archived = false
-           ORDER BY created_at DESC"#,
-    )
-    .bind(user.user_id)
-    .fetch_all(&state.db)
-    .await?;
+           schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+           archived, shared_goal_id, source_habit_id, created_at, updated_at
+           FROM habits WHERE user_id = $1 AND archived = $2 AND {after}
+           ORDER BY {order_by}
+           LIMIT $5"#,
+        after = sort.after("id", 3, 4),
+        order_by = sort.order_by("id"),
+    );
+
+    let habits = sqlx::query_as::<_, Habit>(&sql)
+        .bind(user.user_id)
+        .bind(query.archived)
+        .bind(after_key)
+        .bind(after_id)
+        .bind(pagination.fetch_limit())
+        .fetch_all(&state.db)
+        .await?;
 
-    Ok(Json(habits))
+    Ok(Json(pagination.page(habits, &sort, |h| (query.sort.key(h), h.id))))
 }
 
 async fn create_habit(
@@ -46,13 +110,25 @@ async fn create_habit(
     user: AuthUser,
     Json(body): Json<CreateHabitRequest>,
 ) -> ApiResult<Json<Habit>> {
+    let schedule_type = body.schedule_type.unwrap_or(ScheduleType::Daily);
+    Schedule::from_parts(
+        &schedule_type,
+        body.schedule_weekdays.as_deref(),
+        body.schedule_times_per_week,
+        body.schedule_interval_days,
+    )
+    .map_err(ApiError::BadRequest)?;
+
     let habit = sqlx::query_as::<_, Habit>(
-        r#"INSERT INTO habits (id, user_id, name, description, habit_type, unit, target_value, target_direction, archived, created_at, updated_at)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, false, NOW(), NOW())
+        r#"INSERT INTO habits (id, user_id, name, description, habit_type, unit, target_value, target_direction,
+                               schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                               archived, created_at, updated_at)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, false, NOW(), NOW())
            RETURNING id, user_id, name, description,
                      habit_type, unit, target_value,
                      target_direction,
-                     archived, created_at, updated_at"#,
+                     schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                     archived, shared_goal_id, source_habit_id, created_at, updated_at"#,
     )
     .bind(Uuid::new_v4())
     .bind(user.user_id)
@@ -62,6 +138,10 @@ async fn create_habit(
     .bind(&body.unit)
     .bind(body.target_value)
     .bind(body.target_direction.as_ref().unwrap_or(&TargetDirection::AtLeast))
+    .bind(&schedule_type)
+    .bind(&body.schedule_weekdays)
+    .bind(body.schedule_times_per_week)
+    .bind(body.schedule_interval_days)
     .fetch_one(&state.db)
     .await?;
 
@@ -77,7 +157,8 @@ async fn get_habit(
         r#"SELECT id, user_id, name, description,
            habit_type, unit, target_value,
            target_direction,
-           archived, created_at, updated_at
+           schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+           archived, shared_goal_id, source_habit_id, created_at, updated_at
            FROM habits WHERE id = $1 AND user_id = $2"#,
     )
     .bind(id)
@@ -89,12 +170,55 @@ async fn get_habit(
     Ok(Json(habit))
 }
 
+async fn get_habit_stats(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<HabitStats>> {
+    let habit = sqlx::query_as::<_, Habit>(
+        r#"SELECT id, user_id, name, description,
+           habit_type, unit, target_value,
+           target_direction,
+           schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+           archived, shared_goal_id, source_habit_id, created_at, updated_at
+           FROM habits WHERE id = $1 AND user_id = $2"#,
+    )
+    .bind(id)
+    .bind(user.user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let check_ins = sqlx::query_as::<_, CheckIn>(
+        r#"SELECT id, habit_id, user_id, value, note, effective_date, created_at
+           FROM check_ins WHERE habit_id = $1
+           ORDER BY effective_date ASC"#,
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let today = db::local_today(&state.db, user.user_id).await?;
+
+    Ok(Json(streaks::habit_stats(&habit, &check_ins, today)))
+}
+
 async fn update_habit(
     Extension(state): Extension<AppState>,
     user: AuthUser,
     Path(id): Path<Uuid>,
     Json(body): Json<UpdateHabitRequest>,
 ) -> ApiResult<Json<Habit>> {
+    if let Some(schedule_type) = &body.schedule_type {
+        Schedule::from_parts(
+            schedule_type,
+            body.schedule_weekdays.as_deref(),
+            body.schedule_times_per_week,
+            body.schedule_interval_days,
+        )
+        .map_err(ApiError::BadRequest)?;
+    }
+
     let habit = sqlx::query_as::<_, Habit>(
         r#"UPDATE habits SET
            name = COALESCE($3, name),
@@ -103,12 +227,17 @@ async fn update_habit(
            target_value = COALESCE($6, target_value),
            target_direction = COALESCE($7, target_direction),
            archived = COALESCE($8, archived),
+           schedule_type = COALESCE($9, schedule_type),
+           schedule_weekdays = CASE WHEN $9::schedule_type IS NULL THEN schedule_weekdays ELSE $10 END,
+           schedule_times_per_week = CASE WHEN $9::schedule_type IS NULL THEN schedule_times_per_week ELSE $11 END,
+           schedule_interval_days = CASE WHEN $9::schedule_type IS NULL THEN schedule_interval_days ELSE $12 END,
            updated_at = NOW()
            WHERE id = $1 AND user_id = $2
            RETURNING id, user_id, name, description,
                      habit_type, unit, target_value,
                      target_direction,
-                     archived, created_at, updated_at"#,
+                     schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                     archived, shared_goal_id, source_habit_id, created_at, updated_at"#,
     )
     .bind(id)
     .bind(user.user_id)
@@ -118,6 +247,10 @@ async fn update_habit(
     .bind(body.target_value)
     .bind(&body.target_direction)
     .bind(body.archived)
+    .bind(&body.schedule_type)
+    .bind(&body.schedule_weekdays)
+    .bind(body.schedule_times_per_week)
+    .bind(body.schedule_interval_days)
     .fetch_optional(&state.db)
     .await?
     .ok_or(ApiError::NotFound)?;
@@ -143,35 +276,196 @@ async fn delete_habit(
     Ok(Json(serde_json::json!({ "deleted": true })))
 }
 
-async fn update_reminder(
+/// Most reminders a single habit can have
+const MAX_REMINDERS_PER_HABIT: i64 = 10;
+
+async fn list_reminders(
     Extension(state): Extension<AppState>,
     user: AuthUser,
     Path(habit_id): Path<Uuid>,
-    Json(body): Json<HabitReminder>,
+) -> ApiResult<Json<Vec<HabitReminder>>> {
+    verify_habit_owner(&state.db, habit_id, user.user_id).await?;
+
+    let reminders = sqlx::query_as::<_, HabitReminder>(
+        r#"SELECT id, habit_id, enabled,
+                  reminder_type,
+                  interval_hours, daily_time, random_window_start, random_window_end,
+                  created_at, updated_at
+           FROM habit_reminders WHERE habit_id = $1
+           ORDER BY created_at ASC"#,
+    )
+    .bind(habit_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(reminders))
+}
+
+/// Add another reminder to a habit
+async fn add_reminder(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(habit_id): Path<Uuid>,
+    Json(body): Json<UpsertReminderRequest>,
 ) -> ApiResult<Json<HabitReminder>> {
-    // Verify habit ownership
-    let _habit: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM habits WHERE id = $1 AND user_id = $2")
+    body.validate().map_err(ApiError::BadRequest)?;
+    verify_habit_owner(&state.db, habit_id, user.user_id).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    // Lock the habit so concurrent adds can't exceed the limit
+    sqlx::query("SELECT id FROM habits WHERE id = $1 FOR UPDATE")
         .bind(habit_id)
-        .bind(user.user_id)
-        .fetch_optional(&state.db)
+        .execute(&mut *tx)
+        .await?;
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM habit_reminders WHERE habit_id = $1")
+        .bind(habit_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if count >= MAX_REMINDERS_PER_HABIT {
+        return Err(ApiError::BadRequest(format!(
+            "A habit can have at most {} reminders",
+            MAX_REMINDERS_PER_HABIT
+        )));
+    }
+
+    let reminder = insert_reminder(&mut tx, habit_id, &body).await?;
+    tx.commit().await?;
+
+    Ok(Json(reminder))
+}
+
+/// Replace all of a habit's reminders with the given ones
+async fn replace_reminders(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(habit_id): Path<Uuid>,
+    Json(body): Json<Vec<UpsertReminderRequest>>,
+) -> ApiResult<Json<Vec<HabitReminder>>> {
+    if body.len() as i64 > MAX_REMINDERS_PER_HABIT {
+        return Err(ApiError::BadRequest(format!(
+            "A habit can have at most {} reminders",
+            MAX_REMINDERS_PER_HABIT
+        )));
+    }
+    for reminder in &body {
+        reminder.validate().map_err(ApiError::BadRequest)?;
+    }
+    verify_habit_owner(&state.db, habit_id, user.user_id).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("DELETE FROM habit_reminders WHERE habit_id = $1")
+        .bind(habit_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut reminders = Vec::with_capacity(body.len());
+    for reminder in &body {
+        reminders.push(insert_reminder(&mut tx, habit_id, reminder).await?);
+    }
+    tx.commit().await?;
+
+    Ok(Json(reminders))
+}
+
+async fn delete_reminders(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(habit_id): Path<Uuid>,
+) -> ApiResult<Json<serde_json::Value>> {
+    verify_habit_owner(&state.db, habit_id, user.user_id).await?;
+
+    let result = sqlx::query("DELETE FROM habit_reminders WHERE habit_id = $1")
+        .bind(habit_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(serde_json::json!({ "deleted": result.rows_affected() })))
+}
+
+async fn update_reminder(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((habit_id, reminder_id)): Path<(Uuid, Uuid)>,
+    Json(body): Json<UpsertReminderRequest>,
+) -> ApiResult<Json<HabitReminder>> {
+    body.validate().map_err(ApiError::BadRequest)?;
+    verify_habit_owner(&state.db, habit_id, user.user_id).await?;
+
+    let reminder = sqlx::query_as::<_, HabitReminder>(
+        r#"UPDATE habit_reminders SET
+               enabled = $3,
+               reminder_type = $4,
+               interval_hours = $5,
+               daily_time = $6,
+               random_window_start = $7,
+               random_window_end = $8,
+               next_fire_at = NULL
+           WHERE id = $1 AND habit_id = $2
+           RETURNING id, habit_id, enabled,
+                     reminder_type,
+                     interval_hours, daily_time, random_window_start, random_window_end,
+                     created_at, updated_at"#,
+    )
+    .bind(reminder_id)
+    .bind(habit_id)
+    .bind(body.enabled)
+    .bind(&body.reminder_type)
+    .bind(body.interval_hours)
+    .bind(body.daily_time)
+    .bind(body.random_window_start)
+    .bind(body.random_window_end)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(reminder))
+}
+
+async fn delete_reminder(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((habit_id, reminder_id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    verify_habit_owner(&state.db, habit_id, user.user_id).await?;
+
+    let result = sqlx::query("DELETE FROM habit_reminders WHERE id = $1 AND habit_id = $2")
+        .bind(reminder_id)
+        .bind(habit_id)
+        .execute(&state.db)
         .await?;
-    
-    if _habit.is_none() {
+
+    if result.rows_affected() == 0 {
         return Err(ApiError::NotFound);
     }
 
+    Ok(Json(serde_json::json!({ "deleted": true })))
+}
+
+// ============ Helper Functions ============
+
+async fn verify_habit_owner(db: &sqlx::PgPool, habit_id: Uuid, user_id: Uuid) -> ApiResult<()> {
+    let habit: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM habits WHERE id = $1 AND user_id = $2")
+        .bind(habit_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+
+    habit.map(|_| ()).ok_or(ApiError::NotFound)
+}
+
+async fn insert_reminder(
+    conn: &mut PgConnection,
+    habit_id: Uuid,
+    body: &UpsertReminderRequest,
+) -> ApiResult<HabitReminder> {
     let reminder = sqlx::query_as::<_, HabitReminder>(
         r#"INSERT INTO habit_reminders (id, habit_id, enabled, reminder_type, interval_hours, daily_time, random_window_start, random_window_end, created_at, updated_at)
            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW(), NOW())
-           ON CONFLICT (habit_id) DO UPDATE SET
-               enabled = EXCLUDED.enabled,
-               reminder_type = EXCLUDED.reminder_type,
-               interval_hours = EXCLUDED.interval_hours,
-               daily_time = EXCLUDED.daily_time,
-               random_window_start = EXCLUDED.random_window_start,
-               random_window_end = EXCLUDED.random_window_end,
-               updated_at = NOW()
-           RETURNING id, habit_id, enabled, 
+           RETURNING id, habit_id, enabled,
                      reminder_type,
                      interval_hours, daily_time, random_window_start, random_window_end,
                      created_at, updated_at"#,
@@ -181,11 +475,59 @@ async fn update_reminder(
     .bind(body.enabled)
     .bind(&body.reminder_type)
     .bind(body.interval_hours)
-    .bind(&body.daily_time)
-    .bind(&body.random_window_start)
-    .bind(&body.random_window_end)
-    .fetch_one(&state.db)
+    .bind(body.daily_time)
+    .bind(body.random_window_start)
+    .bind(body.random_window_end)
+    .fetch_one(conn)
     .await?;
 
-    Ok(Json(reminder))
+    Ok(reminder)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support::{app_state, create_habit, create_user};
+
+    fn daily_at(hour: u32) -> UpsertReminderRequest {
+        UpsertReminderRequest {
+            enabled: true,
+            reminder_type: ReminderType::Daily,
+            interval_hours: None,
+            daily_time: NaiveTime::from_hms_opt(hour, 0, 0),
+            random_window_start: None,
+            random_window_end: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn replacing_reminders_keeps_every_one_given(db: PgPool) {
+        let user = create_user(&db, "reminders@example.com").await;
+        let habit_id = create_habit(&db, user.user_id, "Read", 0).await;
+
+        let Json(earlier) = add_reminder(Extension(app_state(db.clone())), user.clone(), Path(habit_id), Json(daily_at(7)))
+            .await
+            .unwrap();
+
+        let Json(replaced) = replace_reminders(
+            Extension(app_state(db.clone())),
+            user.clone(),
+            Path(habit_id),
+            Json(vec![daily_at(8), daily_at(20)]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(replaced.len(), 2);
+
+        let Json(reminders) = list_reminders(Extension(app_state(db.clone())), user.clone(), Path(habit_id))
+            .await
+            .unwrap();
+        assert!(reminders.iter().all(|r| r.id != earlier.id));
+        let mut times: Vec<_> = reminders.iter().map(|r| r.daily_time).collect();
+        times.sort();
+        assert_eq!(times, [NaiveTime::from_hms_opt(8, 0, 0), NaiveTime::from_hms_opt(20, 0, 0)]);
+    }
 }
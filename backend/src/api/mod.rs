@@ -3,6 +3,8 @@
 mod habits;
 mod goals;
 mod checkins;
+mod pagination;
+mod public;
 mod sharing;
 mod sync;
 
@@ -13,6 +15,7 @@ pub fn routes() -> Router {
         .nest("/habits", habits::routes())
         .nest("/goals", goals::routes())
         .nest("/checkins", checkins::routes())
+        .nest("/public", public::routes())
         .nest("/sharing", sharing::routes())
         .nest("/sync", sync::routes())
 }
@@ -0,0 +1,276 @@
+//! Cursor pagination for list routes
+//!
+//! Lists are ordered by one column with the row ID as a tie-breaker. The
+//! cursor records both for the last row of a page, so the next page starts
+//! right after it even if rows were added in the meantime.
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, NaiveDate};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult},
+    models::Page,
+};
+
+/// Page size when none is given, and the largest allowed
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// How a list is ordered
+#[derive(Debug, Clone, Copy)]
+pub struct Sort {
+    /// Name in the `sort` query parameter
+    name: &'static str,
+    column: &'static str,
+    /// Type the column's cursor key is cast to
+    sql_type: &'static str,
+    order: SortOrder,
+}
+
+impl Sort {
+    pub const fn new(name: &'static str, column: &'static str, sql_type: &'static str, order: SortOrder) -> Self {
+        Self { name, column, sql_type, order }
+    }
+
+    /// SQL condition keeping rows after the cursor, whose key and ID are
+    /// bound to parameters `key_param` and `id_param`. Holds for every row
+    /// when no cursor is bound.
+    pub fn after(&self, id_column: &str, key_param: usize, id_param: usize) -> String {
+        let op = match self.order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        format!(
+            "(${key}::text IS NULL OR ({column}, {id_column}) {op} (${key}::{sql_type}, ${id}))",
+            key = key_param,
+            id = id_param,
+            column = self.column,
+            sql_type = self.sql_type,
+        )
+    }
+
+    /// SQL `ORDER BY` list
+    pub fn order_by(&self, id_column: &str) -> String {
+        let direction = match self.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        format!("{column} {direction}, {id_column} {direction}", column = self.column)
+    }
+
+    /// Whether a cursor key casts to the column's type. Checked up front so
+    /// a tampered key is a bad request rather than a failed query.
+    fn accepts_key(&self, key: &str) -> bool {
+        match self.sql_type {
+            "date" => key.parse::<NaiveDate>().is_ok(),
+            "timestamptz" => DateTime::parse_from_rfc3339(key).is_ok(),
+            _ => true,
+        }
+    }
+
+    /// Cursors only carry on the sort they were made for
+    fn cursor_name(&self) -> String {
+        match self.order {
+            SortOrder::Asc => format!("{}:asc", self.name),
+            SortOrder::Desc => format!("{}:desc", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PageParams {
+    /// `next_cursor` from the previous page
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct Cursor {
+    sort: String,
+    key: String,
+    id: Uuid,
+}
+
+/// `?cursor=&limit=` from the query string, alongside any filters
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    cursor: Option<Cursor>,
+    pub limit: i64,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Pagination
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<PageParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+
+        Ok(Pagination {
+            cursor: params.cursor.as_deref().map(decode_cursor).transpose()?,
+            limit: params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        })
+    }
+}
+
+impl Pagination {
+    /// The cursor's key and ID, to bind to the parameters given to
+    /// `Sort::after`
+    pub fn after(&self, sort: &Sort) -> ApiResult<(Option<&str>, Option<Uuid>)> {
+        match &self.cursor {
+            None => Ok((None, None)),
+            Some(cursor) if cursor.sort != sort.cursor_name() => {
+                Err(ApiError::BadRequest("Cursor belongs to a different sort order".to_string()))
+            }
+            Some(cursor) if !sort.accepts_key(&cursor.key) => Err(ApiError::BadRequest("Invalid cursor".to_string())),
+            Some(cursor) => Ok((Some(&cursor.key), Some(cursor.id))),
+        }
+    }
+
+    /// Rows to fetch: one more than a page, which tells whether another follows
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// Turn rows fetched with `fetch_limit` into a page. `key` gives a row's
+    /// sort column as text, and its ID.
+    pub fn page<T>(&self, mut items: Vec<T>, sort: &Sort, key: impl Fn(&T) -> (String, Uuid)) -> Page<T> {
+        let next_cursor = if items.len() as i64 > self.limit {
+            items.truncate(self.limit as usize);
+            items.last().map(|item| {
+                let (key, id) = key(item);
+                URL_SAFE_NO_PAD.encode(format!("{}|{}|{}", sort.cursor_name(), key, id))
+            })
+        } else {
+            None
+        };
+
+        Page { items, next_cursor }
+    }
+}
+
+fn decode_cursor(cursor: &str) -> ApiResult<Cursor> {
+    let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+
+    let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+
+    // Keys may contain the separator; the sort name and ID can't
+    let (sort, rest) = decoded.split_once('|').ok_or_else(invalid)?;
+    let (key, id) = rest.rsplit_once('|').ok_or_else(invalid)?;
+
+    Ok(Cursor {
+        sort: sort.to_string(),
+        key: key.to_string(),
+        id: id.parse().map_err(|_| invalid())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME_ASC: Sort = Sort::new("name", "h.name", "text", SortOrder::Asc);
+
+    fn pagination(limit: i64) -> Pagination {
+        Pagination { cursor: None, limit }
+    }
+
+    /// The cursor for the next page after `items`, fetched as one more than `limit`
+    fn next_cursor(limit: i64, items: Vec<(&str, Uuid)>, sort: &Sort) -> Option<String> {
+        pagination(limit)
+            .page(items, sort, |&(key, id)| (key.to_string(), id))
+            .next_cursor
+    }
+
+    #[test]
+    fn cursors_round_trip_to_the_last_row_of_the_page() {
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let items = vec![("Read", ids[0]), ("Run | outside", ids[1]), ("Swim", ids[2])];
+
+        let cursor = next_cursor(2, items, &NAME_ASC).unwrap();
+        let next = Pagination { cursor: Some(decode_cursor(&cursor).unwrap()), limit: 2 };
+
+        assert_eq!(next.after(&NAME_ASC).unwrap(), (Some("Run | outside"), Some(ids[1])));
+    }
+
+    #[test]
+    fn the_last_page_has_no_cursor() {
+        let items = vec![("Read", Uuid::new_v4()), ("Run", Uuid::new_v4())];
+        assert_eq!(next_cursor(2, items, &NAME_ASC), None);
+    }
+
+    #[test]
+    fn cursors_only_continue_their_own_sort() {
+        let items = vec![("Read", Uuid::new_v4()), ("Run", Uuid::new_v4())];
+        let cursor = next_cursor(1, items, &NAME_ASC).unwrap();
+        let next = Pagination { cursor: Some(decode_cursor(&cursor).unwrap()), limit: 1 };
+
+        let name_desc = Sort::new("name", "h.name", "text", SortOrder::Desc);
+        let created = Sort::new("created_at", "h.created_at", "timestamptz", SortOrder::Asc);
+        assert!(next.after(&name_desc).is_err());
+        assert!(next.after(&created).is_err());
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let id = Uuid::new_v4();
+        for raw in ["not base64!", "bm8gc2VwYXJhdG9y"] {
+            assert!(decode_cursor(raw).is_err(), "accepted {raw}");
+        }
+        for decoded in ["name:asc|Read".to_string(), "name:asc|Read|not-a-uuid".to_string(), format!("{id}")] {
+            assert!(decode_cursor(&URL_SAFE_NO_PAD.encode(&decoded)).is_err(), "accepted {decoded}");
+        }
+    }
+
+    #[test]
+    fn cursor_keys_must_cast_to_the_sort_column() {
+        let created = Sort::new("created_at", "h.created_at", "timestamptz", SortOrder::Desc);
+        let deadline = Sort::new("deadline", "g.deadline", "date", SortOrder::Asc);
+        let cursor = |sort: &Sort, key: &str| Pagination {
+            cursor: Some(Cursor { sort: sort.cursor_name(), key: key.to_string(), id: Uuid::new_v4() }),
+            limit: 10,
+        };
+
+        assert!(cursor(&created, "2024-05-01T08:30:00+00:00").after(&created).is_ok());
+        assert!(cursor(&deadline, "2024-05-01").after(&deadline).is_ok());
+        for (sort, key) in [(&created, "yesterday"), (&created, "2024-05-01"), (&deadline, "2024-13-01"), (&deadline, "")] {
+            assert!(
+                matches!(cursor(sort, key).after(sort), Err(ApiError::BadRequest(_))),
+                "accepted {key} for {}",
+                sort.name,
+            );
+        }
+    }
+
+    #[test]
+    fn after_compares_the_key_and_id_in_sort_order() {
+        assert_eq!(
+            NAME_ASC.after("h.id", 3, 4),
+            "($3::text IS NULL OR (h.name, h.id) > ($3::text, $4))",
+        );
+        assert_eq!(
+            Sort::new("created_at", "c.created_at", "timestamptz", SortOrder::Desc).after("c.id", 5, 6),
+            "($5::text IS NULL OR (c.created_at, c.id) < ($5::timestamptz, $6))",
+        );
+        assert_eq!(NAME_ASC.order_by("h.id"), "h.name ASC, h.id ASC");
+    }
+}
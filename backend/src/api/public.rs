@@ -0,0 +1,152 @@
+//! Public API
+//!
+//! Read-only views that work without an account. Signed-in callers get the
+//! same data, plus a hint about whether it's theirs.
+
+use axum::{extract::Path, routing::get, Extension, Json, Router};
+
+use crate::{
+    analytics::progress,
+    auth::{jwt, middleware::OptionalAuthUser},
+    error::{ApiError, ApiResult},
+    models::*,
+    AppState,
+};
+
+pub fn routes() -> Router {
+    Router::new().route("/goals/:token", get(get_public_goal))
+}
+
+async fn get_public_goal(
+    Extension(state): Extension<AppState>,
+    OptionalAuthUser(viewer): OptionalAuthUser,
+    Path(token): Path<String>,
+) -> ApiResult<Json<PublicGoalSnapshot>> {
+    // Forged tokens and revoked links look the same as a missing goal
+    let claims = jwt::validate_public_link_token(&token, &state.jwt_secret).map_err(|_| ApiError::NotFound)?;
+
+    let goal = sqlx::query_as::<_, Goal>(
+        r#"SELECT g.id, g.user_id, g.name, g.description, g.deadline,
+           g.status, g.is_shared, g.created_at, g.updated_at
+           FROM goal_public_links l
+           JOIN goals g ON g.id = l.goal_id
+           WHERE l.id = $1 AND l.revoked_at IS NULL"#,
+    )
+    .bind(claims.sub)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let (progress, days) =
+        progress::load_goal_days(&state.db, &goal, progress::DEFAULT_ON_TRACK_PERCENTAGE).await?;
+
+    Ok(Json(PublicGoalSnapshot {
+        name: goal.name,
+        status: goal.status,
+        start_date: progress.start_date,
+        deadline: progress.deadline,
+        days_remaining: progress.days_remaining,
+        percentage: progress.percentage,
+        projected_percentage: progress.projected_percentage,
+        on_track: progress.on_track,
+        habit_count: progress.habits.len() as i32,
+        days,
+        is_owner: viewer.is_some_and(|viewer| viewer.user_id == goal.user_id),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        shared_goals,
+        test_support::{add_participant, app_state, check_in, create_goal, create_habit, create_user, share_goal, JWT_SECRET},
+    };
+
+    /// Publish a link to the goal and return its token
+    async fn publish(db: &PgPool, goal_id: Uuid, user_id: Uuid) -> (Uuid, String) {
+        let link_id = Uuid::new_v4();
+        let created_at = Utc::now();
+
+        sqlx::query("INSERT INTO goal_public_links (id, goal_id, created_by, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(link_id)
+            .bind(goal_id)
+            .bind(user_id)
+            .bind(created_at)
+            .execute(db)
+            .await
+            .unwrap();
+
+        (link_id, jwt::generate_public_link_token(link_id, created_at, JWT_SECRET).unwrap())
+    }
+
+    async fn view(db: &PgPool, token: &str) -> ApiResult<PublicGoalSnapshot> {
+        get_public_goal(Extension(app_state(db.clone())), OptionalAuthUser(None), Path(token.to_string()))
+            .await
+            .map(|Json(snapshot)| snapshot)
+    }
+
+    #[sqlx::test]
+    async fn snapshot_leaves_out_participants_habits(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let member = create_user(&db, "member@example.com").await;
+
+        let habit_id = create_habit(&db, owner.user_id, "Run", 30).await;
+        let goal_id = create_goal(&db, owner.user_id, "Marathon", &[habit_id]).await;
+        let shared_goal_id = share_goal(&db, owner.user_id, goal_id).await;
+        add_participant(&db, shared_goal_id, member.user_id, "collaborator").await;
+
+        let mut conn = db.acquire().await.unwrap();
+        shared_goals::copy_linked_habits(&mut conn, shared_goal_id, member.user_id).await.unwrap();
+        drop(conn);
+
+        let (copy_id,): (Uuid,) = sqlx::query_as("SELECT id FROM habits WHERE user_id = $1")
+            .bind(member.user_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        check_in(&db, member.user_id, copy_id, 0).await;
+
+        let (_, token) = publish(&db, goal_id, owner.user_id).await;
+        let snapshot = view(&db, &token).await.unwrap();
+
+        assert_eq!(snapshot.habit_count, 1);
+        assert!(snapshot.days.iter().all(|day| day.completed == 0));
+    }
+
+    #[sqlx::test]
+    async fn revoked_links_and_foreign_tokens_are_not_found(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let goal_id = create_goal(&db, owner.user_id, "Marathon", &[]).await;
+        let (link_id, token) = publish(&db, goal_id, owner.user_id).await;
+
+        assert!(view(&db, &token).await.is_ok());
+
+        // Signed with the right secret, but for another audience
+        let claims = PublicLinkClaims { sub: link_id, aud: "other".to_string(), iat: Utc::now().timestamp() };
+        let foreign = encode(&Header::default(), &claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap();
+        assert!(matches!(view(&db, &foreign).await, Err(ApiError::NotFound)));
+
+        // An access token doesn't open public links either
+        let claims = Claims {
+            sub: link_id,
+            email: owner.email.clone(),
+            exp: Utc::now().timestamp() + 3600,
+            iat: Utc::now().timestamp(),
+        };
+        let access = encode(&Header::default(), &claims, &EncodingKey::from_secret(JWT_SECRET.as_bytes())).unwrap();
+        assert!(matches!(view(&db, &access).await, Err(ApiError::NotFound)));
+
+        sqlx::query("UPDATE goal_public_links SET revoked_at = NOW() WHERE id = $1")
+            .bind(link_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        assert!(matches!(view(&db, &token).await, Err(ApiError::NotFound)));
+    }
+}
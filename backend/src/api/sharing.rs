@@ -1,32 +1,66 @@
 //! Shared goals API
 
+use std::collections::HashMap;
+
 use axum::{
-    extract::Path,
-    routing::{get, post},
+    extract::{Path, Query},
+    routing::{delete, get, post, put},
     Extension, Json, Router,
 };
 use chrono::{DateTime, Utc};
 use rand::Rng;
-use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use serde::Deserialize;
+use sqlx::{Acquire, FromRow, PgConnection, PgExecutor};
 use uuid::Uuid;
 
 use crate::{
+    activity,
+    analytics::{leaderboard, progress},
     auth::middleware::AuthUser,
     error::{ApiError, ApiResult},
+    mail::{self, Template},
     models::*,
+    shared_goals::{self, PreviousOwner},
     AppState,
 };
 
+use super::pagination::{Pagination, Sort, SortOrder};
+
 pub fn routes() -> Router {
     Router::new()
         .route("/goals", get(list_shared_goals))
         .route("/goals/:goal_id/share", post(share_goal))
-        .route("/goals/:id", get(get_shared_goal).delete(unshare_goal))
+        .route("/goals/:id", get(get_shared_goal).patch(update_shared_goal).delete(unshare_goal))
+        .route(
+            "/goals/:id/participants/:user_id",
+            put(update_participant_role).delete(remove_participant),
+        )
+        .route("/goals/:id/transfer", post(transfer_ownership))
+        .route("/goals/:id/invite-code", put(update_invite_code))
+        .route("/goals/:id/invite-code/rotate", post(rotate_invite_code))
+        .route("/goals/:id/progress", get(get_shared_progress))
+        .route("/goals/:id/habits/copy", post(copy_habits))
+        .route("/goals/:id/leaderboard", get(get_leaderboard))
+        .route("/goals/:id/leaderboard/ranking", put(set_leaderboard_ranking))
         .route("/goals/:id/invite", post(invite_user))
+        .route("/goals/:id/invites", get(list_goal_invites))
+        .route("/goals/:id/invites/:invite_id", delete(revoke_invite))
+        .route("/invites", get(list_my_invites))
+        .route("/invites/:id/accept", post(accept_invite))
+        .route("/invites/:id/decline", post(decline_invite))
         .route("/join", post(join_by_code))
         .route("/goals/:id/leave", post(leave_shared_goal))
         .route("/goals/:id/activity", get(get_activity_feed))
+        .route("/goals/:id/encourage", post(encourage))
+        .route("/goals/:id/activity/:activity_id/reactions", post(add_reaction))
+        .route("/goals/:id/activity/:activity_id/reactions/:emoji", delete(remove_reaction))
+}
+
+#[derive(Debug, FromRow)]
+struct InviteCodeRow {
+    id: Uuid,
+    #[sqlx(flatten)]
+    settings: InviteCodeSettings,
 }
 
 #[derive(Debug, FromRow)]
@@ -34,7 +68,10 @@ struct SharedGoalRow {
     id: Uuid,
     goal_id: Uuid,
     invite_code: String,
+    #[sqlx(flatten)]
+    invite_code_settings: InviteCodeSettings,
     max_participants: i32,
+    join_role: ShareRole,
     created_at: DateTime<Utc>,
     name: String,
     description: Option<String>,
@@ -44,25 +81,82 @@ struct SharedGoalRow {
     updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SharedGoalSort {
+    /// Newest first by default
+    #[default]
+    CreatedAt,
+    Deadline,
+    Name,
+}
+
+impl SharedGoalSort {
+    fn sort(self, order: Option<SortOrder>) -> Sort {
+        let (name, column, sql_type, default) = match self {
+            SharedGoalSort::CreatedAt => ("created_at", "sg.created_at", "timestamptz", SortOrder::Desc),
+            SharedGoalSort::Deadline => ("deadline", "g.deadline", "date", SortOrder::Asc),
+            SharedGoalSort::Name => ("name", "g.name", "text", SortOrder::Asc),
+        };
+        Sort::new(name, column, sql_type, order.unwrap_or(default))
+    }
+
+    fn key(self, sg: &SharedGoalRow) -> String {
+        match self {
+            SharedGoalSort::CreatedAt => sg.created_at.to_rfc3339(),
+            SharedGoalSort::Deadline => sg.deadline.to_string(),
+            SharedGoalSort::Name => sg.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SharedGoalListQuery {
+    status: Option<GoalStatus>,
+    #[serde(default)]
+    sort: SharedGoalSort,
+    order: Option<SortOrder>,
+}
+
 async fn list_shared_goals(
     Extension(state): Extension<AppState>,
     user: AuthUser,
-) -> ApiResult<Json<Vec<SharedGoalResponse>>> {
-    let shared_goals = sqlx::query_as::<_, SharedGoalRow>(
-        r#"SELECT sg.id, sg.goal_id, sg.invite_code, sg.max_participants, sg.created_at,
+    Query(query): Query<SharedGoalListQuery>,
+    pagination: Pagination,
+) -> ApiResult<Json<Page<SharedGoalResponse>>> {
+    let sort = query.sort.sort(query.order);
+    let (after_key, after_id) = pagination.after(&sort)?;
+
+    let sql = format!(
+        r#"SELECT sg.id, sg.goal_id, sg.invite_code,
+                  sg.invite_code_enabled, sg.invite_code_expires_at, sg.invite_code_max_uses, sg.invite_code_uses,
+                  sg.max_participants, sg.join_role, sg.created_at,
                   g.name, g.description, g.deadline, g.status, g.user_id, g.updated_at
            FROM shared_goals sg
            JOIN goals g ON g.id = sg.goal_id
            JOIN goal_participants gp ON gp.shared_goal_id = sg.id
            WHERE gp.user_id = $1
-           ORDER BY sg.created_at DESC"#,
-    )
-    .bind(user.user_id)
-    .fetch_all(&state.db)
-    .await?;
+             AND ($2::goal_status IS NULL OR g.status = $2)
+             AND {after}
+           ORDER BY {order_by}
+           LIMIT $5"#,
+        after = sort.after("sg.id", 3, 4),
+        order_by = sort.order_by("sg.id"),
+    );
+
+    let shared_goals = sqlx::query_as::<_, SharedGoalRow>(&sql)
+        .bind(user.user_id)
+        .bind(&query.status)
+        .bind(after_key)
+        .bind(after_id)
+        .bind(pagination.fetch_limit())
+        .fetch_all(&state.db)
+        .await?;
+
+    let page = pagination.page(shared_goals, &sort, |sg| (query.sort.key(sg), sg.id));
 
     let mut responses = Vec::new();
-    for sg in shared_goals {
+    for sg in page.items {
         let participants = get_participants(&state.db, sg.id).await?;
         
         responses.push(SharedGoalResponse {
@@ -79,12 +173,18 @@ async fn list_shared_goals(
                 updated_at: sg.updated_at,
             },
             invite_code: sg.invite_code,
+            invite_code_settings: sg.invite_code_settings,
+            max_participants: sg.max_participants,
+            join_role: sg.join_role,
             participants,
             created_at: sg.created_at,
         });
     }
 
-    Ok(Json(responses))
+    Ok(Json(Page {
+        items: responses,
+        next_cursor: page.next_cursor,
+    }))
 }
 
 async fn share_goal(
@@ -110,24 +210,44 @@ async fn share_goal(
         return Err(ApiError::Conflict("Goal is already shared".to_string()));
     }
 
+    let max_participants = body.max_participants.unwrap_or(10);
+    if max_participants < 1 {
+        return Err(ApiError::BadRequest("max_participants must be at least 1".to_string()));
+    }
+
+    let join_role = body.join_role.unwrap_or(ShareRole::Collaborator);
+    if join_role == ShareRole::Owner {
+        return Err(ApiError::BadRequest("New participants cannot join as owner".to_string()));
+    }
+
     let mut tx = state.db.begin().await?;
 
-    // Generate invite code
-    let invite_code = generate_invite_code();
     let shared_goal_id = Uuid::new_v4();
 
-    // Create shared goal
-    sqlx::query(
-        r#"INSERT INTO shared_goals (id, goal_id, created_by, invite_code, max_participants, created_at)
-           VALUES ($1, $2, $3, $4, $5, NOW())"#,
-    )
-    .bind(shared_goal_id)
-    .bind(goal.id)
-    .bind(user.user_id)
-    .bind(&invite_code)
-    .bind(body.max_participants.unwrap_or(10))
-    .execute(&mut *tx)
-    .await?;
+    // Create shared goal, retrying if the invite code is already taken
+    let mut invite_code = None;
+    for _ in 0..INVITE_CODE_ATTEMPTS {
+        let code = generate_invite_code();
+        let result = sqlx::query(
+            r#"INSERT INTO shared_goals (id, goal_id, created_by, invite_code, max_participants, join_role, created_at)
+               VALUES ($1, $2, $3, $4, $5, $6, NOW())
+               ON CONFLICT (invite_code) DO NOTHING"#,
+        )
+        .bind(shared_goal_id)
+        .bind(goal.id)
+        .bind(user.user_id)
+        .bind(&code)
+        .bind(max_participants)
+        .bind(&join_role)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 1 {
+            invite_code = Some(code);
+            break;
+        }
+    }
+    let invite_code = invite_code.ok_or_else(invite_code_exhausted)?;
 
     // Add owner as participant
     sqlx::query(
@@ -161,6 +281,14 @@ async fn share_goal(
         id: shared_goal_id,
         goal: Goal { is_shared: true, ..goal },
         invite_code,
+        invite_code_settings: InviteCodeSettings {
+            enabled: true,
+            expires_at: None,
+            max_uses: None,
+            uses: 0,
+        },
+        max_participants,
+        join_role,
         participants,
         created_at,
     }))
@@ -185,7 +313,9 @@ async fn get_shared_goal(
     }
 
     let sg = sqlx::query_as::<_, SharedGoalRow>(
-        r#"SELECT sg.id, sg.goal_id, sg.invite_code, sg.max_participants, sg.created_at,
+        r#"SELECT sg.id, sg.goal_id, sg.invite_code,
+                  sg.invite_code_enabled, sg.invite_code_expires_at, sg.invite_code_max_uses, sg.invite_code_uses,
+                  sg.max_participants, sg.join_role, sg.created_at,
                   g.user_id, g.name, g.description, g.deadline, g.status, g.updated_at
            FROM shared_goals sg
            JOIN goals g ON g.id = sg.goal_id
@@ -212,6 +342,9 @@ async fn get_shared_goal(
             updated_at: sg.updated_at,
         },
         invite_code: sg.invite_code,
+        invite_code_settings: sg.invite_code_settings,
+        max_participants: sg.max_participants,
+        join_role: sg.join_role,
         participants,
         created_at: sg.created_at,
     }))
@@ -222,18 +355,10 @@ async fn unshare_goal(
     user: AuthUser,
     Path(id): Path<Uuid>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    // Verify user is owner
-    let participant: Option<ParticipantRow> = sqlx::query_as(
-        r#"SELECT id, user_id, role, joined_at FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2"#,
-    )
-    .bind(id)
-    .bind(user.user_id)
-    .fetch_optional(&state.db)
-    .await?;
-
-    let participant = participant.ok_or(ApiError::Forbidden)?;
+    // Only the owner can dissolve the group
+    let role = participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
 
-    if participant.role != ShareRole::Owner {
+    if role != ShareRole::Owner {
         return Err(ApiError::Forbidden);
     }
 
@@ -247,7 +372,17 @@ async fn unshare_goal(
     
     let sg = sg.ok_or(ApiError::NotFound)?;
 
-    // Delete shared goal (cascades to participants)
+    // Participants keep their copies as ordinary habits
+    sqlx::query(
+        r#"DELETE FROM goal_habits gh
+           USING goals g, habits h
+           WHERE gh.goal_id = $1 AND g.id = gh.goal_id AND h.id = gh.habit_id AND h.user_id <> g.user_id"#,
+    )
+    .bind(sg.0)
+    .execute(&mut *tx)
+    .await?;
+
+    // Delete shared goal (cascades to participants and unbinds copies)
     sqlx::query("DELETE FROM shared_goals WHERE id = $1")
         .bind(id)
         .execute(&mut *tx)
@@ -264,14 +399,6 @@ async fn unshare_goal(
     Ok(Json(serde_json::json!({ "unshared": true })))
 }
 
-#[derive(Debug, FromRow)]
-struct ParticipantRow {
-    id: Uuid,
-    user_id: Uuid,
-    role: ShareRole,
-    joined_at: DateTime<Utc>,
-}
-
 async fn invite_user(
     Extension(state): Extension<AppState>,
     user: AuthUser,
@@ -279,216 +406,781 @@ async fn invite_user(
     Json(body): Json<InviteUserRequest>,
 ) -> ApiResult<Json<InviteResponse>> {
     // Verify user can invite (owner or collaborator)
-    let participant: Option<ParticipantRow> = sqlx::query_as(
-        r#"SELECT id, user_id, role, joined_at FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2"#,
+    let role = participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    if role == ShareRole::Viewer {
+        return Err(ApiError::Forbidden);
+    }
+
+    let email = body.email.trim();
+    if !email.contains('@') || email.len() > 255 {
+        return Err(ApiError::BadRequest("Invalid email address".to_string()));
+    }
+
+    let pending: Option<(Uuid,)> = sqlx::query_as(
+        r#"SELECT id FROM goal_invites
+           WHERE shared_goal_id = $1 AND LOWER(invitee_email) = LOWER($2)
+             AND status = 'pending' AND expires_at > NOW()"#,
     )
     .bind(id)
-    .bind(user.user_id)
+    .bind(email)
     .fetch_optional(&state.db)
     .await?;
 
-    let participant = participant.ok_or(ApiError::Forbidden)?;
-
-    if participant.role == ShareRole::Viewer {
-        return Err(ApiError::Forbidden);
+    if pending.is_some() {
+        return Err(ApiError::Conflict("An invite is already pending for this email".to_string()));
     }
 
+    let mut tx = state.db.begin().await?;
+
     let invite_id = Uuid::new_v4();
-    sqlx::query(
+    let (expires_at,): (DateTime<Utc>,) = sqlx::query_as(
         r#"INSERT INTO goal_invites (id, shared_goal_id, inviter_id, invitee_email, status, created_at, expires_at)
-           VALUES ($1, $2, $3, $4, 'pending', NOW(), NOW() + INTERVAL '7 days')"#,
+           VALUES ($1, $2, $3, $4, 'pending', NOW(), NOW() + INTERVAL '7 days')
+           RETURNING expires_at"#,
     )
     .bind(invite_id)
     .bind(id)
     .bind(user.user_id)
-    .bind(&body.email)
-    .execute(&state.db)
+    .bind(email)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let (goal_name, inviter_name): (String, Option<String>) = sqlx::query_as(
+        r#"SELECT g.name, u.name
+           FROM shared_goals sg
+           JOIN goals g ON g.id = sg.goal_id
+           JOIN users u ON u.id = $2
+           WHERE sg.id = $1"#,
+    )
+    .bind(id)
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
     .await?;
 
+    let template = Template::GoalInvite {
+        inviter_name: inviter_name.as_deref(),
+        goal_name: &goal_name,
+        expires_at,
+    };
+    mail::enqueue(&mut tx, None, email, &template).await?;
+
+    tx.commit().await?;
+
     Ok(Json(InviteResponse {
         invite_id,
         status: InviteStatus::Pending,
     }))
 }
 
-#[derive(Debug, FromRow)]
-struct SharedGoalBasic {
-    id: Uuid,
-    goal_id: Uuid,
-    invite_code: String,
-    max_participants: i32,
-    created_at: DateTime<Utc>,
-}
-
-async fn join_by_code(
+/// Invites sent for a shared goal, newest first
+async fn list_goal_invites(
     Extension(state): Extension<AppState>,
     user: AuthUser,
-    Json(body): Json<JoinByCodeRequest>,
-) -> ApiResult<Json<SharedGoalResponse>> {
-    let shared_goal: Option<SharedGoalBasic> = sqlx::query_as(
-        "SELECT id, goal_id, invite_code, max_participants, created_at FROM shared_goals WHERE invite_code = $1",
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Vec<InviteDetails>>> {
+    let role = participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    if role == ShareRole::Viewer {
+        return Err(ApiError::Forbidden);
+    }
+
+    let invites = sqlx::query_as::<_, InviteDetails>(
+        r#"SELECT gi.id, gi.shared_goal_id, g.name AS goal_name,
+                  gi.inviter_id, u.name AS inviter_name,
+                  gi.invitee_email, gi.status, gi.created_at, gi.expires_at
+           FROM goal_invites gi
+           JOIN shared_goals sg ON sg.id = gi.shared_goal_id
+           JOIN goals g ON g.id = sg.goal_id
+           JOIN users u ON u.id = gi.inviter_id
+           WHERE gi.shared_goal_id = $1
+           ORDER BY gi.created_at DESC"#,
     )
-    .bind(&body.invite_code)
-    .fetch_optional(&state.db)
+    .bind(id)
+    .fetch_all(&state.db)
     .await?;
 
-    let shared_goal = shared_goal.ok_or(ApiError::NotFound)?;
+    Ok(Json(invites))
+}
 
-    // Check if already a participant
-    let existing: Option<(Uuid,)> = sqlx::query_as(
-        "SELECT id FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2",
+/// Withdraw a pending invite. Allowed for whoever sent it and for the owner.
+async fn revoke_invite(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((id, invite_id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<Json<InviteResponse>> {
+    let invite = sqlx::query_as::<_, GoalInvite>(
+        r#"SELECT id, shared_goal_id, inviter_id, invitee_email, status, created_at, expires_at
+           FROM goal_invites WHERE id = $1 AND shared_goal_id = $2"#,
     )
-    .bind(shared_goal.id)
-    .bind(user.user_id)
+    .bind(invite_id)
+    .bind(id)
     .fetch_optional(&state.db)
-    .await?;
+    .await?
+    .ok_or(ApiError::NotFound)?;
 
-    if existing.is_some() {
-        return Err(ApiError::Conflict("Already a participant".to_string()));
+    if invite.inviter_id != user.user_id {
+        let role: Option<(ShareRole,)> = sqlx::query_as(
+            "SELECT role FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2",
+        )
+        .bind(id)
+        .bind(user.user_id)
+        .fetch_optional(&state.db)
+        .await?;
+
+        if !matches!(role, Some((ShareRole::Owner,))) {
+            return Err(ApiError::Forbidden);
+        }
     }
 
-    // Check participant limit
-    let count: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM goal_participants WHERE shared_goal_id = $1",
+    let result = sqlx::query(
+        "UPDATE goal_invites SET status = 'revoked' WHERE id = $1 AND status = 'pending'",
     )
-    .bind(shared_goal.id)
-    .fetch_one(&state.db)
+    .bind(invite_id)
+    .execute(&state.db)
     .await?;
 
-    if count.0 >= shared_goal.max_participants as i64 {
-        return Err(ApiError::Conflict("Goal has reached maximum participants".to_string()));
+    if result.rows_affected() == 0 {
+        return Err(ApiError::Conflict("Invite is no longer pending".to_string()));
     }
 
-    // Add as collaborator
-    sqlx::query(
-        r#"INSERT INTO goal_participants (id, shared_goal_id, user_id, role, joined_at)
-           VALUES ($1, $2, $3, 'collaborator', NOW())"#,
+    Ok(Json(InviteResponse {
+        invite_id,
+        status: InviteStatus::Revoked,
+    }))
+}
+
+/// Pending invites addressed to the user's primary email or any of their
+/// verified ones
+async fn list_my_invites(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<Vec<InviteDetails>>> {
+    let invites = sqlx::query_as::<_, InviteDetails>(
+        r#"SELECT gi.id, gi.shared_goal_id, g.name AS goal_name,
+                  gi.inviter_id, u.name AS inviter_name,
+                  gi.invitee_email, gi.status, gi.created_at, gi.expires_at
+           FROM goal_invites gi
+           JOIN shared_goals sg ON sg.id = gi.shared_goal_id
+           JOIN goals g ON g.id = sg.goal_id
+           JOIN users u ON u.id = gi.inviter_id
+           WHERE gi.status = 'pending' AND gi.expires_at > NOW()
+             AND LOWER(gi.invitee_email) IN (
+                 SELECT LOWER(email) FROM user_identities WHERE user_id = $1 AND email_verified
+             )
+           ORDER BY gi.created_at DESC"#,
     )
-    .bind(Uuid::new_v4())
-    .bind(shared_goal.id)
     .bind(user.user_id)
-    .execute(&state.db)
+    .fetch_all(&state.db)
     .await?;
 
-    // Return the shared goal
-    get_shared_goal(Extension(state), user, Path(shared_goal.id)).await
+    Ok(Json(invites))
 }
 
-async fn leave_shared_goal(
+async fn accept_invite(
     Extension(state): Extension<AppState>,
     user: AuthUser,
     Path(id): Path<Uuid>,
-) -> ApiResult<Json<serde_json::Value>> {
-    let participant: Option<ParticipantRow> = sqlx::query_as(
-        r#"SELECT id, user_id, role, joined_at FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2"#,
-    )
-    .bind(id)
-    .bind(user.user_id)
-    .fetch_optional(&state.db)
-    .await?;
+    body: Option<Json<AcceptInviteRequest>>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    let Json(body) = body.unwrap_or_default();
+
+    let mut tx = state.db.begin().await?;
 
-    let participant = participant.ok_or(ApiError::NotFound)?;
+    let invite = find_pending_invite(&mut tx, id, user.user_id).await?;
 
-    // Owner cannot leave, must unshare
-    if participant.role == ShareRole::Owner {
-        return Err(ApiError::BadRequest("Owner cannot leave. Use unshare instead.".to_string()));
+    if invite.expires_at <= Utc::now() {
+        sqlx::query("UPDATE goal_invites SET status = 'expired' WHERE id = $1")
+            .bind(invite.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        return Err(ApiError::BadRequest("Invite has expired".to_string()));
     }
 
-    sqlx::query(
-        "DELETE FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2",
-    )
-    .bind(id)
-    .bind(user.user_id)
-    .execute(&state.db)
-    .await?;
+    // Someone who already joined by code just uses up the invite
+    if let Some(role) = add_participant(&mut tx, invite.shared_goal_id, user.user_id).await? {
+        if role != ShareRole::Viewer && body.copy_habits.unwrap_or(true) {
+            shared_goals::copy_linked_habits(&mut tx, invite.shared_goal_id, user.user_id).await?;
+        }
+    }
 
-    Ok(Json(serde_json::json!({ "left": true })))
-}
+    sqlx::query("UPDATE goal_invites SET status = 'accepted' WHERE id = $1")
+        .bind(invite.id)
+        .execute(&mut *tx)
+        .await?;
 
-#[derive(Debug, FromRow)]
-struct ActivityRow {
-    id: Uuid,
-    activity_type: ActivityType,
-    message: Option<String>,
-    created_at: DateTime<Utc>,
-    user_name: Option<String>,
-    user_avatar: Option<String>,
-    habit_name: Option<String>,
+    tx.commit().await?;
+
+    get_shared_goal(Extension(state), user, Path(invite.shared_goal_id)).await
 }
 
-async fn get_activity_feed(
+async fn decline_invite(
     Extension(state): Extension<AppState>,
     user: AuthUser,
     Path(id): Path<Uuid>,
-) -> ApiResult<Json<Vec<ActivityFeedItem>>> {
-    // Verify user is a participant
-    let participant_exists: Option<(Uuid,)> = sqlx::query_as(
-        "SELECT id FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2",
+) -> ApiResult<Json<InviteResponse>> {
+    let mut tx = state.db.begin().await?;
+
+    let invite = find_pending_invite(&mut tx, id, user.user_id).await?;
+
+    sqlx::query("UPDATE goal_invites SET status = 'declined' WHERE id = $1")
+        .bind(invite.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(InviteResponse {
+        invite_id: invite.id,
+        status: InviteStatus::Declined,
+    }))
+}
+
+async fn join_by_code(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Json(body): Json<JoinByCodeRequest>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    let mut tx = state.db.begin().await?;
+
+    // Disabled codes look the same as unknown ones
+    let code = sqlx::query_as::<_, InviteCodeRow>(
+        r#"SELECT id, invite_code_enabled, invite_code_expires_at, invite_code_max_uses, invite_code_uses
+           FROM shared_goals WHERE invite_code = $1 AND invite_code_enabled
+           FOR UPDATE"#,
     )
-    .bind(id)
-    .bind(user.user_id)
-    .fetch_optional(&state.db)
-    .await?;
+    .bind(body.invite_code.trim().to_uppercase())
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApiError::NotFound)?;
 
-    if participant_exists.is_none() {
-        return Err(ApiError::Forbidden);
+    let shared_goal_id = code.id;
+
+    if code.settings.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(ApiError::BadRequest("Invite code has expired".to_string()));
     }
 
-    let activities: Vec<ActivityRow> = sqlx::query_as(
-        r#"SELECT sa.id, sa.activity_type, sa.message, sa.created_at,
-                  u.name as user_name, u.avatar_url as user_avatar,
-                  h.name as habit_name
-           FROM shared_activities sa
-           JOIN users u ON u.id = sa.user_id
-           LEFT JOIN habits h ON h.id = sa.habit_id
-           WHERE sa.shared_goal_id = $1
-           ORDER BY sa.created_at DESC
-           LIMIT 50"#,
-    )
-    .bind(id)
-    .fetch_all(&state.db)
-    .await?;
+    if code.settings.max_uses.is_some_and(|max_uses| code.settings.uses >= max_uses) {
+        return Err(ApiError::Conflict("Invite code has been used up".to_string()));
+    }
 
-    let feed: Vec<ActivityFeedItem> = activities
-        .into_iter()
-        .map(|a| ActivityFeedItem {
-            id: a.id,
-            user_name: a.user_name,
-            user_avatar: a.user_avatar,
-            activity_type: a.activity_type,
-            habit_name: a.habit_name,
-            message: a.message,
-            created_at: a.created_at,
-        })
-        .collect();
+    let role = add_participant(&mut tx, shared_goal_id, user.user_id)
+        .await?
+        .ok_or_else(|| ApiError::Conflict("Already a participant".to_string()))?;
 
-    Ok(Json(feed))
-}
+    sqlx::query("UPDATE shared_goals SET invite_code_uses = invite_code_uses + 1 WHERE id = $1")
+        .bind(shared_goal_id)
+        .execute(&mut *tx)
+        .await?;
 
-// Helper functions
+    if role != ShareRole::Viewer && body.copy_habits.unwrap_or(true) {
+        shared_goals::copy_linked_habits(&mut tx, shared_goal_id, user.user_id).await?;
+    }
 
-#[derive(Debug, FromRow)]
-struct ParticipantInfoRow {
-    user_id: Uuid,
-    role: ShareRole,
-    joined_at: DateTime<Utc>,
-    name: Option<String>,
-    avatar_url: Option<String>,
+    tx.commit().await?;
+
+    // Return the shared goal
+    get_shared_goal(Extension(state), user, Path(shared_goal_id)).await
 }
 
-async fn get_participants(db: &sqlx::PgPool, shared_goal_id: Uuid) -> ApiResult<Vec<ParticipantInfo>> {
-    let participants: Vec<ParticipantInfoRow> = sqlx::query_as(
-        r#"SELECT gp.user_id, gp.role, gp.joined_at, u.name, u.avatar_url
-           FROM goal_participants gp
-           JOIN users u ON u.id = gp.user_id
-           WHERE gp.shared_goal_id = $1
-           ORDER BY gp.joined_at ASC"#,
-    )
-    .bind(shared_goal_id)
-    .fetch_all(db)
-    .await?;
+async fn leave_shared_goal(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let mut tx = state.db.begin().await?;
 
-    Ok(participants
+    let role = participant_role(&mut *tx, id, user.user_id).await?.ok_or(ApiError::NotFound)?;
+
+    // The goal always has an owner
+    if role == ShareRole::Owner {
+        return Err(ApiError::BadRequest(
+            "Owner cannot leave. Transfer ownership or unshare the goal instead.".to_string(),
+        ));
+    }
+
+    remove_member(&mut tx, id, user.user_id).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({ "left": true })))
+}
+
+/// Update the goal's sharing settings (owner only)
+async fn update_shared_goal(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(body): Json<UpdateSharedGoalRequest>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    if body.join_role == Some(ShareRole::Owner) {
+        return Err(ApiError::BadRequest("New participants cannot join as owner".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    lock_as_owner(&mut tx, id, user.user_id).await?;
+
+    if let Some(max_participants) = body.max_participants {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM goal_participants WHERE shared_goal_id = $1",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if i64::from(max_participants) < count.max(1) {
+            return Err(ApiError::BadRequest(format!(
+                "max_participants must be at least the current participant count ({})",
+                count
+            )));
+        }
+    }
+
+    sqlx::query(
+        r#"UPDATE shared_goals SET
+           max_participants = COALESCE($2, max_participants),
+           join_role = COALESCE($3, join_role)
+           WHERE id = $1"#,
+    )
+    .bind(id)
+    .bind(body.max_participants)
+    .bind(&body.join_role)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    get_shared_goal(Extension(state), user, Path(id)).await
+}
+
+/// Replace the invite code's expiry, use limit and on/off switch (owner only)
+async fn update_invite_code(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(body): Json<UpdateInviteCodeRequest>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    if body.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(ApiError::BadRequest("expires_at must be in the future".to_string()));
+    }
+
+    if body.max_uses.is_some_and(|max_uses| max_uses < 1) {
+        return Err(ApiError::BadRequest("max_uses must be at least 1".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    lock_as_owner(&mut tx, id, user.user_id).await?;
+
+    sqlx::query(
+        r#"UPDATE shared_goals SET
+           invite_code_enabled = $2,
+           invite_code_expires_at = $3,
+           invite_code_max_uses = $4
+           WHERE id = $1"#,
+    )
+    .bind(id)
+    .bind(body.enabled)
+    .bind(body.expires_at)
+    .bind(body.max_uses)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    get_shared_goal(Extension(state), user, Path(id)).await
+}
+
+/// Replace the invite code with a new one, so the old code stops working
+/// (owner only). The use count starts over; other settings are kept.
+async fn rotate_invite_code(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    let mut tx = state.db.begin().await?;
+
+    lock_as_owner(&mut tx, id, user.user_id).await?;
+
+    let mut rotated = false;
+    for _ in 0..INVITE_CODE_ATTEMPTS {
+        // A code taken meanwhile fails the unique index; the savepoint keeps
+        // that from aborting the transaction
+        let mut attempt = tx.begin().await?;
+        let result = sqlx::query("UPDATE shared_goals SET invite_code = $2, invite_code_uses = 0 WHERE id = $1")
+            .bind(id)
+            .bind(generate_invite_code())
+            .execute(&mut *attempt)
+            .await;
+
+        match result {
+            Ok(_) => {
+                attempt.commit().await?;
+                rotated = true;
+                break;
+            }
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => attempt.rollback().await?,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if !rotated {
+        return Err(invite_code_exhausted());
+    }
+
+    tx.commit().await?;
+
+    get_shared_goal(Extension(state), user, Path(id)).await
+}
+
+/// Promote or demote a participant (owner only). Ownership itself moves
+/// through `transfer_ownership`.
+async fn update_participant_role(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((id, participant_id)): Path<(Uuid, Uuid)>,
+    Json(body): Json<UpdateParticipantRequest>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    if body.role == ShareRole::Owner {
+        return Err(ApiError::BadRequest("Use the transfer endpoint to hand over ownership".to_string()));
+    }
+
+    if participant_id == user.user_id {
+        return Err(ApiError::BadRequest("Owner cannot change their own role".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    lock_as_owner(&mut tx, id, user.user_id).await?;
+
+    participant_role(&mut *tx, id, participant_id).await?.ok_or(ApiError::NotFound)?;
+
+    sqlx::query("UPDATE goal_participants SET role = $3 WHERE shared_goal_id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(participant_id)
+        .bind(&body.role)
+        .execute(&mut *tx)
+        .await?;
+
+    // Viewers can't invite, so their outstanding invites go too
+    if body.role == ShareRole::Viewer {
+        revoke_invites_from(&mut tx, id, participant_id).await?;
+    }
+
+    tx.commit().await?;
+
+    get_shared_goal(Extension(state), user, Path(id)).await
+}
+
+/// Remove a participant from the goal (owner only)
+async fn remove_participant(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((id, participant_id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if participant_id == user.user_id {
+        return Err(ApiError::BadRequest(
+            "Owner cannot remove themselves. Transfer ownership or unshare the goal instead.".to_string(),
+        ));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    lock_as_owner(&mut tx, id, user.user_id).await?;
+
+    participant_role(&mut *tx, id, participant_id).await?.ok_or(ApiError::NotFound)?;
+
+    remove_member(&mut tx, id, participant_id).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({ "removed": true })))
+}
+
+/// Hand ownership to another participant. The previous owner stays on as a
+/// collaborator, and the goal moves to the new owner's account. Linked habits
+/// stay with whoever tracks them and keep counting toward the goal.
+async fn transfer_ownership(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(body): Json<TransferOwnershipRequest>,
+) -> ApiResult<Json<SharedGoalResponse>> {
+    if body.user_id == user.user_id {
+        return Err(ApiError::BadRequest("You already own this goal".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    lock_as_owner(&mut tx, id, user.user_id).await?;
+
+    participant_role(&mut *tx, id, body.user_id).await?.ok_or(ApiError::NotFound)?;
+
+    shared_goals::transfer_ownership(&mut tx, id, user.user_id, body.user_id, PreviousOwner::Stays).await?;
+
+    tx.commit().await?;
+
+    get_shared_goal(Extension(state), user, Path(id)).await
+}
+
+/// Progress of the goal across every participant's linked habits
+async fn get_shared_progress(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<GoalProgress>> {
+    participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    let goal = sqlx::query_as::<_, Goal>(
+        r#"SELECT g.id, g.user_id, g.name, g.description, g.deadline,
+           g.status, g.is_shared, g.created_at, g.updated_at
+           FROM shared_goals sg
+           JOIN goals g ON g.id = sg.goal_id
+           WHERE sg.id = $1"#,
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let progress = progress::load_goal_progress(&state.db, &goal, progress::DEFAULT_ON_TRACK_PERCENTAGE).await?;
+
+    Ok(Json(progress))
+}
+
+/// Copy the goal's habits into the caller's account, including any linked
+/// since they joined. Returns all of the caller's copies for the goal.
+async fn copy_habits(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Vec<Habit>>> {
+    let mut tx = state.db.begin().await?;
+
+    match participant_role(&mut *tx, id, user.user_id).await? {
+        None | Some(ShareRole::Viewer) => return Err(ApiError::Forbidden),
+        Some(ShareRole::Owner) => {
+            return Err(ApiError::BadRequest("The owner's habits are already linked".to_string()));
+        }
+        Some(ShareRole::Collaborator) => {}
+    }
+
+    shared_goals::copy_linked_habits(&mut tx, id, user.user_id).await?;
+
+    let habits = sqlx::query_as::<_, Habit>(
+        r#"SELECT id, user_id, name, description,
+           habit_type, unit, target_value,
+           target_direction,
+           schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+           archived, shared_goal_id, source_habit_id, created_at, updated_at
+           FROM habits WHERE user_id = $1 AND shared_goal_id = $2
+           ORDER BY created_at ASC"#,
+    )
+    .bind(user.user_id)
+    .bind(id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(habits))
+}
+
+async fn get_leaderboard(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<LeaderboardQuery>,
+) -> ApiResult<Json<Leaderboard>> {
+    participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    let leaderboard = leaderboard::load_leaderboard(&state.db, id, query.window, user.user_id).await?;
+
+    Ok(Json(leaderboard))
+}
+
+/// Opt in to or out of the goal's leaderboard ranking
+async fn set_leaderboard_ranking(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(body): Json<LeaderboardRankingRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = sqlx::query(
+        "UPDATE goal_participants SET leaderboard_opt_out = $3 WHERE shared_goal_id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(user.user_id)
+    .bind(!body.ranked)
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(serde_json::json!({ "ranked": body.ranked })))
+}
+
+/// The feed is always newest first
+const FEED_SORT: Sort = Sort::new("created_at", "sa.created_at", "timestamptz", SortOrder::Desc);
+
+/// Longest encouragement message
+const MAX_ENCOURAGEMENT_LEN: usize = 500;
+
+#[derive(Debug, FromRow)]
+struct ActivityRow {
+    id: Uuid,
+    user_id: Uuid,
+    activity_type: ActivityType,
+    message: Option<String>,
+    created_at: DateTime<Utc>,
+    user_name: Option<String>,
+    user_avatar: Option<String>,
+    habit_name: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+struct ReactionRow {
+    activity_id: Uuid,
+    emoji: String,
+    count: i64,
+    reacted: bool,
+}
+
+async fn get_activity_feed(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    pagination: Pagination,
+) -> ApiResult<Json<Page<ActivityFeedItem>>> {
+    participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    let (after_key, after_id) = pagination.after(&FEED_SORT)?;
+    let activities = load_activities(&state.db, id, None, after_key, after_id, pagination.fetch_limit()).await?;
+
+    let page = pagination.page(activities, &FEED_SORT, |a| (a.created_at.to_rfc3339(), a.id));
+    let items = with_reactions(&state.db, page.items, user.user_id).await?;
+
+    Ok(Json(Page { items, next_cursor: page.next_cursor }))
+}
+
+/// Post a message of support to the goal's feed
+async fn encourage(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(body): Json<EncourageRequest>,
+) -> ApiResult<Json<ActivityFeedItem>> {
+    participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    let message = body.message.trim();
+    if message.is_empty() || message.chars().count() > MAX_ENCOURAGEMENT_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "Message must be between 1 and {} characters",
+            MAX_ENCOURAGEMENT_LEN
+        )));
+    }
+
+    let activity_id = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO shared_activities (id, shared_goal_id, user_id, activity_type, message, created_at)
+           VALUES ($1, $2, $3, 'encouragement', $4, NOW())"#,
+    )
+    .bind(activity_id)
+    .bind(id)
+    .bind(user.user_id)
+    .bind(message)
+    .execute(&state.db)
+    .await?;
+
+    let activity = load_activities(&state.db, id, Some(activity_id), None, None, 1).await?;
+    let item = with_reactions(&state.db, activity, user.user_id).await?.pop().ok_or(ApiError::NotFound)?;
+
+    Ok(Json(item))
+}
+
+/// React to a feed item. Reacting twice with the same emoji is a no-op.
+async fn add_reaction(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((id, activity_id)): Path<(Uuid, Uuid)>,
+    Json(body): Json<ReactionRequest>,
+) -> ApiResult<Json<ActivityFeedItem>> {
+    if !is_valid_emoji(&body.emoji) {
+        return Err(ApiError::BadRequest("Reactions must be a single emoji".to_string()));
+    }
+
+    participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    sqlx::query(
+        r#"INSERT INTO activity_reactions (id, activity_id, user_id, emoji, created_at)
+           SELECT $1, sa.id, $3, $4, NOW()
+           FROM shared_activities sa
+           WHERE sa.id = $2 AND sa.shared_goal_id = $5
+           ON CONFLICT (activity_id, user_id, emoji) DO NOTHING"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(activity_id)
+    .bind(user.user_id)
+    .bind(&body.emoji)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    // Not found unless the item belongs to this goal
+    Ok(Json(feed_item(&state.db, id, activity_id, user.user_id).await?))
+}
+
+async fn remove_reaction(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((id, activity_id, emoji)): Path<(Uuid, Uuid, String)>,
+) -> ApiResult<Json<ActivityFeedItem>> {
+    participant_role(&state.db, id, user.user_id).await?.ok_or(ApiError::Forbidden)?;
+
+    sqlx::query(
+        r#"DELETE FROM activity_reactions r
+           USING shared_activities sa
+           WHERE sa.id = r.activity_id AND sa.shared_goal_id = $4
+             AND r.activity_id = $1 AND r.user_id = $2 AND r.emoji = $3"#,
+    )
+    .bind(activity_id)
+    .bind(user.user_id)
+    .bind(&emoji)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    Ok(Json(feed_item(&state.db, id, activity_id, user.user_id).await?))
+}
+
+// Helper functions
+
+#[derive(Debug, FromRow)]
+struct ParticipantInfoRow {
+    user_id: Uuid,
+    role: ShareRole,
+    joined_at: DateTime<Utc>,
+    name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+async fn get_participants(db: &sqlx::PgPool, shared_goal_id: Uuid) -> ApiResult<Vec<ParticipantInfo>> {
+    let participants: Vec<ParticipantInfoRow> = sqlx::query_as(
+        r#"SELECT gp.user_id, gp.role, gp.joined_at, u.name, u.avatar_url
+           FROM goal_participants gp
+           JOIN users u ON u.id = gp.user_id
+           WHERE gp.shared_goal_id = $1
+           ORDER BY gp.joined_at ASC"#,
+    )
+    .bind(shared_goal_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(participants
         .into_iter()
         .map(|p| ParticipantInfo {
             user_id: p.user_id,
@@ -500,6 +1192,264 @@ async fn get_participants(db: &sqlx::PgPool, shared_goal_id: Uuid) -> ApiResult<
         .collect())
 }
 
+/// The user's role in a shared goal, if they take part in it
+async fn participant_role(
+    executor: impl PgExecutor<'_>,
+    shared_goal_id: Uuid,
+    user_id: Uuid,
+) -> ApiResult<Option<ShareRole>> {
+    let role: Option<(ShareRole,)> = sqlx::query_as(
+        "SELECT role FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2",
+    )
+    .bind(shared_goal_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(role.map(|(role,)| role))
+}
+
+/// Lock a shared goal against concurrent membership changes, failing unless
+/// the user owns it. Returns the underlying goal's ID.
+async fn lock_as_owner(conn: &mut PgConnection, shared_goal_id: Uuid, user_id: Uuid) -> ApiResult<Uuid> {
+    let (goal_id,): (Uuid,) = sqlx::query_as("SELECT goal_id FROM shared_goals WHERE id = $1 FOR UPDATE")
+        .bind(shared_goal_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    match participant_role(&mut *conn, shared_goal_id, user_id).await? {
+        Some(ShareRole::Owner) => Ok(goal_id),
+        _ => Err(ApiError::Forbidden),
+    }
+}
+
+/// Drop a participant along with the invites they still have outstanding.
+/// Their habits are unlinked from the goal but stay in their account.
+async fn remove_member(conn: &mut PgConnection, shared_goal_id: Uuid, user_id: Uuid) -> ApiResult<()> {
+    sqlx::query("DELETE FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2")
+        .bind(shared_goal_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        r#"DELETE FROM goal_habits gh
+           USING shared_goals sg, habits h
+           WHERE sg.id = $1 AND gh.goal_id = sg.goal_id AND h.id = gh.habit_id AND h.user_id = $2"#,
+    )
+    .bind(shared_goal_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE habits SET shared_goal_id = NULL, updated_at = NOW() WHERE shared_goal_id = $1 AND user_id = $2")
+        .bind(shared_goal_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    revoke_invites_from(conn, shared_goal_id, user_id).await
+}
+
+async fn revoke_invites_from(conn: &mut PgConnection, shared_goal_id: Uuid, inviter_id: Uuid) -> ApiResult<()> {
+    sqlx::query(
+        r#"UPDATE goal_invites SET status = 'revoked'
+           WHERE shared_goal_id = $1 AND inviter_id = $2 AND status = 'pending'"#,
+    )
+    .bind(shared_goal_id)
+    .bind(inviter_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Lock an invite addressed to one of the user's verified emails, failing
+/// unless it's still pending. Accounts from before identities were tracked
+/// verify their email by signing in again.
+async fn find_pending_invite(conn: &mut PgConnection, id: Uuid, user_id: Uuid) -> ApiResult<GoalInvite> {
+    let invite = sqlx::query_as::<_, GoalInvite>(
+        r#"SELECT id, shared_goal_id, inviter_id, invitee_email, status, created_at, expires_at
+           FROM goal_invites
+           WHERE id = $1 AND LOWER(invitee_email) IN (
+               SELECT LOWER(email) FROM user_identities WHERE user_id = $2 AND email_verified
+           )
+           FOR UPDATE"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    if invite.status != InviteStatus::Pending {
+        return Err(ApiError::Conflict("Invite is no longer pending".to_string()));
+    }
+
+    Ok(invite)
+}
+
+/// Add the user to a shared goal with its join role, respecting its
+/// participant limit. Returns the role they joined with, or `None` if they
+/// were already a participant.
+async fn add_participant(
+    conn: &mut PgConnection,
+    shared_goal_id: Uuid,
+    user_id: Uuid,
+) -> ApiResult<Option<ShareRole>> {
+    // Lock the goal so concurrent joins can't overshoot the limit
+    let (max_participants, join_role): (i32, ShareRole) = sqlx::query_as(
+        "SELECT max_participants, join_role FROM shared_goals WHERE id = $1 FOR UPDATE",
+    )
+    .bind(shared_goal_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let existing: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM goal_participants WHERE shared_goal_id = $1 AND user_id = $2",
+    )
+    .bind(shared_goal_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if existing.is_some() {
+        return Ok(None);
+    }
+
+    let count: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM goal_participants WHERE shared_goal_id = $1",
+    )
+    .bind(shared_goal_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if count.0 >= max_participants as i64 {
+        return Err(ApiError::Conflict("Goal has reached maximum participants".to_string()));
+    }
+
+    sqlx::query(
+        r#"INSERT INTO goal_participants (id, shared_goal_id, user_id, role, joined_at)
+           VALUES ($1, $2, $3, $4, NOW())"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(shared_goal_id)
+    .bind(user_id)
+    .bind(&join_role)
+    .execute(&mut *conn)
+    .await?;
+
+    activity::joined_goal(conn, shared_goal_id, user_id).await?;
+
+    Ok(Some(join_role))
+}
+
+/// Feed rows for a shared goal, newest first: a single item when
+/// `activity_id` is given, otherwise the page after the cursor
+async fn load_activities(
+    db: &sqlx::PgPool,
+    shared_goal_id: Uuid,
+    activity_id: Option<Uuid>,
+    after_key: Option<&str>,
+    after_id: Option<Uuid>,
+    limit: i64,
+) -> ApiResult<Vec<ActivityRow>> {
+    let sql = format!(
+        r#"SELECT sa.id, sa.user_id, sa.activity_type, sa.message, sa.created_at,
+                  u.name as user_name, u.avatar_url as user_avatar,
+                  h.name as habit_name
+           FROM shared_activities sa
+           JOIN shared_goals sg ON sg.id = sa.shared_goal_id
+           JOIN users u ON u.id = sa.user_id
+           -- Habits unlinked since stay private
+           LEFT JOIN habits h ON h.id = sa.habit_id
+               AND EXISTS (SELECT 1 FROM goal_habits gh WHERE gh.goal_id = sg.goal_id AND gh.habit_id = h.id)
+           WHERE sa.shared_goal_id = $1
+             AND ($2::uuid IS NULL OR sa.id = $2)
+             AND {after}
+           ORDER BY {order_by}
+           LIMIT $5"#,
+        after = FEED_SORT.after("sa.id", 3, 4),
+        order_by = FEED_SORT.order_by("sa.id"),
+    );
+
+    let activities = sqlx::query_as::<_, ActivityRow>(&sql)
+        .bind(shared_goal_id)
+        .bind(activity_id)
+        .bind(after_key)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
+
+    Ok(activities)
+}
+
+/// Attach reaction counts to feed rows
+async fn with_reactions(
+    db: &sqlx::PgPool,
+    activities: Vec<ActivityRow>,
+    user_id: Uuid,
+) -> ApiResult<Vec<ActivityFeedItem>> {
+    let ids: Vec<Uuid> = activities.iter().map(|a| a.id).collect();
+    let reactions = sqlx::query_as::<_, ReactionRow>(
+        r#"SELECT activity_id, emoji, COUNT(*) AS count, BOOL_OR(user_id = $2) AS reacted
+           FROM activity_reactions
+           WHERE activity_id = ANY($1)
+           GROUP BY activity_id, emoji
+           ORDER BY MIN(created_at)"#,
+    )
+    .bind(&ids)
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut by_activity: HashMap<Uuid, Vec<ReactionCount>> = HashMap::new();
+    for r in reactions {
+        by_activity.entry(r.activity_id).or_default().push(ReactionCount {
+            emoji: r.emoji,
+            count: r.count,
+            reacted: r.reacted,
+        });
+    }
+
+    Ok(activities
+        .into_iter()
+        .map(|a| ActivityFeedItem {
+            id: a.id,
+            user_id: a.user_id,
+            user_name: a.user_name,
+            user_avatar: a.user_avatar,
+            activity_type: a.activity_type,
+            habit_name: a.habit_name,
+            message: a.message,
+            reactions: by_activity.remove(&a.id).unwrap_or_default(),
+            created_at: a.created_at,
+        })
+        .collect())
+}
+
+async fn feed_item(db: &sqlx::PgPool, shared_goal_id: Uuid, activity_id: Uuid, user_id: Uuid) -> ApiResult<ActivityFeedItem> {
+    let activity = load_activities(db, shared_goal_id, Some(activity_id), None, None, 1).await?;
+    with_reactions(db, activity, user_id).await?.pop().ok_or(ApiError::NotFound)
+}
+
+/// A single fully-qualified Unicode emoji, including skin tones, flags,
+/// keycaps and ZWJ sequences. Text-style symbols like "©" without the emoji
+/// variation selector don't count.
+fn is_valid_emoji(emoji: &str) -> bool {
+    emojis::get(emoji).is_some_and(|e| e.as_str() == emoji)
+}
+
+/// Fresh invite codes to try before giving up on a collision
+const INVITE_CODE_ATTEMPTS: usize = 5;
+
+fn invite_code_exhausted() -> ApiError {
+    ApiError::Internal(anyhow::anyhow!("Could not generate a unique invite code"))
+}
+
 fn generate_invite_code() -> String {
     let mut rng = rand::thread_rng();
     let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
@@ -507,3 +1457,181 @@ fn generate_invite_code() -> String {
         .map(|_| chars[rng.gen_range(0..chars.len())])
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::auth::identities::{self, ProviderIdentity};
+    use crate::test_support::{app_state, create_goal, create_habit, create_user};
+
+    /// A goal with one linked habit, shared by `owner`. Returns the shared
+    /// goal's ID and invite code.
+    async fn shared_goal(db: &PgPool, owner: &AuthUser) -> (Uuid, String) {
+        let habit_id = create_habit(db, owner.user_id, "Run", 30).await;
+        let goal_id = create_goal(db, owner.user_id, "Marathon", &[habit_id]).await;
+
+        let body = CreateSharedGoalRequest { goal_id, max_participants: None, join_role: None };
+        let Json(shared) = share_goal(Extension(app_state(db.clone())), owner.clone(), Path(goal_id), Json(body))
+            .await
+            .unwrap();
+
+        (shared.id, shared.invite_code)
+    }
+
+    async fn join(db: &PgPool, user: &AuthUser, invite_code: &str) {
+        let body = JoinByCodeRequest { invite_code: invite_code.to_string(), copy_habits: None };
+        let _ = join_by_code(Extension(app_state(db.clone())), user.clone(), Json(body)).await.unwrap();
+    }
+
+    async fn copy(db: &PgPool, user: &AuthUser, shared_goal_id: Uuid) -> ApiResult<Vec<Habit>> {
+        copy_habits(Extension(app_state(db.clone())), user.clone(), Path(shared_goal_id))
+            .await
+            .map(|Json(habits)| habits)
+    }
+
+    async fn linked_habit_count(db: &PgPool, shared_goal_id: Uuid) -> i64 {
+        let (count,): (i64,) = sqlx::query_as(
+            r#"SELECT COUNT(*) FROM goal_habits gh
+               JOIN shared_goals sg ON sg.goal_id = gh.goal_id
+               WHERE sg.id = $1"#,
+        )
+        .bind(shared_goal_id)
+        .fetch_one(db)
+        .await
+        .unwrap();
+        count
+    }
+
+    #[sqlx::test]
+    async fn invites_wait_for_the_invitee_to_verify_their_email(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let (shared_goal_id, _) = shared_goal(&db, &owner).await;
+
+        // Identities backfilled for older accounts were never verified
+        let invitee = create_user(&db, "invitee@example.com").await;
+        sqlx::query("UPDATE user_identities SET email_verified = false WHERE user_id = $1")
+            .bind(invitee.user_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        for email in ["Invitee@Example.com", "someone-else@example.com"] {
+            sqlx::query(
+                r#"INSERT INTO goal_invites (id, shared_goal_id, inviter_id, invitee_email, status, created_at, expires_at)
+                   VALUES ($1, $2, $3, $4, 'pending', NOW(), NOW() + INTERVAL '7 days')"#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(shared_goal_id)
+            .bind(owner.user_id)
+            .bind(email)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let Json(invites) = list_my_invites(Extension(app_state(db.clone())), invitee.clone()).await.unwrap();
+        assert!(invites.is_empty());
+
+        // Signing in again verifies the email with the provider
+        let identity = ProviderIdentity {
+            provider: AuthProvider::Google,
+            provider_id: invitee.user_id.to_string(),
+            email: invitee.email.clone(),
+            email_verified: true,
+            name: None,
+            avatar_url: None,
+        };
+        identities::sign_in(&db, &identity).await.unwrap();
+
+        let Json(invites) = list_my_invites(Extension(app_state(db.clone())), invitee.clone()).await.unwrap();
+        assert_eq!(invites.len(), 1);
+
+        let Json(joined) = accept_invite(Extension(app_state(db.clone())), invitee.clone(), Path(invites[0].id), None)
+            .await
+            .unwrap();
+        assert!(joined.participants.iter().any(|p| p.user_id == invitee.user_id));
+    }
+
+    #[sqlx::test]
+    async fn copying_again_after_an_ownership_transfer_adds_nothing(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let successor = create_user(&db, "successor@example.com").await;
+        let member = create_user(&db, "member@example.com").await;
+
+        let (shared_goal_id, invite_code) = shared_goal(&db, &owner).await;
+        join(&db, &successor, &invite_code).await;
+        join(&db, &member, &invite_code).await;
+        assert_eq!(copy(&db, &member, shared_goal_id).await.unwrap().len(), 1);
+        assert_eq!(linked_habit_count(&db, shared_goal_id).await, 3);
+
+        let body = TransferOwnershipRequest { user_id: successor.user_id };
+        let _ = transfer_ownership(Extension(app_state(db.clone())), owner.clone(), Path(shared_goal_id), Json(body))
+            .await
+            .unwrap();
+
+        // The successor's linked habits are now copies of the previous owner's
+        assert_eq!(copy(&db, &member, shared_goal_id).await.unwrap().len(), 1);
+        assert!(copy(&db, &owner, shared_goal_id).await.unwrap().is_empty());
+        assert_eq!(linked_habit_count(&db, shared_goal_id).await, 3);
+    }
+
+    #[sqlx::test]
+    async fn copying_picks_up_habits_linked_since_joining(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let member = create_user(&db, "member@example.com").await;
+
+        let (shared_goal_id, invite_code) = shared_goal(&db, &owner).await;
+        join(&db, &member, &invite_code).await;
+
+        let habit_id = create_habit(&db, owner.user_id, "Stretch", 0).await;
+        sqlx::query(
+            r#"INSERT INTO goal_habits (id, goal_id, habit_id, weight)
+               SELECT gen_random_uuid(), goal_id, $2, 1.0 FROM shared_goals WHERE id = $1"#,
+        )
+        .bind(shared_goal_id)
+        .bind(habit_id)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let copies = copy(&db, &member, shared_goal_id).await.unwrap();
+        assert_eq!(copies.len(), 2);
+        assert!(copies.iter().any(|h| h.source_habit_id == Some(habit_id)));
+    }
+
+    #[sqlx::test]
+    async fn rotating_the_invite_code_retires_the_old_one(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let member = create_user(&db, "member@example.com").await;
+        let (shared_goal_id, old_code) = shared_goal(&db, &owner).await;
+        join(&db, &member, &old_code).await;
+
+        let Json(rotated) = rotate_invite_code(Extension(app_state(db.clone())), owner.clone(), Path(shared_goal_id))
+            .await
+            .unwrap();
+        assert_ne!(rotated.invite_code, old_code);
+
+        let (uses,): (i32,) = sqlx::query_as("SELECT invite_code_uses FROM shared_goals WHERE id = $1")
+            .bind(shared_goal_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(uses, 0);
+
+        let late = create_user(&db, "late@example.com").await;
+        let body = JoinByCodeRequest { invite_code: old_code, copy_habits: None };
+        assert!(join_by_code(Extension(app_state(db.clone())), late, Json(body)).await.is_err());
+    }
+
+    #[test]
+    fn reactions_must_be_a_single_emoji() {
+        for emoji in ["👍", "👍🏽", "❤️", "🇳🇿", "1️⃣", "👩‍💻", "©️"] {
+            assert!(is_valid_emoji(emoji), "rejected {emoji}");
+        }
+        for text in ["", "a", ":)", "👍👍", "€€", "→", "©", "👍 ", "\u{200d}"] {
+            assert!(!is_valid_emoji(text), "accepted {text:?}");
+        }
+    }
+}
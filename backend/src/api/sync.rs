@@ -1,17 +1,28 @@
 //! Data sync API for cloud storage opt-in users
+//!
+//! Clients identify records by their own `local_id`s. The server keeps a
+//! stable mapping to its own IDs, so pushing the same record twice updates
+//! it instead of duplicating it. Pulls accept a `since` cursor and return
+//! only records changed after it, plus tombstones for deleted records.
+
+use std::collections::HashMap;
 
 use axum::{
+    extract::Query,
     routing::{get, post},
     Extension, Json, Router,
 };
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
 use uuid::Uuid;
 
 use crate::{
+    activity,
     auth::middleware::AuthUser,
+    db,
     error::{ApiError, ApiResult},
+    models::{CheckIn, SyncEntity},
     AppState,
 };
 
@@ -36,6 +47,7 @@ pub struct SyncStatus {
 #[derive(Debug, FromRow)]
 struct UserSyncStatus {
     cloud_sync_enabled: bool,
+    last_sync_at: Option<DateTime<Utc>>,
 }
 
 async fn sync_status(
@@ -43,7 +55,7 @@ async fn sync_status(
     user: AuthUser,
 ) -> ApiResult<Json<SyncStatus>> {
     let user_record: UserSyncStatus = sqlx::query_as(
-        "SELECT cloud_sync_enabled FROM users WHERE id = $1",
+        "SELECT cloud_sync_enabled, last_sync_at FROM users WHERE id = $1",
     )
     .bind(user.user_id)
     .fetch_one(&state.db)
@@ -72,7 +84,7 @@ async fn sync_status(
 
     Ok(Json(SyncStatus {
         enabled: user_record.cloud_sync_enabled,
-        last_sync: None, // TODO: Track last sync time
+        last_sync: user_record.last_sync_at,
         habits_count: habits_count.0,
         checkins_count: checkins_count.0,
         goals_count: goals_count.0,
@@ -115,14 +127,38 @@ async fn disable_cloud_sync(
     }))
 }
 
-/// Full data export for sync
+/// Records exchanged by push and pull
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SyncData {
     pub habits: Vec<HabitSyncData>,
     pub check_ins: Vec<CheckInSyncData>,
     pub goals: Vec<GoalSyncData>,
     pub goal_habits: Vec<GoalHabitSyncData>,
+    /// Records deleted on the client (push) or the server (pull)
+    #[serde(default)]
+    pub deleted: Vec<SyncTombstone>,
     pub synced_at: DateTime<Utc>,
+    /// Pass back as `since` on the next pull to get only newer changes
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// How to resolve conflicts for records that don't set their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<ConflictResolution>,
+}
+
+/// What to do when a pushed record was based on an outdated server copy
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictResolution {
+    /// Keep the server copy and report the conflict
+    #[default]
+    ServerWins,
+    /// Overwrite the server copy
+    ClientWins,
+    /// Take the client copy but keep both notes or descriptions
+    MergeNotes,
+    /// Check-ins only: keep the larger value. Other records keep the server copy.
+    MaxValue,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -134,7 +170,20 @@ pub struct HabitSyncData {
     pub unit: Option<String>,
     pub target_value: Option<i32>,
     pub target_direction: String,
+    #[serde(default)]
+    pub schedule_type: Option<String>,
+    #[serde(default)]
+    pub schedule_weekdays: Option<Vec<i32>>,
+    #[serde(default)]
+    pub schedule_times_per_week: Option<i32>,
+    #[serde(default)]
+    pub schedule_interval_days: Option<i32>,
     pub archived: bool,
+    /// Server version the client's copy is based on
+    #[serde(default)]
+    pub version: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<ConflictResolution>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -146,6 +195,11 @@ pub struct CheckInSyncData {
     pub value: i32,
     pub note: Option<String>,
     pub effective_date: String,
+    /// Server version the client's copy is based on
+    #[serde(default)]
+    pub version: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<ConflictResolution>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -156,6 +210,11 @@ pub struct GoalSyncData {
     pub description: Option<String>,
     pub deadline: String,
     pub status: String,
+    /// Server version the client's copy is based on
+    #[serde(default)]
+    pub version: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<ConflictResolution>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -167,128 +226,152 @@ pub struct GoalHabitSyncData {
     pub weight: f32,
 }
 
+/// A deleted record. Goal-habit links are identified as
+/// `<goal_local_id>/<habit_local_id>`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncTombstone {
+    pub entity_type: SyncEntity,
+    pub local_id: String,
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Server ID and current version of a client record
+#[derive(Debug, Serialize)]
+pub struct IdMapping {
+    pub entity_type: SyncEntity,
+    pub local_id: String,
+    pub server_id: Uuid,
+    pub version: i64,
+}
+
+/// A pushed record that was edited on the server since the client last saw it
+#[derive(Debug, Serialize)]
+pub struct SyncConflict {
+    pub entity_type: SyncEntity,
+    pub local_id: String,
+    pub server_id: Uuid,
+    pub client_version: Option<i64>,
+    pub server_version: i64,
+    pub resolution: ConflictResolution,
+    /// Whether any of the client's changes were applied
+    pub applied: bool,
+    /// The record as stored after resolution
+    pub record: SyncRecord,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SyncRecord {
+    Habit(HabitSyncData),
+    CheckIn(CheckInSyncData),
+    Goal(GoalSyncData),
+}
+
+/// Outcome of pushing a single record
+struct Pushed {
+    server_id: Uuid,
+    version: i64,
+    applied: bool,
+    conflict: Option<SyncConflict>,
+}
+
 /// Push local data to cloud
 async fn push_data(
     Extension(state): Extension<AppState>,
     user: AuthUser,
     Json(data): Json<SyncData>,
 ) -> ApiResult<Json<SyncResult>> {
-    // Verify cloud sync is enabled
-    let user_record: UserSyncStatus = sqlx::query_as(
-        "SELECT cloud_sync_enabled FROM users WHERE id = $1",
-    )
-    .bind(user.user_id)
-    .fetch_one(&state.db)
-    .await?;
-
-    if !user_record.cloud_sync_enabled {
-        return Err(ApiError::BadRequest("Cloud sync is not enabled".to_string()));
-    }
+    require_cloud_sync(&state.db, user.user_id).await?;
 
     let mut tx = state.db.begin().await?;
     let mut synced_habits = 0;
     let mut synced_checkins = 0;
     let mut synced_goals = 0;
+    let mut deleted = 0;
+    let mut id_map = Vec::new();
+    let mut conflicts = Vec::new();
+    let resolution = data.resolution.unwrap_or_default();
+    let today = db::local_today(&state.db, user.user_id).await?;
 
-    // Map local IDs to server IDs
-    let mut habit_id_map: std::collections::HashMap<String, Uuid> = std::collections::HashMap::new();
-    let mut goal_id_map: std::collections::HashMap<String, Uuid> = std::collections::HashMap::new();
+    // Apply deletions first so a deleted parent isn't resurrected below
+    for tombstone in &data.deleted {
+        if delete_record(&mut tx, user.user_id, tombstone).await? {
+            deleted += 1;
+        }
+    }
 
     // Sync habits
+    let mut habit_ids: HashMap<String, Uuid> = HashMap::new();
     for habit in &data.habits {
-        let server_id = Uuid::new_v4();
-        
-        sqlx::query(
-            r#"INSERT INTO habits (id, user_id, name, description, habit_type, unit, target_value, target_direction, archived, created_at, updated_at)
-               VALUES ($1, $2, $3, $4, $5::habit_type, $6, $7, $8::target_direction, $9, $10, $11)
-               ON CONFLICT (id) DO UPDATE SET
-                   name = EXCLUDED.name,
-                   description = EXCLUDED.description,
-                   unit = EXCLUDED.unit,
-                   target_value = EXCLUDED.target_value,
-                   target_direction = EXCLUDED.target_direction,
-                   archived = EXCLUDED.archived,
-                   updated_at = EXCLUDED.updated_at"#,
-        )
-        .bind(server_id)
-        .bind(user.user_id)
-        .bind(&habit.name)
-        .bind(&habit.description)
-        .bind(&habit.habit_type.to_lowercase())  // PostgreSQL expects lowercase enum values
-        .bind(&habit.unit)
-        .bind(habit.target_value)
-        .bind(&habit.target_direction)
-        .bind(habit.archived)
-        .bind(habit.created_at)
-        .bind(habit.updated_at)
-        .execute(&mut *tx)
-        .await?;
+        // Deleted on the server: the client learns about it from the next pull
+        let Some(pushed) = push_habit(&mut tx, user.user_id, habit, resolution).await? else { continue };
 
-        habit_id_map.insert(habit.local_id.clone(), server_id);
-        synced_habits += 1;
+        habit_ids.insert(habit.local_id.clone(), pushed.server_id);
+        id_map.push(IdMapping {
+            entity_type: SyncEntity::Habit,
+            local_id: habit.local_id.clone(),
+            server_id: pushed.server_id,
+            version: pushed.version,
+        });
+        if pushed.applied {
+            synced_habits += 1;
+        }
+        conflicts.extend(pushed.conflict);
     }
 
     // Sync check-ins
     for checkin in &data.check_ins {
-        if let Some(&habit_id) = habit_id_map.get(&checkin.habit_local_id) {
-            let effective_date = checkin.effective_date.parse::<NaiveDate>()
-                .map_err(|_| ApiError::BadRequest("Invalid date format".to_string()))?;
+        let habit_id = match habit_ids.get(&checkin.habit_local_id) {
+            Some(&id) => Some(id),
+            None => resolve_id(&mut tx, user.user_id, SyncEntity::Habit, &checkin.habit_local_id).await?,
+        };
+        let Some(habit_id) = habit_id else { continue };
 
-            sqlx::query(
-                r#"INSERT INTO check_ins (id, habit_id, user_id, value, note, effective_date, created_at)
-                   VALUES ($1, $2, $3, $4, $5, $6, $7)
-                   ON CONFLICT (habit_id, effective_date) DO UPDATE SET
-                       value = EXCLUDED.value,
-                       note = COALESCE(EXCLUDED.note, check_ins.note)"#,
-            )
-            .bind(Uuid::new_v4())
-            .bind(habit_id)
-            .bind(user.user_id)
-            .bind(checkin.value)
-            .bind(&checkin.note)
-            .bind(effective_date)
-            .bind(checkin.created_at)
-            .execute(&mut *tx)
-            .await?;
+        let Some(pushed) = push_check_in(&mut tx, user.user_id, habit_id, checkin, today, resolution).await? else { continue };
 
+        id_map.push(IdMapping {
+            entity_type: SyncEntity::CheckIn,
+            local_id: checkin.local_id.clone(),
+            server_id: pushed.server_id,
+            version: pushed.version,
+        });
+        if pushed.applied {
             synced_checkins += 1;
         }
+        conflicts.extend(pushed.conflict);
     }
 
     // Sync goals
+    let mut goal_ids: HashMap<String, Uuid> = HashMap::new();
     for goal in &data.goals {
-        let server_id = Uuid::new_v4();
-        let deadline = goal.deadline.parse::<NaiveDate>()
-            .map_err(|_| ApiError::BadRequest("Invalid date format".to_string()))?;
+        let Some(pushed) = push_goal(&mut tx, user.user_id, goal, resolution).await? else { continue };
 
-        sqlx::query(
-            r#"INSERT INTO goals (id, user_id, name, description, deadline, status, is_shared, created_at, updated_at)
-               VALUES ($1, $2, $3, $4, $5, $6::goal_status, false, $7, $8)
-               ON CONFLICT (id) DO UPDATE SET
-                   name = EXCLUDED.name,
-                   description = EXCLUDED.description,
-                   deadline = EXCLUDED.deadline,
-                   status = EXCLUDED.status,
-                   updated_at = EXCLUDED.updated_at"#,
-        )
-        .bind(server_id)
-        .bind(user.user_id)
-        .bind(&goal.name)
-        .bind(&goal.description)
-        .bind(deadline)
-        .bind(&goal.status)
-        .bind(goal.created_at)
-        .bind(goal.updated_at)
-        .execute(&mut *tx)
-        .await?;
-
-        goal_id_map.insert(goal.local_id.clone(), server_id);
-        synced_goals += 1;
+        goal_ids.insert(goal.local_id.clone(), pushed.server_id);
+        id_map.push(IdMapping {
+            entity_type: SyncEntity::Goal,
+            local_id: goal.local_id.clone(),
+            server_id: pushed.server_id,
+            version: pushed.version,
+        });
+        if pushed.applied {
+            synced_goals += 1;
+        }
+        conflicts.extend(pushed.conflict);
     }
 
     // Sync goal-habit links
     for gh in &data.goal_habits {
-        if let (Some(&goal_id), Some(&habit_id)) = (goal_id_map.get(&gh.goal_local_id), habit_id_map.get(&gh.habit_local_id)) {
+        let goal_id = match goal_ids.get(&gh.goal_local_id) {
+            Some(&id) => Some(id),
+            None => resolve_id(&mut tx, user.user_id, SyncEntity::Goal, &gh.goal_local_id).await?,
+        };
+        let habit_id = match habit_ids.get(&gh.habit_local_id) {
+            Some(&id) => Some(id),
+            None => resolve_id(&mut tx, user.user_id, SyncEntity::Habit, &gh.habit_local_id).await?,
+        };
+
+        if let (Some(goal_id), Some(habit_id)) = (goal_id, habit_id) {
             sqlx::query(
                 r#"INSERT INTO goal_habits (id, goal_id, habit_id, weight)
                    VALUES ($1, $2, $3, $4)
@@ -303,6 +386,13 @@ async fn push_data(
         }
     }
 
+    let (synced_at,): (DateTime<Utc>,) = sqlx::query_as(
+        "UPDATE users SET last_sync_at = NOW() WHERE id = $1 RETURNING last_sync_at",
+    )
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
     tx.commit().await?;
 
     Ok(Json(SyncResult {
@@ -310,7 +400,10 @@ async fn push_data(
         synced_habits,
         synced_checkins,
         synced_goals,
-        synced_at: Utc::now(),
+        deleted,
+        id_map,
+        conflicts,
+        synced_at,
     }))
 }
 
@@ -320,9 +413,387 @@ pub struct SyncResult {
     pub synced_habits: i32,
     pub synced_checkins: i32,
     pub synced_goals: i32,
+    pub deleted: i32,
+    pub id_map: Vec<IdMapping>,
+    pub conflicts: Vec<SyncConflict>,
     pub synced_at: DateTime<Utc>,
 }
 
+async fn push_habit(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    habit: &HabitSyncData,
+    default_resolution: ConflictResolution,
+) -> ApiResult<Option<Pushed>> {
+    let Some(id) = resolve_id(&mut *conn, user_id, SyncEntity::Habit, &habit.local_id).await? else {
+        let row: HabitRow = sqlx::query_as(
+            r#"INSERT INTO habits (id, user_id, name, description, habit_type, unit, target_value, target_direction,
+                                   schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                                   archived, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5::habit_type, $6, $7, $8::target_direction,
+                       COALESCE($9::schedule_type, 'daily'), $10, $11, $12, $13, $14, NOW())
+               RETURNING id, name, description, habit_type::text, unit, target_value, target_direction::text,
+                         schedule_type::text, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                         archived, version, created_at, updated_at"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&habit.name)
+        .bind(&habit.description)
+        .bind(habit.habit_type.to_lowercase())  // PostgreSQL expects lowercase enum values
+        .bind(&habit.unit)
+        .bind(habit.target_value)
+        .bind(&habit.target_direction)
+        .bind(&habit.schedule_type)
+        .bind(&habit.schedule_weekdays)
+        .bind(habit.schedule_times_per_week)
+        .bind(habit.schedule_interval_days)
+        .bind(habit.archived)
+        .bind(habit.created_at)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        remember_id(&mut *conn, user_id, SyncEntity::Habit, &habit.local_id, row.id).await?;
+        return Ok(Some(Pushed::created(row.id, row.version)));
+    };
+
+    let current: Option<HabitRow> = sqlx::query_as(
+        r#"SELECT id, name, description, habit_type::text, unit, target_value, target_direction::text,
+                  schedule_type::text, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                  archived, version, created_at, updated_at
+           FROM habits WHERE id = $1 AND user_id = $2
+           FOR UPDATE"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(current) = current else { return Ok(None) };
+
+    let resolution = habit.resolution.unwrap_or(default_resolution);
+    let conflict = is_stale(habit.version, current.version).then_some(resolution);
+
+    let description = match conflict {
+        Some(ConflictResolution::ServerWins | ConflictResolution::MaxValue) => {
+            let server_version = current.version;
+            let record = SyncRecord::Habit(current.into_sync(habit.local_id.clone()));
+            return Ok(Some(Pushed::kept(SyncEntity::Habit, &habit.local_id, habit.version, server_version, resolution, id, record)));
+        }
+        Some(ConflictResolution::MergeNotes) => merge_notes(current.description.as_deref(), habit.description.as_deref()),
+        Some(ConflictResolution::ClientWins) | None => habit.description.clone(),
+    };
+
+    let row: HabitRow = sqlx::query_as(
+        r#"UPDATE habits SET
+               name = $3,
+               description = $4,
+               unit = $5,
+               target_value = $6,
+               target_direction = $7::target_direction,
+               schedule_type = COALESCE($8::schedule_type, schedule_type),
+               schedule_weekdays = $9,
+               schedule_times_per_week = $10,
+               schedule_interval_days = $11,
+               archived = $12
+           WHERE id = $1 AND user_id = $2
+           RETURNING id, name, description, habit_type::text, unit, target_value, target_direction::text,
+                     schedule_type::text, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                     archived, version, created_at, updated_at"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(&habit.name)
+    .bind(&description)
+    .bind(&habit.unit)
+    .bind(habit.target_value)
+    .bind(&habit.target_direction)
+    .bind(&habit.schedule_type)
+    .bind(&habit.schedule_weekdays)
+    .bind(habit.schedule_times_per_week)
+    .bind(habit.schedule_interval_days)
+    .bind(habit.archived)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let version = row.version;
+    let conflict = conflict.map(|resolution| SyncConflict {
+        entity_type: SyncEntity::Habit,
+        local_id: habit.local_id.clone(),
+        server_id: id,
+        client_version: habit.version,
+        server_version: current.version,
+        resolution,
+        applied: true,
+        record: SyncRecord::Habit(row.into_sync(habit.local_id.clone())),
+    });
+
+    Ok(Some(Pushed { server_id: id, version, applied: true, conflict }))
+}
+
+async fn push_check_in(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    habit_id: Uuid,
+    checkin: &CheckInSyncData,
+    today: NaiveDate,
+    default_resolution: ConflictResolution,
+) -> ApiResult<Option<Pushed>> {
+    let effective_date = checkin.effective_date.parse::<NaiveDate>()
+        .map_err(|_| ApiError::BadRequest("Invalid date format".to_string()))?;
+
+    if effective_date > today {
+        return Err(ApiError::BadRequest("Cannot check in for a future date".to_string()));
+    }
+
+    let resolution = checkin.resolution.unwrap_or(default_resolution);
+    let mapped = resolve_id(&mut *conn, user_id, SyncEntity::CheckIn, &checkin.local_id).await?;
+
+    let (current, conflict) = match mapped {
+        Some(id) => {
+            let current: Option<CheckInRow> = sqlx::query_as(
+                r#"SELECT id, habit_id, value, note, effective_date, version, created_at
+                   FROM check_ins WHERE id = $1 AND user_id = $2
+                   FOR UPDATE"#,
+            )
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+            let Some(current) = current else { return Ok(None) };
+
+            let conflict = is_stale(checkin.version, current.version).then_some(resolution);
+            (current, conflict)
+        }
+        None => {
+            // Another device may already have checked in for the same day
+            let current: Option<CheckInRow> = sqlx::query_as(
+                r#"SELECT id, habit_id, value, note, effective_date, version, created_at
+                   FROM check_ins WHERE habit_id = $1 AND effective_date = $2
+                   FOR UPDATE"#,
+            )
+            .bind(habit_id)
+            .bind(effective_date)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            let Some(current) = current else {
+                let row: CheckInRow = sqlx::query_as(
+                    r#"INSERT INTO check_ins (id, habit_id, user_id, value, note, effective_date, created_at)
+                       VALUES ($1, $2, $3, $4, $5, $6, $7)
+                       RETURNING id, habit_id, value, note, effective_date, version, created_at"#,
+                )
+                .bind(Uuid::new_v4())
+                .bind(habit_id)
+                .bind(user_id)
+                .bind(checkin.value)
+                .bind(&checkin.note)
+                .bind(effective_date)
+                .bind(checkin.created_at)
+                .fetch_one(&mut *conn)
+                .await?;
+
+                let recorded = CheckIn {
+                    id: row.id,
+                    habit_id,
+                    user_id,
+                    value: row.value,
+                    note: row.note,
+                    effective_date,
+                    created_at: row.created_at,
+                };
+                activity::check_in_recorded(&mut *conn, &recorded, None, today).await?;
+
+                remember_id(&mut *conn, user_id, SyncEntity::CheckIn, &checkin.local_id, row.id).await?;
+                return Ok(Some(Pushed::created(row.id, row.version)));
+            };
+
+            remember_id(&mut *conn, user_id, SyncEntity::CheckIn, &checkin.local_id, current.id).await?;
+
+            let differs = current.value != checkin.value || current.note != checkin.note;
+            (current, differs.then_some(resolution))
+        }
+    };
+
+    let id = current.id;
+    let (value, note) = match conflict {
+        Some(ConflictResolution::ServerWins) => {
+            let server_version = current.version;
+            let record = SyncRecord::CheckIn(current.into_sync(checkin.local_id.clone(), checkin.habit_local_id.clone()));
+            return Ok(Some(Pushed::kept(SyncEntity::CheckIn, &checkin.local_id, checkin.version, server_version, resolution, id, record)));
+        }
+        Some(ConflictResolution::MergeNotes) => {
+            (checkin.value, merge_notes(current.note.as_deref(), checkin.note.as_deref()))
+        }
+        Some(ConflictResolution::MaxValue) => {
+            (checkin.value.max(current.value), checkin.note.clone().or_else(|| current.note.clone()))
+        }
+        Some(ConflictResolution::ClientWins) | None => (checkin.value, checkin.note.clone()),
+    };
+
+    let row: CheckInRow = sqlx::query_as(
+        r#"UPDATE check_ins SET value = $3, note = $4, effective_date = $5
+           WHERE id = $1 AND user_id = $2
+           RETURNING id, habit_id, value, note, effective_date, version, created_at"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(value)
+    .bind(&note)
+    .bind(effective_date)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let recorded = CheckIn {
+        id,
+        habit_id: row.habit_id,
+        user_id,
+        value: row.value,
+        note: row.note.clone(),
+        effective_date: row.effective_date,
+        created_at: row.created_at,
+    };
+    activity::check_in_recorded(&mut *conn, &recorded, Some(current.value), today).await?;
+
+    let version = row.version;
+    let conflict = conflict.map(|resolution| SyncConflict {
+        entity_type: SyncEntity::CheckIn,
+        local_id: checkin.local_id.clone(),
+        server_id: id,
+        client_version: checkin.version,
+        server_version: current.version,
+        resolution,
+        applied: true,
+        record: SyncRecord::CheckIn(row.into_sync(checkin.local_id.clone(), checkin.habit_local_id.clone())),
+    });
+
+    Ok(Some(Pushed { server_id: id, version, applied: true, conflict }))
+}
+
+async fn push_goal(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    goal: &GoalSyncData,
+    default_resolution: ConflictResolution,
+) -> ApiResult<Option<Pushed>> {
+    let deadline = goal.deadline.parse::<NaiveDate>()
+        .map_err(|_| ApiError::BadRequest("Invalid date format".to_string()))?;
+
+    let Some(id) = resolve_id(&mut *conn, user_id, SyncEntity::Goal, &goal.local_id).await? else {
+        let row: GoalRow = sqlx::query_as(
+            r#"INSERT INTO goals (id, user_id, name, description, deadline, status, is_shared, created_at, updated_at)
+               VALUES ($1, $2, $3, $4, $5, $6::goal_status, false, $7, NOW())
+               RETURNING id, name, description, deadline, status::text, version, created_at, updated_at"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&goal.name)
+        .bind(&goal.description)
+        .bind(deadline)
+        .bind(&goal.status)
+        .bind(goal.created_at)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        remember_id(&mut *conn, user_id, SyncEntity::Goal, &goal.local_id, row.id).await?;
+        return Ok(Some(Pushed::created(row.id, row.version)));
+    };
+
+    let current: Option<GoalRow> = sqlx::query_as(
+        r#"SELECT id, name, description, deadline, status::text, version, created_at, updated_at
+           FROM goals WHERE id = $1 AND user_id = $2
+           FOR UPDATE"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(current) = current else { return Ok(None) };
+
+    let resolution = goal.resolution.unwrap_or(default_resolution);
+    let conflict = is_stale(goal.version, current.version).then_some(resolution);
+
+    let description = match conflict {
+        Some(ConflictResolution::ServerWins | ConflictResolution::MaxValue) => {
+            let server_version = current.version;
+            let record = SyncRecord::Goal(current.into_sync(goal.local_id.clone()));
+            return Ok(Some(Pushed::kept(SyncEntity::Goal, &goal.local_id, goal.version, server_version, resolution, id, record)));
+        }
+        Some(ConflictResolution::MergeNotes) => merge_notes(current.description.as_deref(), goal.description.as_deref()),
+        Some(ConflictResolution::ClientWins) | None => goal.description.clone(),
+    };
+
+    let row: GoalRow = sqlx::query_as(
+        r#"UPDATE goals SET
+               name = $3,
+               description = $4,
+               deadline = $5,
+               status = $6::goal_status
+           WHERE id = $1 AND user_id = $2
+           RETURNING id, name, description, deadline, status::text, version, created_at, updated_at"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(&goal.name)
+    .bind(&description)
+    .bind(deadline)
+    .bind(&goal.status)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let version = row.version;
+    let conflict = conflict.map(|resolution| SyncConflict {
+        entity_type: SyncEntity::Goal,
+        local_id: goal.local_id.clone(),
+        server_id: id,
+        client_version: goal.version,
+        server_version: current.version,
+        resolution,
+        applied: true,
+        record: SyncRecord::Goal(row.into_sync(goal.local_id.clone())),
+    });
+
+    Ok(Some(Pushed { server_id: id, version, applied: true, conflict }))
+}
+
+impl Pushed {
+    fn created(server_id: Uuid, version: i64) -> Self {
+        Self { server_id, version, applied: true, conflict: None }
+    }
+
+    /// A conflict resolved in favour of the server copy
+    fn kept(
+        entity_type: SyncEntity,
+        local_id: &str,
+        client_version: Option<i64>,
+        server_version: i64,
+        resolution: ConflictResolution,
+        server_id: Uuid,
+        record: SyncRecord,
+    ) -> Self {
+        Self {
+            server_id,
+            version: server_version,
+            applied: false,
+            conflict: Some(SyncConflict {
+                entity_type,
+                local_id: local_id.to_string(),
+                server_id,
+                client_version,
+                server_version,
+                resolution,
+                applied: false,
+                record,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullQuery {
+    /// Cursor from a previous pull; omit for a full pull
+    pub since: Option<String>,
+}
+
 #[derive(Debug, FromRow)]
 struct HabitRow {
     id: Uuid,
@@ -332,11 +803,39 @@ struct HabitRow {
     unit: Option<String>,
     target_value: Option<i32>,
     target_direction: String,
+    schedule_type: String,
+    schedule_weekdays: Option<Vec<i32>>,
+    schedule_times_per_week: Option<i32>,
+    schedule_interval_days: Option<i32>,
     archived: bool,
+    version: i64,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
 
+impl HabitRow {
+    fn into_sync(self, local_id: String) -> HabitSyncData {
+        HabitSyncData {
+            local_id,
+            name: self.name,
+            description: self.description,
+            habit_type: self.habit_type,
+            unit: self.unit,
+            target_value: self.target_value,
+            target_direction: self.target_direction,
+            schedule_type: Some(self.schedule_type),
+            schedule_weekdays: self.schedule_weekdays,
+            schedule_times_per_week: self.schedule_times_per_week,
+            schedule_interval_days: self.schedule_interval_days,
+            archived: self.archived,
+            version: Some(self.version),
+            resolution: None,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
 #[derive(Debug, FromRow)]
 struct CheckInRow {
     id: Uuid,
@@ -344,9 +843,25 @@ struct CheckInRow {
     value: i32,
     note: Option<String>,
     effective_date: NaiveDate,
+    version: i64,
     created_at: DateTime<Utc>,
 }
 
+impl CheckInRow {
+    fn into_sync(self, local_id: String, habit_local_id: String) -> CheckInSyncData {
+        CheckInSyncData {
+            local_id,
+            habit_local_id,
+            value: self.value,
+            note: self.note,
+            effective_date: self.effective_date.to_string(),
+            version: Some(self.version),
+            resolution: None,
+            created_at: self.created_at,
+        }
+    }
+}
+
 #[derive(Debug, FromRow)]
 struct GoalRow {
     id: Uuid,
@@ -354,10 +869,27 @@ struct GoalRow {
     description: Option<String>,
     deadline: NaiveDate,
     status: String,
+    version: i64,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
 
+impl GoalRow {
+    fn into_sync(self, local_id: String) -> GoalSyncData {
+        GoalSyncData {
+            local_id,
+            name: self.name,
+            description: self.description,
+            deadline: self.deadline.to_string(),
+            status: self.status,
+            version: Some(self.version),
+            resolution: None,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
 #[derive(Debug, FromRow)]
 struct GoalHabitRow {
     goal_id: Uuid,
@@ -365,108 +897,473 @@ struct GoalHabitRow {
     weight: f32,
 }
 
+#[derive(Debug, FromRow)]
+struct TombstoneRow {
+    entity_type: SyncEntity,
+    server_id: Uuid,
+    goal_id: Option<Uuid>,
+    habit_id: Option<Uuid>,
+    deleted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct IdMapRow {
+    entity_type: SyncEntity,
+    local_id: String,
+    server_id: Uuid,
+}
+
 /// Pull cloud data to local
 async fn pull_data(
     Extension(state): Extension<AppState>,
     user: AuthUser,
+    Query(query): Query<PullQuery>,
 ) -> ApiResult<Json<SyncData>> {
-    // Verify cloud sync is enabled
-    let user_record: UserSyncStatus = sqlx::query_as(
-        "SELECT cloud_sync_enabled FROM users WHERE id = $1",
+    require_cloud_sync(&state.db, user.user_id).await?;
+
+    let since = query.since.as_deref().map(parse_cursor).transpose()?;
+
+    // Everything is read from one snapshot. Writers that hadn't committed
+    // when it was taken have transaction IDs at or above its xmin, so using
+    // that as the cursor hands their changes to the next pull. Changes that
+    // committed earlier may be sent twice; clients apply them by local ID.
+    let mut tx = state.db.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .execute(&mut *tx)
+        .await?;
+
+    let (cursor,): (i64,) = sqlx::query_as("SELECT pg_snapshot_xmin(pg_current_snapshot())::text::bigint")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let id_rows: Vec<IdMapRow> = sqlx::query_as(
+        "SELECT entity_type, local_id, server_id FROM sync_id_map WHERE user_id = $1",
     )
     .bind(user.user_id)
-    .fetch_one(&state.db)
+    .fetch_all(&mut *tx)
     .await?;
 
-    if !user_record.cloud_sync_enabled {
-        return Err(ApiError::BadRequest("Cloud sync is not enabled".to_string()));
-    }
+    let local_ids: HashMap<(SyncEntity, Uuid), String> = id_rows
+        .into_iter()
+        .map(|r| ((r.entity_type, r.server_id), r.local_id))
+        .collect();
+    let local_id = |entity: SyncEntity, id: Uuid| {
+        local_ids.get(&(entity, id)).cloned().unwrap_or_else(|| id.to_string())
+    };
 
-    // Fetch all user data
     let habits: Vec<HabitRow> = sqlx::query_as(
-        r#"SELECT id, name, description, habit_type::text, unit, target_value, target_direction::text, archived, created_at, updated_at
-           FROM habits WHERE user_id = $1"#,
+        r#"SELECT id, name, description, habit_type::text, unit, target_value, target_direction::text,
+                  schedule_type::text, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                  archived, version, created_at, updated_at
+           FROM habits WHERE user_id = $1 AND ($2::bigint IS NULL OR change_xid >= $2)"#,
     )
     .bind(user.user_id)
-    .fetch_all(&state.db)
+    .bind(since)
+    .fetch_all(&mut *tx)
     .await?;
 
     let habit_data: Vec<HabitSyncData> = habits
         .into_iter()
-        .map(|h| HabitSyncData {
-            local_id: h.id.to_string(),
-            name: h.name,
-            description: h.description,
-            habit_type: h.habit_type,
-            unit: h.unit,
-            target_value: h.target_value,
-            target_direction: h.target_direction,
-            archived: h.archived,
-            created_at: h.created_at,
-            updated_at: h.updated_at,
+        .map(|h| {
+            let local_id = local_id(SyncEntity::Habit, h.id);
+            h.into_sync(local_id)
         })
         .collect();
 
     let checkins: Vec<CheckInRow> = sqlx::query_as(
-        "SELECT id, habit_id, value, note, effective_date, created_at FROM check_ins WHERE user_id = $1",
+        r#"SELECT id, habit_id, value, note, effective_date, version, created_at
+           FROM check_ins WHERE user_id = $1 AND ($2::bigint IS NULL OR change_xid >= $2)"#,
     )
     .bind(user.user_id)
-    .fetch_all(&state.db)
+    .bind(since)
+    .fetch_all(&mut *tx)
     .await?;
 
     let checkin_data: Vec<CheckInSyncData> = checkins
         .into_iter()
-        .map(|c| CheckInSyncData {
-            local_id: c.id.to_string(),
-            habit_local_id: c.habit_id.to_string(),
-            value: c.value,
-            note: c.note,
-            effective_date: c.effective_date.to_string(),
-            created_at: c.created_at,
+        .map(|c| {
+            let (id, habit_id) = (c.id, c.habit_id);
+            c.into_sync(local_id(SyncEntity::CheckIn, id), local_id(SyncEntity::Habit, habit_id))
         })
         .collect();
 
     let goals: Vec<GoalRow> = sqlx::query_as(
-        "SELECT id, name, description, deadline, status::text, created_at, updated_at FROM goals WHERE user_id = $1",
+        r#"SELECT id, name, description, deadline, status::text, version, created_at, updated_at
+           FROM goals WHERE user_id = $1 AND ($2::bigint IS NULL OR change_xid >= $2)"#,
     )
     .bind(user.user_id)
-    .fetch_all(&state.db)
+    .bind(since)
+    .fetch_all(&mut *tx)
     .await?;
 
     let goal_data: Vec<GoalSyncData> = goals
         .into_iter()
-        .map(|g| GoalSyncData {
-            local_id: g.id.to_string(),
-            name: g.name,
-            description: g.description,
-            deadline: g.deadline.to_string(),
-            status: g.status,
-            created_at: g.created_at,
-            updated_at: g.updated_at,
+        .map(|g| {
+            let local_id = local_id(SyncEntity::Goal, g.id);
+            g.into_sync(local_id)
         })
         .collect();
 
     let goal_habits: Vec<GoalHabitRow> = sqlx::query_as(
-        "SELECT gh.goal_id, gh.habit_id, gh.weight FROM goal_habits gh JOIN goals g ON g.id = gh.goal_id WHERE g.user_id = $1",
+        r#"SELECT gh.goal_id, gh.habit_id, gh.weight
+           FROM goal_habits gh
+           JOIN goals g ON g.id = gh.goal_id
+           JOIN habits h ON h.id = gh.habit_id
+           -- Participants' copies linked to a shared goal stay off the owner's devices
+           WHERE g.user_id = $1 AND h.user_id = $1 AND ($2::bigint IS NULL OR gh.change_xid >= $2)"#,
     )
     .bind(user.user_id)
-    .fetch_all(&state.db)
+    .bind(since)
+    .fetch_all(&mut *tx)
     .await?;
 
     let goal_habit_data: Vec<GoalHabitSyncData> = goal_habits
         .into_iter()
         .map(|gh| GoalHabitSyncData {
-            goal_local_id: gh.goal_id.to_string(),
-            habit_local_id: gh.habit_id.to_string(),
+            goal_local_id: local_id(SyncEntity::Goal, gh.goal_id),
+            habit_local_id: local_id(SyncEntity::Habit, gh.habit_id),
             weight: gh.weight,
         })
         .collect();
 
+    // A full pull already reflects every deletion
+    let tombstones: Vec<TombstoneRow> = match since {
+        Some(since) => sqlx::query_as(
+            r#"SELECT entity_type, server_id, goal_id, habit_id, deleted_at
+               FROM sync_tombstones WHERE user_id = $1 AND change_xid >= $2
+               ORDER BY deleted_at ASC"#,
+        )
+        .bind(user.user_id)
+        .bind(since)
+        .fetch_all(&mut *tx)
+        .await?,
+        None => Vec::new(),
+    };
+
+    let deleted: Vec<SyncTombstone> = tombstones
+        .into_iter()
+        .map(|t| SyncTombstone {
+            entity_type: t.entity_type,
+            local_id: match (t.entity_type, t.goal_id, t.habit_id) {
+                (SyncEntity::GoalHabit, Some(goal_id), Some(habit_id)) => format!(
+                    "{}/{}",
+                    local_id(SyncEntity::Goal, goal_id),
+                    local_id(SyncEntity::Habit, habit_id)
+                ),
+                _ => local_id(t.entity_type, t.server_id),
+            },
+            deleted_at: Some(t.deleted_at),
+        })
+        .collect();
+
+    tx.commit().await?;
+
+    sqlx::query("UPDATE users SET last_sync_at = NOW() WHERE id = $1")
+        .bind(user.user_id)
+        .execute(&state.db)
+        .await?;
+
     Ok(Json(SyncData {
         habits: habit_data,
         check_ins: checkin_data,
         goals: goal_data,
         goal_habits: goal_habit_data,
+        deleted,
         synced_at: Utc::now(),
+        cursor: Some(cursor.to_string()),
+        resolution: None,
     }))
 }
+
+// Helper functions
+
+/// Cursors are transaction IDs; timestamps handed out by older servers no
+/// longer parse, and those clients need a full pull
+fn parse_cursor(cursor: &str) -> ApiResult<i64> {
+    cursor
+        .parse()
+        .map_err(|_| ApiError::BadRequest("Invalid sync cursor; pull again without `since`".to_string()))
+}
+
+async fn require_cloud_sync(db: &sqlx::PgPool, user_id: Uuid) -> ApiResult<()> {
+    let (enabled,): (bool,) = sqlx::query_as(
+        "SELECT cloud_sync_enabled FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+
+    if !enabled {
+        return Err(ApiError::BadRequest("Cloud sync is not enabled".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Server ID for a client record: a mapped local ID, or a server ID the
+/// client received from an earlier pull. Mappings to records that have
+/// since moved to another account, such as a transferred goal, are ignored.
+async fn resolve_id(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    entity: SyncEntity,
+    local_id: &str,
+) -> ApiResult<Option<Uuid>> {
+    // Deleted records still resolve, so pushes skip them instead of
+    // recreating them
+    let mapped: Option<(Uuid,)> = sqlx::query_as(
+        r#"SELECT m.server_id FROM sync_id_map m
+           WHERE m.user_id = $1 AND m.entity_type = $2 AND m.local_id = $3
+             AND NOT EXISTS (SELECT 1 FROM habits WHERE id = m.server_id AND user_id <> $1)
+             AND NOT EXISTS (SELECT 1 FROM check_ins WHERE id = m.server_id AND user_id <> $1)
+             AND NOT EXISTS (SELECT 1 FROM goals WHERE id = m.server_id AND user_id <> $1)"#,
+    )
+    .bind(user_id)
+    .bind(entity)
+    .bind(local_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((id,)) = mapped {
+        return Ok(Some(id));
+    }
+
+    let Ok(id) = Uuid::parse_str(local_id) else {
+        return Ok(None);
+    };
+
+    let owned_sql = match entity {
+        SyncEntity::Habit => "SELECT id FROM habits WHERE id = $1 AND user_id = $2",
+        SyncEntity::CheckIn => "SELECT id FROM check_ins WHERE id = $1 AND user_id = $2",
+        SyncEntity::Goal => "SELECT id FROM goals WHERE id = $1 AND user_id = $2",
+        SyncEntity::GoalHabit => return Ok(None),
+    };
+
+    let owned: Option<(Uuid,)> = sqlx::query_as(owned_sql)
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(owned.map(|(id,)| id))
+}
+
+async fn remember_id(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    entity: SyncEntity,
+    local_id: &str,
+    server_id: Uuid,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"INSERT INTO sync_id_map (user_id, entity_type, local_id, server_id, created_at)
+           VALUES ($1, $2, $3, $4, NOW())
+           ON CONFLICT (user_id, entity_type, local_id) DO UPDATE SET server_id = EXCLUDED.server_id"#,
+    )
+    .bind(user_id)
+    .bind(entity)
+    .bind(local_id)
+    .bind(server_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete a record the client deleted; returns whether anything was removed
+async fn delete_record(conn: &mut PgConnection, user_id: Uuid, tombstone: &SyncTombstone) -> ApiResult<bool> {
+    let result = match tombstone.entity_type {
+        SyncEntity::GoalHabit => {
+            let Some((goal_local_id, habit_local_id)) = tombstone.local_id.split_once('/') else {
+                return Err(ApiError::BadRequest("Invalid goal habit id".to_string()));
+            };
+            let goal_id = resolve_id(&mut *conn, user_id, SyncEntity::Goal, goal_local_id).await?;
+            let habit_id = resolve_id(&mut *conn, user_id, SyncEntity::Habit, habit_local_id).await?;
+            let (Some(goal_id), Some(habit_id)) = (goal_id, habit_id) else {
+                return Ok(false);
+            };
+
+            sqlx::query("DELETE FROM goal_habits WHERE goal_id = $1 AND habit_id = $2")
+                .bind(goal_id)
+                .bind(habit_id)
+                .execute(&mut *conn)
+                .await?
+        }
+        entity => {
+            let Some(id) = resolve_id(&mut *conn, user_id, entity, &tombstone.local_id).await? else {
+                return Ok(false);
+            };
+
+            let delete_sql = match entity {
+                SyncEntity::Habit => "DELETE FROM habits WHERE id = $1 AND user_id = $2",
+                SyncEntity::CheckIn => "DELETE FROM check_ins WHERE id = $1 AND user_id = $2",
+                _ => "DELETE FROM goals WHERE id = $1 AND user_id = $2",
+            };
+
+            sqlx::query(delete_sql)
+                .bind(id)
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await?
+        }
+    };
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether the client edited an older copy than the server holds. Clients
+/// that don't send versions keep last-writer-wins behaviour.
+fn is_stale(client_version: Option<i64>, server_version: i64) -> bool {
+    client_version.is_some_and(|version| version != server_version)
+}
+
+/// Combine two notes, keeping both when they differ
+fn merge_notes(server: Option<&str>, client: Option<&str>) -> Option<String> {
+    match (server.filter(|n| !n.is_empty()), client.filter(|n| !n.is_empty())) {
+        (Some(server), Some(client)) if server == client || server.contains(client) => Some(server.to_string()),
+        (Some(server), Some(client)) if client.contains(server) => Some(client.to_string()),
+        (Some(server), Some(client)) => Some(format!("{}\n\n{}", server, client)),
+        (server, client) => client.or(server).map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support::{app_state, create_goal, create_habit, create_user, share_goal};
+
+    async fn pull(db: &PgPool, user: &AuthUser, since: Option<String>) -> SyncData {
+        let Json(data) = pull_data(Extension(app_state(db.clone())), user.clone(), Query(PullQuery { since }))
+            .await
+            .unwrap();
+        data
+    }
+
+    #[sqlx::test]
+    async fn pull_after_cursor_includes_writes_committed_after_the_previous_pull(db: PgPool) {
+        let user = create_user(&db, "sync@example.com").await;
+
+        // A push that starts before the pull and commits after it
+        let mut writer = db.begin().await.unwrap();
+        sqlx::query("INSERT INTO habits (id, user_id, name, created_at, updated_at) VALUES ($1, $2, 'Read', NOW(), NOW())")
+            .bind(Uuid::new_v4())
+            .bind(user.user_id)
+            .execute(&mut *writer)
+            .await
+            .unwrap();
+
+        let first = pull(&db, &user, None).await;
+        assert!(first.habits.is_empty());
+
+        writer.commit().await.unwrap();
+
+        let second = pull(&db, &user, first.cursor).await;
+        assert_eq!(second.habits.len(), 1);
+        assert_eq!(second.habits[0].name, "Read");
+    }
+
+    #[sqlx::test]
+    async fn pull_after_cursor_includes_later_deletions(db: PgPool) {
+        let user = create_user(&db, "sync@example.com").await;
+        let habit_id = create_habit(&db, user.user_id, "Read", 0).await;
+
+        let first = pull(&db, &user, None).await;
+        assert_eq!(first.habits.len(), 1);
+
+        sqlx::query("DELETE FROM habits WHERE id = $1").bind(habit_id).execute(&db).await.unwrap();
+
+        let second = pull(&db, &user, first.cursor).await;
+        assert!(second.habits.is_empty());
+        assert_eq!(second.deleted.len(), 1);
+        assert_eq!(second.deleted[0].local_id, habit_id.to_string());
+    }
+
+    #[sqlx::test]
+    async fn pushed_updates_that_complete_a_check_in_reach_the_feed(db: PgPool) {
+        let user = create_user(&db, "sync@example.com").await;
+        let habit_id = create_habit(&db, user.user_id, "Pages", 10).await;
+        sqlx::query("UPDATE habits SET target_value = 3 WHERE id = $1")
+            .bind(habit_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let goal_id = create_goal(&db, user.user_id, "Read more", &[habit_id]).await;
+        share_goal(&db, user.user_id, goal_id).await;
+
+        let today = db::local_today(&db, user.user_id).await.unwrap();
+        let mut conn = db.acquire().await.unwrap();
+        let checkin = |value, version| CheckInSyncData {
+            local_id: "local-check-in".to_string(),
+            habit_local_id: habit_id.to_string(),
+            value,
+            note: None,
+            effective_date: today.to_string(),
+            version,
+            resolution: None,
+            created_at: Utc::now(),
+        };
+
+        let created = push_check_in(&mut conn, user.user_id, habit_id, &checkin(1, None), today, ConflictResolution::ClientWins)
+            .await
+            .unwrap()
+            .unwrap();
+        let pushed = checkin(3, Some(created.version));
+        let updated = push_check_in(&mut conn, user.user_id, habit_id, &pushed, today, ConflictResolution::ClientWins)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.server_id, created.server_id);
+
+        let (posts,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM shared_activities WHERE check_in_id = $1 AND activity_type = 'check_in'",
+        )
+        .bind(created.server_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(posts, 1);
+    }
+
+    #[sqlx::test]
+    async fn mappings_stop_resolving_once_the_record_changes_hands(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let other = create_user(&db, "other@example.com").await;
+        let habit_id = create_habit(&db, owner.user_id, "Read", 0).await;
+        let goal_id = create_goal(&db, owner.user_id, "Read more", &[habit_id]).await;
+
+        let mut conn = db.acquire().await.unwrap();
+        remember_id(&mut conn, owner.user_id, SyncEntity::Goal, "local-goal", goal_id).await.unwrap();
+        assert_eq!(resolve_id(&mut conn, owner.user_id, SyncEntity::Goal, "local-goal").await.unwrap(), Some(goal_id));
+
+        sqlx::query("UPDATE goals SET user_id = $2 WHERE id = $1")
+            .bind(goal_id)
+            .bind(other.user_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_eq!(resolve_id(&mut conn, owner.user_id, SyncEntity::Goal, "local-goal").await.unwrap(), None);
+
+        // The former owner can no longer unlink habits from the goal
+        let tombstone = SyncTombstone {
+            entity_type: SyncEntity::GoalHabit,
+            local_id: format!("local-goal/{}", habit_id),
+            deleted_at: None,
+        };
+        assert!(!delete_record(&mut conn, owner.user_id, &tombstone).await.unwrap());
+
+        let (links,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM goal_habits WHERE goal_id = $1")
+            .bind(goal_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(links, 1);
+    }
+
+    #[test]
+    fn timestamp_cursors_are_rejected() {
+        assert!(parse_cursor("2024-06-01T00:00:00Z").is_err());
+        assert_eq!(parse_cursor("1234").unwrap(), 1234);
+    }
+}
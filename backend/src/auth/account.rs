@@ -0,0 +1,448 @@
+//! Account deletion and personal data export
+
+use axum::{extract::Query, Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::{ApiError, ApiResult},
+    mail::{self, Template},
+    models::*,
+    shared_goals::{self, PreviousOwner},
+};
+use super::middleware::AuthUser;
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountQuery {
+    /// Dissolve owned shared goals instead of handing them to another participant
+    #[serde(default)]
+    pub dissolve_shared_goals: bool,
+}
+
+/// Delete the current user and everything they own
+pub async fn delete_me(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Query(query): Query<DeleteAccountQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let avatar_key = super::profile::avatar_key(&state, user.user_id).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    let owned: Vec<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM shared_goals WHERE created_by = $1 FOR UPDATE",
+    )
+    .bind(user.user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut transferred = 0;
+    let mut dissolved = 0;
+    for (shared_goal_id,) in owned {
+        let successor = if query.dissolve_shared_goals {
+            None
+        } else {
+            next_owner(&mut tx, shared_goal_id, user.user_id).await?
+        };
+
+        match successor {
+            Some(new_owner) => {
+                shared_goals::transfer_ownership(&mut tx, shared_goal_id, user.user_id, new_owner, PreviousOwner::Leaves)
+                    .await?;
+                transferred += 1;
+            }
+            None => {
+                // Cascades to participants, invites and activity
+                sqlx::query("DELETE FROM shared_goals WHERE id = $1")
+                    .bind(shared_goal_id)
+                    .execute(&mut *tx)
+                    .await?;
+                dissolved += 1;
+            }
+        }
+    }
+
+    shared_goals::reroot_copies(&mut tx, user.user_id).await?;
+
+    // Rows referencing the user without ON DELETE CASCADE
+    sqlx::query("DELETE FROM shared_activities WHERE user_id = $1")
+        .bind(user.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM goal_invites WHERE inviter_id = $1")
+        .bind(user.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Queued and sent mail would otherwise outlive the account
+    sqlx::query("DELETE FROM email_outbox WHERE user_id = $1")
+        .bind(user.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Cascades to identities, tokens, habits, check-ins, reminders, goals and memberships
+    let deleted: Option<(String,)> = sqlx::query_as("DELETE FROM users WHERE id = $1 RETURNING email")
+        .bind(user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let (email,) = deleted.ok_or(ApiError::NotFound)?;
+    mail::enqueue(&mut tx, None, &email, &Template::AccountDeleted).await?;
+
+    tx.commit().await?;
+
+    if let Some(key) = avatar_key {
+        super::profile::remove_blob(&state, &key).await;
+    }
+
+    Ok(Json(serde_json::json!({
+        "deleted": true,
+        "shared_goals_transferred": transferred,
+        "shared_goals_dissolved": dissolved,
+    })))
+}
+
+/// Longest-standing participant, preferring collaborators over viewers
+async fn next_owner(conn: &mut PgConnection, shared_goal_id: Uuid, owner_id: Uuid) -> ApiResult<Option<Uuid>> {
+    let successor: Option<(Uuid,)> = sqlx::query_as(
+        r#"SELECT user_id FROM goal_participants
+           WHERE shared_goal_id = $1 AND user_id <> $2
+           ORDER BY role ASC, joined_at ASC
+           LIMIT 1"#,
+    )
+    .bind(shared_goal_id)
+    .bind(owner_id)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(successor.map(|(id,)| id))
+}
+
+/// Refresh token metadata; hashes are never exported
+#[derive(Debug, Serialize, FromRow)]
+pub struct SessionExport {
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Client ID assigned to a synced record
+#[derive(Debug, Serialize, FromRow)]
+pub struct SyncIdExport {
+    pub entity_type: SyncEntity,
+    pub local_id: String,
+    pub server_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Deletion kept for incremental sync
+#[derive(Debug, Serialize, FromRow)]
+pub struct SyncTombstoneExport {
+    pub entity_type: SyncEntity,
+    pub server_id: Uuid,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Email sent or queued for the user; bodies are left out
+#[derive(Debug, Serialize, FromRow)]
+pub struct EmailExport {
+    pub recipient: String,
+    pub template: String,
+    pub subject: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+/// Everything stored about a user
+#[derive(Debug, Serialize)]
+pub struct AccountExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub identities: Vec<UserIdentity>,
+    pub sessions: Vec<SessionExport>,
+    pub habits: Vec<Habit>,
+    pub reminders: Vec<HabitReminder>,
+    pub check_ins: Vec<CheckIn>,
+    pub goals: Vec<Goal>,
+    pub goal_habits: Vec<GoalHabit>,
+    pub public_links: Vec<GoalPublicLink>,
+    pub shared_goals_created: Vec<SharedGoal>,
+    pub memberships: Vec<GoalParticipant>,
+    pub invites_sent: Vec<GoalInvite>,
+    pub invites_received: Vec<GoalInvite>,
+    pub activities: Vec<SharedActivity>,
+    pub reactions: Vec<ActivityReaction>,
+    pub sync_ids: Vec<SyncIdExport>,
+    pub sync_tombstones: Vec<SyncTombstoneExport>,
+    pub emails: Vec<EmailExport>,
+}
+
+/// Export all data referencing the current user, regardless of cloud sync
+pub async fn export_me(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<AccountExport>> {
+    let db = &state.db;
+    let id = user.user_id;
+
+    let user_record = sqlx::query_as::<_, User>(
+        r#"SELECT id, email, name, avatar_url,
+           provider, provider_id,
+           cloud_sync_enabled, timezone, week_start, locale,
+           notify_reminders, notify_shared_activity, notify_email,
+           created_at, updated_at
+           FROM users WHERE id = $1"#,
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let identities = sqlx::query_as::<_, UserIdentity>(
+        r#"SELECT id, user_id, provider, provider_id, email, email_verified, created_at
+           FROM user_identities WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let sessions = sqlx::query_as::<_, SessionExport>(
+        r#"SELECT id, family_id, created_at, expires_at, revoked_at
+           FROM refresh_tokens WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let habits = sqlx::query_as::<_, Habit>(
+        r#"SELECT id, user_id, name, description,
+           habit_type, unit, target_value,
+           target_direction,
+           schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+           archived, shared_goal_id, source_habit_id, created_at, updated_at
+           FROM habits WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let reminders = sqlx::query_as::<_, HabitReminder>(
+        r#"SELECT r.id, r.habit_id, r.enabled,
+                  r.reminder_type,
+                  r.interval_hours, r.daily_time, r.random_window_start, r.random_window_end,
+                  r.created_at, r.updated_at
+           FROM habit_reminders r
+           JOIN habits h ON h.id = r.habit_id
+           WHERE h.user_id = $1 ORDER BY r.created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let check_ins = sqlx::query_as::<_, CheckIn>(
+        r#"SELECT id, habit_id, user_id, value, note, effective_date, created_at
+           FROM check_ins WHERE user_id = $1 ORDER BY effective_date, created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let goals = sqlx::query_as::<_, Goal>(
+        r#"SELECT id, user_id, name, description, deadline,
+           status, is_shared, created_at, updated_at
+           FROM goals WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let goal_habits = sqlx::query_as::<_, GoalHabit>(
+        r#"SELECT gh.id, gh.goal_id, gh.habit_id, gh.weight
+           FROM goal_habits gh
+           JOIN habits h ON h.id = gh.habit_id
+           WHERE h.user_id = $1"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let public_links = sqlx::query_as::<_, GoalPublicLink>(
+        r#"SELECT id, goal_id, created_by, created_at, revoked_at
+           FROM goal_public_links WHERE created_by = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let shared_goals_created = sqlx::query_as::<_, SharedGoal>(
+        r#"SELECT id, goal_id, created_by, invite_code,
+                  invite_code_enabled, invite_code_expires_at, invite_code_max_uses, invite_code_uses,
+                  max_participants, join_role, created_at
+           FROM shared_goals WHERE created_by = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let memberships = sqlx::query_as::<_, GoalParticipant>(
+        r#"SELECT id, shared_goal_id, user_id, role, leaderboard_opt_out, joined_at
+           FROM goal_participants WHERE user_id = $1 ORDER BY joined_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let invites_sent = sqlx::query_as::<_, GoalInvite>(
+        r#"SELECT id, shared_goal_id, inviter_id, invitee_email, status, created_at, expires_at
+           FROM goal_invites WHERE inviter_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let invites_received = sqlx::query_as::<_, GoalInvite>(
+        r#"SELECT id, shared_goal_id, inviter_id, invitee_email, status, created_at, expires_at
+           FROM goal_invites
+           WHERE LOWER(invitee_email) IN (
+               SELECT LOWER(email) FROM users WHERE id = $1
+               UNION SELECT LOWER(email) FROM user_identities WHERE user_id = $1
+           )
+           ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let activities = sqlx::query_as::<_, SharedActivity>(
+        r#"SELECT id, shared_goal_id, user_id, activity_type, habit_id, check_in_id, message, created_at
+           FROM shared_activities WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let reactions = sqlx::query_as::<_, ActivityReaction>(
+        r#"SELECT id, activity_id, user_id, emoji, created_at
+           FROM activity_reactions WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let sync_ids = sqlx::query_as::<_, SyncIdExport>(
+        r#"SELECT entity_type, local_id, server_id, created_at
+           FROM sync_id_map WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let sync_tombstones = sqlx::query_as::<_, SyncTombstoneExport>(
+        r#"SELECT entity_type, server_id, deleted_at
+           FROM sync_tombstones WHERE user_id = $1 ORDER BY deleted_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let emails = sqlx::query_as::<_, EmailExport>(
+        r#"SELECT recipient, template, subject, status::text AS status, created_at, sent_at
+           FROM email_outbox WHERE user_id = $1 ORDER BY created_at"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(Json(AccountExport {
+        exported_at: Utc::now(),
+        user: user_record,
+        identities,
+        sessions,
+        habits,
+        reminders,
+        check_ins,
+        goals,
+        goal_habits,
+        public_links,
+        shared_goals_created,
+        memberships,
+        invites_sent,
+        invites_received,
+        activities,
+        reactions,
+        sync_ids,
+        sync_tombstones,
+        emails,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support::{
+        add_participant, app_state, check_in, create_goal, create_habit, create_user, share_goal,
+    };
+
+    async fn delete(db: &PgPool, user: &AuthUser) {
+        let query = DeleteAccountQuery { dissolve_shared_goals: false };
+        let _ = delete_me(Extension(app_state(db.clone())), user.clone(), Query(query)).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn deleting_an_owner_hands_the_goal_over_with_its_habits(db: PgPool) {
+        let owner = create_user(&db, "owner@example.com").await;
+        let successor = create_user(&db, "successor@example.com").await;
+        let member = create_user(&db, "member@example.com").await;
+
+        let habit_id = create_habit(&db, owner.user_id, "Run", 30).await;
+        check_in(&db, owner.user_id, habit_id, 1).await;
+        let goal_id = create_goal(&db, owner.user_id, "Marathon", &[habit_id]).await;
+        let shared_goal_id = share_goal(&db, owner.user_id, goal_id).await;
+
+        // The successor never copied the goal's habits; the member did
+        add_participant(&db, shared_goal_id, successor.user_id, "collaborator").await;
+        add_participant(&db, shared_goal_id, member.user_id, "collaborator").await;
+        let mut conn = db.acquire().await.unwrap();
+        shared_goals::copy_linked_habits(&mut conn, shared_goal_id, member.user_id).await.unwrap();
+        drop(conn);
+
+        delete(&db, &owner).await;
+
+        let (goal_owner,): (Uuid,) = sqlx::query_as("SELECT user_id FROM goals WHERE id = $1")
+            .bind(goal_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(goal_owner, successor.user_id);
+
+        let linked: Vec<(Uuid, Uuid, Option<Uuid>)> = sqlx::query_as(
+            r#"SELECT h.id, h.user_id, h.source_habit_id FROM goal_habits gh
+               JOIN habits h ON h.id = gh.habit_id
+               WHERE gh.goal_id = $1"#,
+        )
+        .bind(goal_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(linked.len(), 2);
+
+        // The successor's copy is the new original of the member's
+        let (successor_copy, _, successor_source) = linked.iter().find(|(_, user_id, _)| *user_id == successor.user_id).unwrap();
+        let (_, _, member_source) = linked.iter().find(|(_, user_id, _)| *user_id == member.user_id).unwrap();
+        assert_eq!(*successor_source, None);
+        assert_eq!(*member_source, Some(*successor_copy));
+
+        let mut conn = db.acquire().await.unwrap();
+        let copied = shared_goals::copy_linked_habits(&mut conn, shared_goal_id, member.user_id).await.unwrap();
+        assert_eq!(copied, 0);
+    }
+}
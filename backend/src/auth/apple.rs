@@ -0,0 +1,231 @@
+//! Apple id_token verification
+//!
+//! Apple signs id_tokens with rotating RSA keys published as a JWKS. Keys are
+//! cached and refetched when a token references an unknown `kid`.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use crate::error::{ApiError, ApiResult};
+
+pub const DEFAULT_APPLE_BASE_URL: &str = "https://appleid.apple.com";
+
+/// How long fetched keys are trusted before refetching
+const JWKS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Minimum time between refetches triggered by unknown key ids
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+pub struct AppleIdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    /// Apple sends either a boolean or the string "true"/"false"
+    pub email_verified: Option<serde_json::Value>,
+    pub nonce: Option<String>,
+}
+
+impl AppleIdTokenClaims {
+    pub fn is_email_verified(&self) -> bool {
+        match &self.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        }
+    }
+}
+
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Fetches and caches Apple's signing keys
+#[derive(Clone)]
+pub struct AppleKeyStore {
+    /// Issuer and JWKS host, configurable so tests can use a local stand-in
+    base_url: String,
+    http: reqwest::Client,
+    cache: Arc<RwLock<Option<CachedJwks>>>,
+}
+
+impl AppleKeyStore {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            cache: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Verify an id_token's signature, issuer, audience, expiry and nonce
+    pub async fn verify_id_token(
+        &self,
+        id_token: &str,
+        client_id: &str,
+        nonce: Option<&str>,
+    ) -> ApiResult<AppleIdTokenClaims> {
+        let header = decode_header(id_token)
+            .map_err(|_| ApiError::OAuth("Invalid id_token format".to_string()))?;
+        let kid = header
+            .kid
+            .ok_or_else(|| ApiError::OAuth("id_token is missing a key id".to_string()))?;
+
+        let key = self.decoding_key(&kid).await?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&self.base_url]);
+        validation.set_audience(&[client_id]);
+
+        let claims = decode::<AppleIdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| ApiError::OAuth(format!("Invalid id_token: {}", e)))?
+            .claims;
+
+        let nonce = nonce.ok_or_else(|| ApiError::OAuth("Missing nonce".to_string()))?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(ApiError::OAuth("id_token nonce mismatch".to_string()));
+        }
+
+        Ok(claims)
+    }
+
+    async fn decoding_key(&self, kid: &str) -> ApiResult<DecodingKey> {
+        {
+            let cache = self.cache.read().await;
+            if let Some(cached) = cache.as_ref() {
+                if cached.fetched_at.elapsed() < JWKS_CACHE_TTL {
+                    if let Some(jwk) = cached.keys.find(kid) {
+                        return key_from_jwk(jwk);
+                    }
+                }
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+
+        // Another request may have refreshed while we waited for the lock
+        let needs_fetch = match cache.as_ref() {
+            None => true,
+            Some(cached) => {
+                cached.fetched_at.elapsed() >= JWKS_CACHE_TTL
+                    || (cached.keys.find(kid).is_none()
+                        && cached.fetched_at.elapsed() >= JWKS_MIN_REFRESH_INTERVAL)
+            }
+        };
+
+        if needs_fetch {
+            let keys = self.fetch_keys().await?;
+            *cache = Some(CachedJwks {
+                keys,
+                fetched_at: Instant::now(),
+            });
+        }
+
+        let jwk = cache
+            .as_ref()
+            .and_then(|cached| cached.keys.find(kid))
+            .ok_or_else(|| ApiError::OAuth("Unknown id_token signing key".to_string()))?;
+
+        key_from_jwk(jwk)
+    }
+
+    async fn fetch_keys(&self) -> ApiResult<JwkSet> {
+        self.http
+            .get(format!("{}/auth/keys", self.base_url))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ApiError::OAuth(format!("Failed to fetch Apple keys: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| ApiError::OAuth(format!("Failed to parse Apple keys: {}", e)))
+    }
+}
+
+fn key_from_jwk(jwk: &jsonwebtoken::jwk::Jwk) -> ApiResult<DecodingKey> {
+    DecodingKey::from_jwk(jwk).map_err(|e| ApiError::OAuth(format!("Invalid Apple key: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::test_support::{apple_id_token, mock_apple_keys, APPLE_CLIENT_ID as CLIENT_ID};
+
+    async fn mock_apple() -> (AppleKeyStore, Arc<AtomicUsize>) {
+        let (base_url, fetches) = mock_apple_keys().await;
+        (AppleKeyStore::new(base_url), fetches)
+    }
+
+    fn id_token(store: &AppleKeyStore, kid: &str, claims: serde_json::Value) -> String {
+        apple_id_token(&store.base_url, kid, claims)
+    }
+
+    #[tokio::test]
+    async fn tokens_signed_with_a_published_key_verify() {
+        let (store, fetches) = mock_apple().await;
+
+        let token = id_token(&store, "test-key", serde_json::json!({}));
+        let claims = store.verify_id_token(&token, CLIENT_ID, Some("nonce-1")).await.unwrap();
+        assert_eq!(claims.sub, "apple-123");
+        assert_eq!(claims.email.as_deref(), Some("apple@example.com"));
+        assert!(claims.is_email_verified());
+
+        // Keys are cached between verifications
+        store.verify_id_token(&token, CLIENT_ID, Some("nonce-1")).await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn tokens_with_the_wrong_claims_are_rejected() {
+        let (store, _) = mock_apple().await;
+
+        let cases = [
+            serde_json::json!({ "aud": "com.example.other" }),
+            serde_json::json!({ "iss": "https://appleid.example.com" }),
+            serde_json::json!({ "exp": chrono::Utc::now().timestamp() - 600 }),
+            serde_json::json!({ "nonce": "nonce-2" }),
+        ];
+        for claims in cases {
+            let token = id_token(&store, "test-key", claims.clone());
+            let result = store.verify_id_token(&token, CLIENT_ID, Some("nonce-1")).await;
+            assert!(matches!(result, Err(ApiError::OAuth(_))), "accepted {claims}");
+        }
+
+        let token = id_token(&store, "test-key", serde_json::json!({}));
+        assert!(store.verify_id_token(&token, CLIENT_ID, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn tampered_tokens_and_unknown_keys_are_rejected() {
+        let (store, fetches) = mock_apple().await;
+
+        let token = id_token(&store, "test-key", serde_json::json!({}));
+        let other = id_token(&store, "test-key", serde_json::json!({ "sub": "apple-456" }));
+        let mut parts: Vec<&str> = token.split('.').collect();
+        parts[1] = other.split('.').nth(1).unwrap();
+        assert!(store.verify_id_token(&parts.join("."), CLIENT_ID, Some("nonce-1")).await.is_err());
+
+        // Unknown key ids don't refetch more than once a minute
+        let token = id_token(&store, "rotated-key", serde_json::json!({}));
+        assert!(store.verify_id_token(&token, CLIENT_ID, Some("nonce-1")).await.is_err());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn email_verified_accepts_booleans_and_strings() {
+        let claims = |verified| AppleIdTokenClaims { sub: String::new(), email: None, email_verified: verified, nonce: None };
+
+        assert!(claims(Some(serde_json::json!(true))).is_email_verified());
+        assert!(claims(Some(serde_json::json!("true"))).is_email_verified());
+        assert!(!claims(Some(serde_json::json!("false"))).is_email_verified());
+        assert!(!claims(None).is_email_verified());
+    }
+}
@@ -0,0 +1,366 @@
+//! Provider identities and account linking
+//!
+//! A user can sign in with several provider identities. New identities are
+//! attached to an existing account automatically only when both carry the
+//! same provider-verified email; otherwise linking has to be done explicitly
+//! while signed in.
+
+use axum::{extract::Path, Extension, Json};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{AppState, error::{ApiError, ApiResult}, mail::{self, Template}, models::*};
+use super::middleware::AuthUser;
+
+/// Identity details returned by a provider after a successful sign in
+#[derive(Debug, Clone)]
+pub struct ProviderIdentity {
+    pub provider: AuthProvider,
+    pub provider_id: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// Resolve the user for a provider identity, creating one if needed
+pub async fn sign_in(db: &sqlx::PgPool, identity: &ProviderIdentity) -> ApiResult<User> {
+    let mut tx = db.begin().await?;
+
+    let existing: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT user_id FROM user_identities WHERE provider = $1 AND provider_id = $2",
+    )
+    .bind(&identity.provider)
+    .bind(&identity.provider_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let user_id = match existing {
+        Some((user_id,)) => {
+            sqlx::query(
+                r#"UPDATE user_identities SET email = $3, email_verified = $4
+                   WHERE provider = $1 AND provider_id = $2"#,
+            )
+            .bind(&identity.provider)
+            .bind(&identity.provider_id)
+            .bind(&identity.email)
+            .bind(identity.email_verified)
+            .execute(&mut *tx)
+            .await?;
+
+            user_id
+        }
+        None => match find_by_verified_email(&mut tx, identity).await? {
+            Some(user_id) => {
+                insert_identity(&mut tx, user_id, identity).await?;
+                user_id
+            }
+            None => create_user(&mut tx, identity).await?,
+        },
+    };
+
+    let user = sqlx::query_as::<_, User>(
+        r#"UPDATE users SET
+           email = CASE WHEN provider = $2 AND provider_id = $3 THEN $4 ELSE email END,
+           name = COALESCE($5, name),
+           avatar_url = CASE WHEN avatar_key IS NULL THEN COALESCE($6, avatar_url) ELSE avatar_url END,
+           updated_at = NOW()
+           WHERE id = $1
+           RETURNING id, email, name, avatar_url,
+                     provider, provider_id,
+                     cloud_sync_enabled, timezone, week_start, locale,
+                     notify_reminders, notify_shared_activity, notify_email,
+                     created_at, updated_at"#,
+    )
+    .bind(user_id)
+    .bind(&identity.provider)
+    .bind(&identity.provider_id)
+    .bind(&identity.email)
+    .bind(&identity.name)
+    .bind(&identity.avatar_url)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(user)
+}
+
+/// Attach a provider identity to a signed-in user. If the identity already
+/// belongs to another account, that account is merged in when both share a
+/// verified email.
+pub async fn link(db: &sqlx::PgPool, user_id: Uuid, identity: &ProviderIdentity) -> ApiResult<()> {
+    let mut tx = db.begin().await?;
+
+    let owner: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT user_id FROM user_identities WHERE provider = $1 AND provider_id = $2",
+    )
+    .bind(&identity.provider)
+    .bind(&identity.provider_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match owner {
+        None => insert_identity(&mut tx, user_id, identity).await?,
+        Some((owner_id,)) if owner_id == user_id => return Ok(()),
+        Some((owner_id,)) => {
+            let shares_verified_email: Option<(Uuid,)> = sqlx::query_as(
+                r#"SELECT id FROM user_identities
+                   WHERE user_id = $1 AND email_verified AND LOWER(email) = LOWER($2)
+                   LIMIT 1"#,
+            )
+            .bind(user_id)
+            .bind(&identity.email)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if !identity.email_verified || shares_verified_email.is_none() {
+                return Err(ApiError::Conflict(
+                    "This sign-in is already used by another account".to_string(),
+                ));
+            }
+
+            merge_users(&mut tx, owner_id, user_id).await?;
+        }
+    }
+
+    let notice = Template::IdentityLinked {
+        provider: &identity.provider,
+        email: &identity.email,
+    };
+    send_security_notice(&mut tx, user_id, &notice).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// List the identities linked to the current user
+pub async fn list_identities(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<Vec<UserIdentity>>> {
+    let identities = sqlx::query_as::<_, UserIdentity>(
+        r#"SELECT id, user_id, provider, provider_id, email, email_verified, created_at
+           FROM user_identities WHERE user_id = $1
+           ORDER BY created_at ASC"#,
+    )
+    .bind(user.user_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(identities))
+}
+
+/// Unlink an identity; the last remaining identity can't be removed
+pub async fn unlink_identity(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let mut tx = state.db.begin().await?;
+
+    let identities = sqlx::query_as::<_, UserIdentity>(
+        r#"SELECT id, user_id, provider, provider_id, email, email_verified, created_at
+           FROM user_identities WHERE user_id = $1
+           ORDER BY created_at ASC
+           FOR UPDATE"#,
+    )
+    .bind(user.user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let removed = identities
+        .iter()
+        .find(|i| i.id == id)
+        .ok_or(ApiError::NotFound)?;
+
+    let remaining = identities
+        .iter()
+        .find(|i| i.id != id)
+        .ok_or_else(|| ApiError::BadRequest("Cannot unlink the only sign-in method".to_string()))?;
+
+    sqlx::query("DELETE FROM user_identities WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Keep the account's primary identity pointing at a linked one
+    sqlx::query(
+        r#"UPDATE users SET provider = $4, provider_id = $5, updated_at = NOW()
+           WHERE id = $1 AND provider = $2 AND provider_id = $3"#,
+    )
+    .bind(user.user_id)
+    .bind(&removed.provider)
+    .bind(&removed.provider_id)
+    .bind(&remaining.provider)
+    .bind(&remaining.provider_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let notice = Template::IdentityUnlinked { provider: &removed.provider };
+    send_security_notice(&mut tx, user.user_id, &notice).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({ "unlinked": true })))
+}
+
+// ============ Helper Functions ============
+
+async fn find_by_verified_email(
+    conn: &mut PgConnection,
+    identity: &ProviderIdentity,
+) -> ApiResult<Option<Uuid>> {
+    if !identity.email_verified {
+        return Ok(None);
+    }
+
+    let user: Option<(Uuid,)> = sqlx::query_as(
+        r#"SELECT user_id FROM user_identities
+           WHERE email_verified AND LOWER(email) = LOWER($1)
+           ORDER BY created_at ASC
+           LIMIT 1"#,
+    )
+    .bind(&identity.email)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(user.map(|(id,)| id))
+}
+
+async fn insert_identity(conn: &mut PgConnection, user_id: Uuid, identity: &ProviderIdentity) -> ApiResult<()> {
+    sqlx::query(
+        r#"INSERT INTO user_identities (id, user_id, provider, provider_id, email, email_verified, created_at)
+           VALUES ($1, $2, $3, $4, $5, $6, NOW())"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(&identity.provider)
+    .bind(&identity.provider_id)
+    .bind(&identity.email)
+    .bind(identity.email_verified)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn create_user(conn: &mut PgConnection, identity: &ProviderIdentity) -> ApiResult<Uuid> {
+    let user_id = Uuid::new_v4();
+
+    sqlx::query(
+        r#"INSERT INTO users (id, email, name, avatar_url, provider, provider_id, cloud_sync_enabled, created_at, updated_at)
+           VALUES ($1, $2, $3, $4, $5, $6, false, NOW(), NOW())"#,
+    )
+    .bind(user_id)
+    .bind(&identity.email)
+    .bind(&identity.name)
+    .bind(&identity.avatar_url)
+    .bind(&identity.provider)
+    .bind(&identity.provider_id)
+    .execute(&mut *conn)
+    .await?;
+
+    insert_identity(conn, user_id, identity).await?;
+
+    Ok(user_id)
+}
+
+/// Email the account's address about a change to its sign-in methods. These
+/// are sent regardless of the user's email preferences.
+async fn send_security_notice(conn: &mut PgConnection, user_id: Uuid, template: &Template<'_>) -> ApiResult<()> {
+    let (email,): (String,) = sqlx::query_as("SELECT email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    mail::enqueue(conn, Some(user_id), &email, template).await?;
+
+    Ok(())
+}
+
+/// Move everything owned by `from` to `into` and delete `from`
+async fn merge_users(conn: &mut PgConnection, from: Uuid, into: Uuid) -> ApiResult<()> {
+    // Shared goals both users take part in keep a single participant row,
+    // with the stronger of the two roles
+    sqlx::query(
+        r#"UPDATE goal_participants keep SET role = other.role
+           FROM goal_participants other
+           WHERE keep.user_id = $2 AND other.user_id = $1
+             AND keep.shared_goal_id = other.shared_goal_id
+             AND other.role < keep.role"#,
+    )
+    .bind(from)
+    .bind(into)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"DELETE FROM goal_participants gp
+           WHERE gp.user_id = $1
+             AND EXISTS (SELECT 1 FROM goal_participants o
+                         WHERE o.shared_goal_id = gp.shared_goal_id AND o.user_id = $2)"#,
+    )
+    .bind(from)
+    .bind(into)
+    .execute(&mut *conn)
+    .await?;
+
+    // Likewise for reactions both users left on the same item
+    sqlx::query(
+        r#"DELETE FROM activity_reactions r
+           WHERE r.user_id = $1
+             AND EXISTS (SELECT 1 FROM activity_reactions o
+                         WHERE o.activity_id = r.activity_id AND o.emoji = r.emoji AND o.user_id = $2)"#,
+    )
+    .bind(from)
+    .bind(into)
+    .execute(&mut *conn)
+    .await?;
+
+    for statement in [
+        "UPDATE habits SET user_id = $2 WHERE user_id = $1",
+        "UPDATE check_ins SET user_id = $2 WHERE user_id = $1",
+        "UPDATE goals SET user_id = $2 WHERE user_id = $1",
+        "UPDATE goal_public_links SET created_by = $2 WHERE created_by = $1",
+        "UPDATE goal_participants SET user_id = $2 WHERE user_id = $1",
+        "UPDATE shared_goals SET created_by = $2 WHERE created_by = $1",
+        "UPDATE goal_invites SET inviter_id = $2 WHERE inviter_id = $1",
+        "UPDATE shared_activities SET user_id = $2 WHERE user_id = $1",
+        "UPDATE activity_reactions SET user_id = $2 WHERE user_id = $1",
+        "UPDATE user_identities SET user_id = $2 WHERE user_id = $1",
+        "UPDATE sync_tombstones SET user_id = $2 WHERE user_id = $1",
+        "UPDATE email_outbox SET user_id = $2 WHERE user_id = $1",
+        // Local IDs the surviving account already maps keep their mapping
+        r#"UPDATE sync_id_map m SET user_id = $2
+           WHERE m.user_id = $1
+             AND NOT EXISTS (SELECT 1 FROM sync_id_map o
+                             WHERE o.user_id = $2 AND o.entity_type = m.entity_type AND o.local_id = m.local_id)"#,
+    ] {
+        sqlx::query(statement)
+            .bind(from)
+            .bind(into)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query(
+        r#"UPDATE users SET
+           cloud_sync_enabled = cloud_sync_enabled OR (SELECT cloud_sync_enabled FROM users WHERE id = $1),
+           updated_at = NOW()
+           WHERE id = $2"#,
+    )
+    .bind(from)
+    .bind(into)
+    .execute(&mut *conn)
+    .await?;
+
+    // Cascades to the merged account's refresh tokens
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(from)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
@@ -1,20 +1,22 @@
 //! JWT token handling
 
-use axum::{Extension, Json};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
+use uuid::Uuid;
 
-use crate::{AppState, error::{ApiError, ApiResult}, models::*};
+use crate::{error::ApiResult, models::*};
 
 const ACCESS_TOKEN_EXPIRY_HOURS: i64 = 24;
-const REFRESH_TOKEN_EXPIRY_DAYS: i64 = 30;
 
-/// Generate access and refresh tokens for a user
-pub fn generate_tokens(user: &User, secret: &str) -> ApiResult<(String, String)> {
+/// Audience of public goal link tokens, which keeps them from passing as
+/// access tokens
+const PUBLIC_LINK_AUDIENCE: &str = "public-goal";
+
+/// Generate an access token for a user
+pub fn generate_access_token(user: &User, secret: &str) -> ApiResult<String> {
     let now = Utc::now();
-    
-    // Access token
-    let access_claims = Claims {
+
+    let claims = Claims {
         sub: user.id,
         email: user.email.clone(),
         exp: (now + Duration::hours(ACCESS_TOKEN_EXPIRY_HOURS)).timestamp(),
@@ -23,25 +25,11 @@ pub fn generate_tokens(user: &User, secret: &str) -> ApiResult<(String, String)>
 
     let access_token = encode(
         &Header::default(),
-        &access_claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )?;
-
-    // Refresh token (longer expiry)
-    let refresh_claims = Claims {
-        sub: user.id,
-        email: user.email.clone(),
-        exp: (now + Duration::days(REFRESH_TOKEN_EXPIRY_DAYS)).timestamp(),
-        iat: now.timestamp(),
-    };
-
-    let refresh_token = encode(
-        &Header::default(),
-        &refresh_claims,
+        &claims,
         &EncodingKey::from_secret(secret.as_bytes()),
     )?;
 
-    Ok((access_token, refresh_token))
+    Ok(access_token)
 }
 
 /// Validate a JWT token and extract claims
@@ -55,39 +43,34 @@ pub fn validate_token(token: &str, secret: &str) -> ApiResult<Claims> {
     Ok(token_data.claims)
 }
 
-/// Refresh access token endpoint
-pub async fn refresh_token(
-    Extension(state): Extension<AppState>,
-    Json(body): Json<RefreshTokenRequest>,
-) -> ApiResult<Json<AuthResponse>> {
-    // Validate refresh token
-    let claims = validate_token(&body.refresh_token, &state.jwt_secret)?;
+/// Sign a public goal link. The token never expires; revoking the link is
+/// what stops it working. The same link always gives the same token.
+pub fn generate_public_link_token(link_id: Uuid, created_at: DateTime<Utc>, secret: &str) -> ApiResult<String> {
+    let claims = PublicLinkClaims {
+        sub: link_id,
+        aud: PUBLIC_LINK_AUDIENCE.to_string(),
+        iat: created_at.timestamp(),
+    };
 
-    // Fetch user
-    let user = sqlx::query_as::<_, User>(
-        r#"SELECT id, email, name, avatar_url, 
-           provider, provider_id,
-           cloud_sync_enabled, created_at, updated_at 
-           FROM users WHERE id = $1"#,
-    )
-    .bind(claims.sub)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::Unauthorized)?;
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?)
+}
 
-    // Generate new tokens
-    let (access_token, refresh_token) = generate_tokens(&user, &state.jwt_secret)?;
+/// Check a public link token's signature and return its claims
+pub fn validate_public_link_token(token: &str, secret: &str) -> ApiResult<PublicLinkClaims> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[PUBLIC_LINK_AUDIENCE]);
+    validation.set_required_spec_claims(&["aud", "sub"]);
+    validation.validate_exp = false;
 
-    Ok(Json(AuthResponse {
-        access_token,
-        refresh_token,
-        user: user.into(),
-    }))
-}
+    let token_data = decode::<PublicLinkClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )?;
 
-/// Logout endpoint (client should discard tokens)
-pub async fn logout() -> ApiResult<Json<serde_json::Value>> {
-    // In a more robust implementation, you'd invalidate the refresh token
-    // by storing it in a blacklist or removing it from a whitelist
-    Ok(Json(serde_json::json!({ "success": true })))
+    Ok(token_data.claims)
 }
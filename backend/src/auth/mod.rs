@@ -1,11 +1,16 @@
 //! Authentication module
 
+pub mod account;
+pub mod apple;
+pub mod identities;
 pub mod jwt;
 pub mod oauth;
 pub mod middleware;
+pub mod profile;
+pub mod refresh;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 
@@ -17,10 +22,17 @@ pub fn routes() -> Router {
         .route("/apple", get(oauth::apple_auth))
         .route("/apple/callback", post(oauth::apple_callback))
         // Token routes
-        .route("/refresh", post(jwt::refresh_token))
-        .route("/logout", post(jwt::logout))
+        .route("/refresh", post(refresh::refresh_token))
+        .route("/logout", post(refresh::logout))
         // User info
-        .route("/me", get(get_me))
+        .route("/me", get(get_me).patch(profile::update_me).delete(account::delete_me))
+        .route("/me/export", get(account::export_me))
+        .route("/me/avatar", put(profile::upload_avatar).delete(profile::delete_avatar))
+        // Linked sign-in methods
+        .route("/identities", get(identities::list_identities))
+        .route("/identities/google", post(oauth::link_google))
+        .route("/identities/apple", post(oauth::link_apple))
+        .route("/identities/:id", delete(identities::unlink_identity))
 }
 
 use axum::{Extension, Json};
@@ -33,7 +45,9 @@ async fn get_me(
     let user = sqlx::query_as::<_, crate::models::User>(
         r#"SELECT id, email, name, avatar_url, 
            provider, provider_id,
-           cloud_sync_enabled, created_at, updated_at 
+           cloud_sync_enabled, timezone, week_start, locale,
+           notify_reminders, notify_shared_activity, notify_email,
+           created_at, updated_at 
            FROM users WHERE id = $1"#,
     )
     .bind(claims.user_id)
@@ -5,24 +5,44 @@ use axum::{
     response::Redirect,
     Extension, Json,
 };
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use oauth2::{
     basic::BasicClient, AuthUrl, ClientId, ClientSecret, CsrfToken,
     RedirectUrl, Scope, TokenUrl, AuthorizationCode, TokenResponse,
+    PkceCodeChallenge, PkceCodeVerifier,
     reqwest::async_http_client,
 };
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use sha2::{Digest, Sha256};
 
 use crate::{AppState, error::{ApiError, ApiResult}, models::*};
-use super::jwt;
+use super::{
+    apple::{self, AppleKeyStore},
+    identities::{self, ProviderIdentity},
+    middleware::AuthUser,
+    refresh,
+};
+
+/// How long an authorization request may take before its state expires
+const OAUTH_STATE_EXPIRY_MINUTES: i32 = 10;
+
+/// Cookie binding an authorization request to the browser that started it
+const OAUTH_STATE_COOKIE: &str = "oauth_state";
 
 /// OAuth client configuration
 #[derive(Clone)]
 pub struct OAuthClients {
-    pub google: Option<BasicClient>,
+    pub google: Option<GoogleOAuthConfig>,
     pub apple: Option<AppleOAuthConfig>,
 }
 
+#[derive(Clone)]
+pub struct GoogleOAuthConfig {
+    pub client: BasicClient,
+    pub userinfo_url: String,
+}
+
 #[derive(Clone)]
 pub struct AppleOAuthConfig {
     pub client_id: String,
@@ -30,6 +50,7 @@ pub struct AppleOAuthConfig {
     pub key_id: String,
     pub private_key: String,
     pub redirect_uri: String,
+    pub keys: AppleKeyStore,
 }
 
 impl OAuthClients {
@@ -42,13 +63,23 @@ impl OAuthClients {
             let redirect_uri = std::env::var("GOOGLE_REDIRECT_URI")
                 .unwrap_or_else(|_| "http://localhost:5173/auth/google/callback".to_string());
 
-            Some(BasicClient::new(
+            // Endpoints are configurable so the flow can run against a mock provider
+            let auth_url = std::env::var("GOOGLE_AUTH_URL")
+                .unwrap_or_else(|_| "https://accounts.google.com/o/oauth2/v2/auth".to_string());
+            let token_url = std::env::var("GOOGLE_TOKEN_URL")
+                .unwrap_or_else(|_| "https://oauth2.googleapis.com/token".to_string());
+            let userinfo_url = std::env::var("GOOGLE_USERINFO_URL")
+                .unwrap_or_else(|_| "https://www.googleapis.com/oauth2/v2/userinfo".to_string());
+
+            let client = BasicClient::new(
                 ClientId::new(client_id),
                 Some(ClientSecret::new(client_secret)),
-                AuthUrl::new("https://accounts.google.com/o/oauth2/v2/auth".to_string())?,
-                Some(TokenUrl::new("https://oauth2.googleapis.com/token".to_string())?),
+                AuthUrl::new(auth_url)?,
+                Some(TokenUrl::new(token_url)?),
             )
-            .set_redirect_uri(RedirectUrl::new(redirect_uri)?))
+            .set_redirect_uri(RedirectUrl::new(redirect_uri)?);
+
+            Some(GoogleOAuthConfig { client, userinfo_url })
         } else {
             tracing::warn!("Google OAuth not configured");
             None
@@ -63,6 +94,8 @@ impl OAuthClients {
         ) {
             let redirect_uri = std::env::var("APPLE_REDIRECT_URI")
                 .unwrap_or_else(|_| "http://localhost:3000/auth/apple/callback".to_string());
+            let base_url = std::env::var("APPLE_AUTH_BASE_URL")
+                .unwrap_or_else(|_| apple::DEFAULT_APPLE_BASE_URL.to_string());
 
             Some(AppleOAuthConfig {
                 client_id,
@@ -70,6 +103,7 @@ impl OAuthClients {
                 key_id,
                 private_key,
                 redirect_uri,
+                keys: AppleKeyStore::new(base_url),
             })
         } else {
             tracing::warn!("Apple Sign In not configured");
@@ -92,6 +126,7 @@ pub struct OAuthCallbackQuery {
 struct GoogleUserInfo {
     pub id: String,
     pub email: String,
+    pub verified_email: Option<bool>,
     pub name: Option<String>,
     pub picture: Option<String>,
 }
@@ -99,39 +134,133 @@ struct GoogleUserInfo {
 /// Initiate Google OAuth flow
 pub async fn google_auth(
     Extension(state): Extension<AppState>,
-) -> ApiResult<Redirect> {
-    let client = state.oauth.google
+    jar: CookieJar,
+) -> ApiResult<(CookieJar, Redirect)> {
+    let google = state.oauth.google
         .as_ref()
         .ok_or_else(|| ApiError::OAuth("Google OAuth not configured".to_string()))?;
 
-    let (auth_url, _csrf_token) = client
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let (auth_url, csrf_token) = google.client
         .authorize_url(CsrfToken::new_random)
         .add_scope(Scope::new("email".to_string()))
         .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
         .url();
 
-    Ok(Redirect::to(auth_url.as_str()))
+    // Drop abandoned authorization attempts
+    sqlx::query("DELETE FROM oauth_states WHERE expires_at < NOW()")
+        .execute(&state.db)
+        .await?;
+
+    sqlx::query(
+        r#"INSERT INTO oauth_states (state, pkce_verifier, created_at, expires_at)
+           VALUES ($1, $2, NOW(), NOW() + make_interval(mins => $3))"#,
+    )
+    .bind(csrf_token.secret())
+    .bind(pkce_verifier.secret())
+    .bind(OAUTH_STATE_EXPIRY_MINUTES)
+    .execute(&state.db)
+    .await?;
+
+    // Only this browser can complete the request, so a callback URL carrying
+    // someone else's code and state can't sign it into their account
+    let secure = google.client.redirect_url().is_none_or(|url| url.url().scheme() == "https");
+    let cookie = Cookie::build((OAUTH_STATE_COOKIE, state_hash(csrf_token.secret())))
+        .path("/auth")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(OAUTH_STATE_EXPIRY_MINUTES.into()));
+
+    Ok((jar.add(cookie), Redirect::to(auth_url.as_str())))
 }
 
 /// Handle Google OAuth callback
 pub async fn google_callback(
     Extension(state): Extension<AppState>,
+    jar: CookieJar,
     Query(query): Query<OAuthCallbackQuery>,
-) -> ApiResult<Json<AuthResponse>> {
-    let client = state.oauth.google
+) -> ApiResult<(CookieJar, Json<AuthResponse>)> {
+    let identity = google_identity(&state, &jar, query).await?;
+
+    // Create or update user
+    let user = identities::sign_in(&state.db, &identity).await?;
+
+    // Generate tokens
+    let (access_token, refresh_token) = refresh::issue_tokens(&state.db, &user, &state.jwt_secret).await?;
+
+    Ok((
+        jar.remove(state_cookie_removal()),
+        Json(AuthResponse {
+            access_token,
+            refresh_token,
+            user: user.into(),
+        }),
+    ))
+}
+
+/// Link a Google account to the signed-in user
+pub async fn link_google(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    jar: CookieJar,
+    Json(body): Json<OAuthCallbackQuery>,
+) -> ApiResult<(CookieJar, Json<serde_json::Value>)> {
+    let identity = google_identity(&state, &jar, body).await?;
+    identities::link(&state.db, user.user_id, &identity).await?;
+
+    Ok((jar.remove(state_cookie_removal()), Json(serde_json::json!({ "linked": true }))))
+}
+
+/// The state cookie holds a hash so the state itself never sits in the browser
+fn state_hash(csrf_state: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(csrf_state.as_bytes()))
+}
+
+fn state_cookie_removal() -> Cookie<'static> {
+    Cookie::build(OAUTH_STATE_COOKIE).path("/auth").build()
+}
+
+/// Validate the callback state and exchange the code for the user's identity
+async fn google_identity(state: &AppState, jar: &CookieJar, query: OAuthCallbackQuery) -> ApiResult<ProviderIdentity> {
+    let google = state.oauth.google
         .as_ref()
         .ok_or_else(|| ApiError::OAuth("Google OAuth not configured".to_string()))?;
 
+    let csrf_state = query.state
+        .ok_or_else(|| ApiError::OAuth("Missing state".to_string()))?;
+
+    // The state must come back to the browser that asked for it
+    let bound = jar.get(OAUTH_STATE_COOKIE)
+        .is_some_and(|cookie| cookie.value() == state_hash(&csrf_state));
+    if !bound {
+        return Err(ApiError::OAuth("State does not belong to this browser".to_string()));
+    }
+
+    // States are single use: consume it whether or not the exchange succeeds
+    let pkce_verifier: Option<(String,)> = sqlx::query_as(
+        "DELETE FROM oauth_states WHERE state = $1 AND expires_at > NOW() RETURNING pkce_verifier",
+    )
+    .bind(&csrf_state)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let (pkce_verifier,) = pkce_verifier
+        .ok_or_else(|| ApiError::OAuth("Invalid or expired state".to_string()))?;
+
     // Exchange code for token
-    let token = client
+    let token = google.client
         .exchange_code(AuthorizationCode::new(query.code))
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
         .request_async(async_http_client)
         .await
         .map_err(|e| ApiError::OAuth(format!("Token exchange failed: {}", e)))?;
 
     // Fetch user info
     let user_info: GoogleUserInfo = reqwest::Client::new()
-        .get("https://www.googleapis.com/oauth2/v2/userinfo")
+        .get(&google.userinfo_url)
         .bearer_auth(token.access_token().secret())
         .send()
         .await
@@ -140,24 +269,14 @@ pub async fn google_callback(
         .await
         .map_err(|e| ApiError::OAuth(format!("Failed to parse user info: {}", e)))?;
 
-    // Create or update user
-    let user = upsert_user(
-        &state.db,
-        &user_info.email,
-        user_info.name.as_deref(),
-        user_info.picture.as_deref(),
-        AuthProvider::Google,
-        &user_info.id,
-    ).await?;
-
-    // Generate tokens
-    let (access_token, refresh_token) = jwt::generate_tokens(&user, &state.jwt_secret)?;
-
-    Ok(Json(AuthResponse {
-        access_token,
-        refresh_token,
-        user: user.into(),
-    }))
+    Ok(ProviderIdentity {
+        provider: AuthProvider::Google,
+        provider_id: user_info.id,
+        email: user_info.email,
+        email_verified: user_info.verified_email.unwrap_or(false),
+        name: user_info.name,
+        avatar_url: user_info.picture,
+    })
 }
 
 // ============ Apple Sign In ============
@@ -167,6 +286,7 @@ pub struct AppleCallbackBody {
     pub code: String,
     pub id_token: Option<String>,
     pub user: Option<String>, // JSON string with user info on first sign in
+    pub nonce: Option<String>, // Nonce from GET /auth/apple, passed to Apple
 }
 
 #[derive(Debug, Deserialize)]
@@ -183,13 +303,8 @@ struct AppleNameInfo {
     pub last_name: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct AppleIdTokenClaims {
-    pub sub: String,
-    pub email: Option<String>,
-}
-
-/// Initiate Apple Sign In (returns config for client-side)
+/// Initiate Apple Sign In (returns config for client-side, with a
+/// single-use nonce to pass to Apple)
 pub async fn apple_auth(
     Extension(state): Extension<AppState>,
 ) -> ApiResult<Json<AppleAuthConfig>> {
@@ -197,9 +312,26 @@ pub async fn apple_auth(
         .as_ref()
         .ok_or_else(|| ApiError::OAuth("Apple Sign In not configured".to_string()))?;
 
+    let nonce = CsrfToken::new_random().secret().clone();
+
+    // Drop abandoned sign-in attempts
+    sqlx::query("DELETE FROM apple_nonces WHERE expires_at < NOW()")
+        .execute(&state.db)
+        .await?;
+
+    sqlx::query(
+        r#"INSERT INTO apple_nonces (nonce, created_at, expires_at)
+           VALUES ($1, NOW(), NOW() + make_interval(mins => $2))"#,
+    )
+    .bind(&nonce)
+    .bind(OAUTH_STATE_EXPIRY_MINUTES)
+    .execute(&state.db)
+    .await?;
+
     Ok(Json(AppleAuthConfig {
         client_id: config.client_id.clone(),
         redirect_uri: config.redirect_uri.clone(),
+        nonce,
     }))
 }
 
@@ -207,6 +339,7 @@ pub async fn apple_auth(
 pub struct AppleAuthConfig {
     pub client_id: String,
     pub redirect_uri: String,
+    pub nonce: String,
 }
 
 /// Handle Apple Sign In callback
@@ -214,27 +347,61 @@ pub async fn apple_callback(
     Extension(state): Extension<AppState>,
     Json(body): Json<AppleCallbackBody>,
 ) -> ApiResult<Json<AuthResponse>> {
-    let _config = state.oauth.apple
+    let identity = apple_identity(&state, body).await?;
+
+    // Create or update user
+    let user = identities::sign_in(&state.db, &identity).await?;
+
+    // Generate tokens
+    let (access_token, refresh_token) = refresh::issue_tokens(&state.db, &user, &state.jwt_secret).await?;
+
+    Ok(Json(AuthResponse {
+        access_token,
+        refresh_token,
+        user: user.into(),
+    }))
+}
+
+/// Link an Apple ID to the signed-in user
+pub async fn link_apple(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Json(body): Json<AppleCallbackBody>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let identity = apple_identity(&state, body).await?;
+    identities::link(&state.db, user.user_id, &identity).await?;
+
+    Ok(Json(serde_json::json!({ "linked": true })))
+}
+
+/// Verify the id_token and extract the user's identity
+async fn apple_identity(state: &AppState, body: AppleCallbackBody) -> ApiResult<ProviderIdentity> {
+    let config = state.oauth.apple
         .as_ref()
         .ok_or_else(|| ApiError::OAuth("Apple Sign In not configured".to_string()))?;
 
-    // Decode ID token to get user info
     let id_token = body.id_token
         .ok_or_else(|| ApiError::OAuth("Missing id_token".to_string()))?;
+    let nonce = body.nonce
+        .ok_or_else(|| ApiError::OAuth("Missing nonce".to_string()))?;
 
-    // Decode without verification for now (in production, verify with Apple's public keys)
-    let token_parts: Vec<&str> = id_token.split('.').collect();
-    if token_parts.len() != 3 {
-        return Err(ApiError::OAuth("Invalid id_token format".to_string()));
+    // Nonces are single use, so a token carrying one can't be replayed
+    let issued: Option<(String,)> = sqlx::query_as(
+        "DELETE FROM apple_nonces WHERE nonce = $1 AND expires_at > NOW() RETURNING nonce",
+    )
+    .bind(&nonce)
+    .fetch_optional(&state.db)
+    .await?;
+    if issued.is_none() {
+        return Err(ApiError::OAuth("Invalid or expired nonce".to_string()));
     }
 
-    let claims_json = base64::Engine::decode(
-        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
-        token_parts[1]
-    ).map_err(|_| ApiError::OAuth("Failed to decode id_token".to_string()))?;
-
-    let claims: AppleIdTokenClaims = serde_json::from_slice(&claims_json)
-        .map_err(|_| ApiError::OAuth("Failed to parse id_token claims".to_string()))?;
+    // Verify signature and claims against Apple's published keys
+    let claims = config.keys
+        .verify_id_token(&id_token, &config.client_id, Some(&nonce))
+        .await?;
+    // Only the signed email counts as verified; it wins over the user payload
+    let email_verified = claims.email.is_some() && claims.is_email_verified();
 
     // Parse user info if provided (only on first sign in)
     let (name, email) = if let Some(user_json) = body.user {
@@ -248,65 +415,188 @@ pub async fn apple_callback(
             ).trim().to_string()
         });
 
-        (full_name, user_info.email.or(claims.email))
+        // Prefer the signed email over the unsigned user payload
+        (full_name, claims.email.or(user_info.email))
     } else {
         (None, claims.email)
     };
 
     let email = email.ok_or_else(|| ApiError::OAuth("Email not provided".to_string()))?;
 
-    // Create or update user
-    let user = upsert_user(
-        &state.db,
-        &email,
-        name.as_deref(),
-        None, // Apple doesn't provide avatar
-        AuthProvider::Apple,
-        &claims.sub,
-    ).await?;
+    Ok(ProviderIdentity {
+        provider: AuthProvider::Apple,
+        provider_id: claims.sub,
+        email,
+        email_verified,
+        name,
+        avatar_url: None, // Apple doesn't provide avatar
+    })
+}
 
-    // Generate tokens
-    let (access_token, refresh_token) = jwt::generate_tokens(&user, &state.jwt_secret)?;
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::Form,
+        response::IntoResponse,
+        routing::{get, post},
+        Router,
+    };
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support::{apple_id_token, app_state, mock_apple_keys, APPLE_CLIENT_ID};
+
+    /// Serve Google's token and userinfo endpoints on a local port
+    async fn mock_google() -> GoogleOAuthConfig {
+        async fn token(Form(form): Form<std::collections::HashMap<String, String>>) -> impl IntoResponse {
+            assert_eq!(form.get("code").map(String::as_str), Some("mock-code"));
+            assert!(form.contains_key("code_verifier"));
+            Json(serde_json::json!({ "access_token": "mock-access-token", "token_type": "bearer" }))
+        }
+
+        async fn userinfo() -> impl IntoResponse {
+            Json(serde_json::json!({
+                "id": "google-123",
+                "email": "mock@example.com",
+                "verified_email": true,
+                "name": "Mock User",
+            }))
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/token", post(token)).route("/userinfo", get(userinfo));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = BasicClient::new(
+            ClientId::new("client-id".to_string()),
+            Some(ClientSecret::new("client-secret".to_string())),
+            AuthUrl::new(format!("{base}/auth")).unwrap(),
+            Some(TokenUrl::new(format!("{base}/token")).unwrap()),
+        )
+        .set_redirect_uri(RedirectUrl::new("http://localhost:5173/auth/google/callback".to_string()).unwrap());
+
+        GoogleOAuthConfig { client, userinfo_url: format!("{base}/userinfo") }
+    }
 
-    Ok(Json(AuthResponse {
-        access_token,
-        refresh_token,
-        user: user.into(),
-    }))
-}
+    async fn google_state(db: &PgPool) -> AppState {
+        let mut state = app_state(db.clone());
+        state.oauth.google = Some(mock_google().await);
+        state
+    }
 
-// ============ Helper Functions ============
-
-async fn upsert_user(
-    db: &sqlx::PgPool,
-    email: &str,
-    name: Option<&str>,
-    avatar_url: Option<&str>,
-    provider: AuthProvider,
-    provider_id: &str,
-) -> ApiResult<User> {
-    let user = sqlx::query_as::<_, User>(
-        r#"
-        INSERT INTO users (id, email, name, avatar_url, provider, provider_id, cloud_sync_enabled, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, $6, false, NOW(), NOW())
-        ON CONFLICT (provider, provider_id) DO UPDATE SET
-            email = EXCLUDED.email,
-            name = COALESCE(EXCLUDED.name, users.name),
-            avatar_url = COALESCE(EXCLUDED.avatar_url, users.avatar_url),
-            updated_at = NOW()
-        RETURNING id, email, name, avatar_url, 
-                  provider, provider_id,
-                  cloud_sync_enabled, created_at, updated_at
-        "#,
-    )
-    .bind(Uuid::new_v4())
-    .bind(email)
-    .bind(name)
-    .bind(avatar_url)
-    .bind(&provider)
-    .bind(provider_id)
-    .fetch_one(db)
-    .await?;
+    /// Start a sign-in, returning the state from the redirect and the browser's cookies
+    async fn start(state: &AppState) -> (String, CookieJar) {
+        let (jar, redirect) = google_auth(Extension(state.clone()), CookieJar::new()).await.unwrap();
+        let location = redirect.into_response().headers()["location"].to_str().unwrap().to_string();
+        let csrf_state = reqwest::Url::parse(&location)
+            .unwrap()
+            .query_pairs()
+            .find(|(key, _)| key == "state")
+            .map(|(_, value)| value.into_owned())
+            .unwrap();
+
+        (csrf_state, jar)
+    }
+
+    async fn callback(state: &AppState, jar: CookieJar, csrf_state: &str) -> ApiResult<AuthResponse> {
+        let query = OAuthCallbackQuery { code: "mock-code".to_string(), state: Some(csrf_state.to_string()) };
+        google_callback(Extension(state.clone()), jar, Query(query))
+            .await
+            .map(|(_, Json(response))| response)
+    }
+
+    #[sqlx::test]
+    async fn sign_in_completes_in_the_browser_that_started_it(db: PgPool) {
+        let state = google_state(&db).await;
+        let (csrf_state, jar) = start(&state).await;
+
+        let cookie = jar.get(OAUTH_STATE_COOKIE).unwrap();
+        assert!(cookie.http_only().unwrap());
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_ne!(cookie.value(), csrf_state);
+
+        let response = callback(&state, jar, &csrf_state).await.unwrap();
+        assert_eq!(response.user.email, "mock@example.com");
+
+        let (provider_id,): (String,) = sqlx::query_as("SELECT provider_id FROM user_identities WHERE email = $1")
+            .bind("mock@example.com")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(provider_id, "google-123");
+    }
+
+    #[sqlx::test]
+    async fn callbacks_from_another_browser_are_rejected(db: PgPool) {
+        let state = google_state(&db).await;
+        let (csrf_state, jar) = start(&state).await;
+        let (_, other_jar) = start(&state).await;
+
+        assert!(matches!(callback(&state, CookieJar::new(), &csrf_state).await, Err(ApiError::OAuth(_))));
+        assert!(matches!(callback(&state, other_jar, &csrf_state).await, Err(ApiError::OAuth(_))));
+
+        // Rejected attempts leave the state for the browser it belongs to
+        assert!(callback(&state, jar, &csrf_state).await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn states_cannot_be_replayed(db: PgPool) {
+        let state = google_state(&db).await;
+        let (csrf_state, jar) = start(&state).await;
+
+        assert!(callback(&state, jar.clone(), &csrf_state).await.is_ok());
+        assert!(matches!(callback(&state, jar, &csrf_state).await, Err(ApiError::OAuth(_))));
+    }
+
+    async fn apple_state(db: &PgPool) -> (AppState, String) {
+        let (base_url, _) = mock_apple_keys().await;
+        let mut state = app_state(db.clone());
+        state.oauth.apple = Some(AppleOAuthConfig {
+            client_id: APPLE_CLIENT_ID.to_string(),
+            team_id: "team".to_string(),
+            key_id: "key".to_string(),
+            private_key: String::new(),
+            redirect_uri: "http://localhost:3000/auth/apple/callback".to_string(),
+            keys: AppleKeyStore::new(base_url.clone()),
+        });
+        (state, base_url)
+    }
+
+    async fn apple_sign_in(state: &AppState, id_token: &str, nonce: &str) -> ApiResult<AuthResponse> {
+        let body = AppleCallbackBody {
+            code: "apple-code".to_string(),
+            id_token: Some(id_token.to_string()),
+            user: None,
+            nonce: Some(nonce.to_string()),
+        };
+        apple_callback(Extension(state.clone()), Json(body))
+            .await
+            .map(|Json(response)| response)
+    }
 
-    Ok(user)
+    #[sqlx::test]
+    async fn apple_sign_in_consumes_the_issued_nonce(db: PgPool) {
+        let (state, issuer) = apple_state(&db).await;
+        let Json(config) = apple_auth(Extension(state.clone())).await.unwrap();
+
+        let token = apple_id_token(&issuer, "test-key", serde_json::json!({ "nonce": config.nonce }));
+        let response = apple_sign_in(&state, &token, &config.nonce).await.unwrap();
+        assert_eq!(response.user.email, "apple@example.com");
+
+        // A captured token can't be replayed
+        assert!(matches!(apple_sign_in(&state, &token, &config.nonce).await, Err(ApiError::OAuth(_))));
+    }
+
+    #[sqlx::test]
+    async fn apple_nonces_must_be_issued_by_the_server(db: PgPool) {
+        let (state, issuer) = apple_state(&db).await;
+
+        let token = apple_id_token(&issuer, "test-key", serde_json::json!({ "nonce": "client-made" }));
+        assert!(matches!(apple_sign_in(&state, &token, "client-made").await, Err(ApiError::OAuth(_))));
+
+        // Nor can an issued nonce vouch for a token carrying another
+        let Json(config) = apple_auth(Extension(state.clone())).await.unwrap();
+        assert!(matches!(apple_sign_in(&state, &token, &config.nonce).await, Err(ApiError::OAuth(_))));
+    }
 }
@@ -0,0 +1,183 @@
+//! Profile updates and avatar uploads
+
+use axum::{body::Bytes, Extension, Json};
+use uuid::Uuid;
+
+use crate::{AppState, db, error::{ApiError, ApiResult}, models::*, storage};
+use super::middleware::AuthUser;
+
+/// Largest accepted avatar upload
+const MAX_AVATAR_BYTES: usize = 2 * 1024 * 1024;
+
+/// Update the current user's profile
+pub async fn update_me(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Json(body): Json<UpdateUserRequest>,
+) -> ApiResult<Json<UserProfile>> {
+    let prefs = &body.preferences;
+
+    if let Some(timezone) = &prefs.timezone {
+        if !db::is_valid_timezone(&state.db, timezone).await? {
+            return Err(ApiError::BadRequest(format!("Unknown time zone: {}", timezone)));
+        }
+    }
+
+    if let Some(week_start) = prefs.week_start {
+        if !(1..=7).contains(&week_start) {
+            return Err(ApiError::BadRequest("week_start must be between 1 (Monday) and 7 (Sunday)".to_string()));
+        }
+    }
+
+    if let Some(locale) = &prefs.locale {
+        if !is_valid_locale(locale) {
+            return Err(ApiError::BadRequest(format!("Invalid locale: {}", locale)));
+        }
+    }
+
+    if let Some(name) = &body.name {
+        if name.trim().is_empty() || name.len() > 255 {
+            return Err(ApiError::BadRequest("Name must be between 1 and 255 characters".to_string()));
+        }
+    }
+
+    let updated = sqlx::query_as::<_, User>(
+        r#"UPDATE users SET
+           name = COALESCE($2, name),
+           cloud_sync_enabled = COALESCE($3, cloud_sync_enabled),
+           timezone = COALESCE($4, timezone),
+           week_start = COALESCE($5, week_start),
+           locale = COALESCE($6, locale),
+           notify_reminders = COALESCE($7, notify_reminders),
+           notify_shared_activity = COALESCE($8, notify_shared_activity),
+           notify_email = COALESCE($9, notify_email),
+           updated_at = NOW()
+           WHERE id = $1
+           RETURNING id, email, name, avatar_url,
+                     provider, provider_id,
+                     cloud_sync_enabled, timezone, week_start, locale,
+                     notify_reminders, notify_shared_activity, notify_email,
+                     created_at, updated_at"#,
+    )
+    .bind(user.user_id)
+    .bind(body.name.as_deref().map(str::trim))
+    .bind(body.cloud_sync_enabled)
+    .bind(&prefs.timezone)
+    .bind(prefs.week_start)
+    .bind(&prefs.locale)
+    .bind(prefs.notify_reminders)
+    .bind(prefs.notify_shared_activity)
+    .bind(prefs.notify_email)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    // Pending reminder times were resolved in the old time zone
+    if prefs.timezone.is_some() {
+        sqlx::query(
+            r#"UPDATE habit_reminders SET next_fire_at = NULL
+               WHERE habit_id IN (SELECT id FROM habits WHERE user_id = $1)"#,
+        )
+        .bind(user.user_id)
+        .execute(&state.db)
+        .await?;
+    }
+
+    Ok(Json(updated.into()))
+}
+
+/// Upload a new avatar. The request body is the raw PNG, JPEG, GIF or WebP
+/// image.
+pub async fn upload_avatar(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    body: Bytes,
+) -> ApiResult<Json<UserProfile>> {
+    if body.len() > MAX_AVATAR_BYTES {
+        return Err(ApiError::BadRequest(format!(
+            "Avatar must be at most {} MB",
+            MAX_AVATAR_BYTES / (1024 * 1024)
+        )));
+    }
+
+    let (extension, _) = storage::detect_image(&body)
+        .ok_or_else(|| ApiError::BadRequest("Avatar must be a PNG, JPEG, GIF or WebP image".to_string()))?;
+
+    let key = format!("avatars/{}/{}.{}", user.user_id, Uuid::new_v4(), extension);
+    state.blobs.put(&key, body).await?;
+
+    let previous = avatar_key(&state, user.user_id).await?;
+    let updated = set_avatar(&state, user.user_id, Some(&key)).await?;
+
+    if let Some(previous) = previous {
+        remove_blob(&state, &previous).await;
+    }
+
+    Ok(Json(updated.into()))
+}
+
+/// Remove an uploaded avatar
+pub async fn delete_avatar(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<UserProfile>> {
+    let previous = avatar_key(&state, user.user_id).await?;
+    let updated = set_avatar(&state, user.user_id, None).await?;
+
+    if let Some(previous) = previous {
+        remove_blob(&state, &previous).await;
+    }
+
+    Ok(Json(updated.into()))
+}
+
+// ============ Helper Functions ============
+
+/// Storage key of the user's uploaded avatar, if any
+pub(super) async fn avatar_key(state: &AppState, user_id: Uuid) -> ApiResult<Option<String>> {
+    let key: Option<(Option<String>,)> = sqlx::query_as("SELECT avatar_key FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?;
+
+    Ok(key.and_then(|(key,)| key))
+}
+
+async fn set_avatar(state: &AppState, user_id: Uuid, key: Option<&str>) -> ApiResult<User> {
+    let user = sqlx::query_as::<_, User>(
+        r#"UPDATE users SET avatar_key = $2, avatar_url = $3, updated_at = NOW()
+           WHERE id = $1
+           RETURNING id, email, name, avatar_url,
+                     provider, provider_id,
+                     cloud_sync_enabled, timezone, week_start, locale,
+                     notify_reminders, notify_shared_activity, notify_email,
+                     created_at, updated_at"#,
+    )
+    .bind(user_id)
+    .bind(key)
+    .bind(key.map(|k| state.blobs.url(k)))
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    Ok(user)
+}
+
+/// Best-effort removal of a blob that's no longer referenced
+pub(super) async fn remove_blob(state: &AppState, key: &str) {
+    if let Err(e) = state.blobs.delete(key).await {
+        tracing::warn!("Failed to delete blob {}: {:?}", key, e);
+    }
+}
+
+/// Loose BCP 47 check: a 2-3 letter language followed by alphanumeric subtags
+fn is_valid_locale(locale: &str) -> bool {
+    let mut subtags = locale.split('-');
+    let language_ok = subtags
+        .next()
+        .is_some_and(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()));
+
+    language_ok
+        && locale.len() <= 35
+        && subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
@@ -0,0 +1,190 @@
+//! Refresh token rotation and revocation
+//!
+//! Refresh tokens are opaque `<id>.<secret>` strings. Only an argon2 hash of
+//! the secret is stored. Every refresh rotates the token within its family;
+//! presenting a token that was already rotated is treated as theft and
+//! revokes the whole family.
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{Extension, Json};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use crate::{AppState, error::{ApiError, ApiResult}, models::*};
+use super::jwt;
+
+const REFRESH_TOKEN_EXPIRY_DAYS: i64 = 30;
+
+#[derive(Debug, FromRow)]
+struct RefreshTokenRow {
+    id: Uuid,
+    user_id: Uuid,
+    family_id: Uuid,
+    token_hash: String,
+    expires_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Issue an access token and a refresh token starting a new family
+pub async fn issue_tokens(db: &sqlx::PgPool, user: &User, secret: &str) -> ApiResult<(String, String)> {
+    let access_token = jwt::generate_access_token(user, secret)?;
+
+    let mut conn = db.acquire().await?;
+    let refresh_token = store_refresh_token(&mut conn, user.id, Uuid::new_v4()).await?;
+
+    Ok((access_token, refresh_token))
+}
+
+/// Refresh access token endpoint
+pub async fn refresh_token(
+    Extension(state): Extension<AppState>,
+    Json(body): Json<RefreshTokenRequest>,
+) -> ApiResult<Json<AuthResponse>> {
+    let (id, token_secret) = parse_token(&body.refresh_token)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let record = sqlx::query_as::<_, RefreshTokenRow>(
+        r#"SELECT id, user_id, family_id, token_hash, expires_at, revoked_at
+           FROM refresh_tokens WHERE id = $1 FOR UPDATE"#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApiError::Unauthorized)?;
+
+    verify_secret(token_secret, &record.token_hash)?;
+
+    if record.revoked_at.is_some() {
+        // A rotated token was presented again: assume it leaked
+        tracing::warn!("Refresh token reuse detected for user {}", record.user_id);
+        revoke_family(&mut tx, record.family_id).await?;
+        tx.commit().await?;
+        return Err(ApiError::Unauthorized);
+    }
+
+    if record.expires_at < Utc::now() {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let user = sqlx::query_as::<_, User>(
+        r#"SELECT id, email, name, avatar_url,
+           provider, provider_id,
+           cloud_sync_enabled, timezone, week_start, locale,
+           notify_reminders, notify_shared_activity, notify_email,
+           created_at, updated_at
+           FROM users WHERE id = $1"#,
+    )
+    .bind(record.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApiError::Unauthorized)?;
+
+    // Rotate within the same family
+    let refresh_token = store_refresh_token(&mut tx, user.id, record.family_id).await?;
+    let (new_id, _) = parse_token(&refresh_token)?;
+
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW(), replaced_by = $2 WHERE id = $1")
+        .bind(record.id)
+        .bind(new_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let access_token = jwt::generate_access_token(&user, &state.jwt_secret)?;
+
+    Ok(Json(AuthResponse {
+        access_token,
+        refresh_token,
+        user: user.into(),
+    }))
+}
+
+/// Logout endpoint - revokes the refresh token and every token rotated from
+/// the same sign-in
+pub async fn logout(
+    Extension(state): Extension<AppState>,
+    Json(body): Json<RefreshTokenRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let (id, token_secret) = parse_token(&body.refresh_token)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let record = sqlx::query_as::<_, RefreshTokenRow>(
+        r#"SELECT id, user_id, family_id, token_hash, expires_at, revoked_at
+           FROM refresh_tokens WHERE id = $1"#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApiError::Unauthorized)?;
+
+    verify_secret(token_secret, &record.token_hash)?;
+
+    revoke_family(&mut tx, record.family_id).await?;
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+// ============ Helper Functions ============
+
+async fn store_refresh_token(conn: &mut PgConnection, user_id: Uuid, family_id: Uuid) -> ApiResult<String> {
+    let id = Uuid::new_v4();
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token_secret = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+
+    let salt = SaltString::generate(&mut OsRng);
+    let token_hash = Argon2::default()
+        .hash_password(token_secret.as_bytes(), &salt)
+        .map_err(|e| ApiError::Internal(anyhow::anyhow!("Failed to hash refresh token: {}", e)))?
+        .to_string();
+
+    sqlx::query(
+        r#"INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, expires_at, created_at)
+           VALUES ($1, $2, $3, $4, $5, NOW())"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(family_id)
+    .bind(&token_hash)
+    .bind(Utc::now() + Duration::days(REFRESH_TOKEN_EXPIRY_DAYS))
+    .execute(conn)
+    .await?;
+
+    Ok(format!("{}.{}", id, token_secret))
+}
+
+async fn revoke_family(conn: &mut PgConnection, family_id: Uuid) -> ApiResult<()> {
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL")
+        .bind(family_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+fn parse_token(token: &str) -> ApiResult<(Uuid, &str)> {
+    let (id, token_secret) = token.split_once('.').ok_or(ApiError::Unauthorized)?;
+    let id = Uuid::parse_str(id).map_err(|_| ApiError::Unauthorized)?;
+
+    Ok((id, token_secret))
+}
+
+fn verify_secret(token_secret: &str, token_hash: &str) -> ApiResult<()> {
+    let parsed = PasswordHash::new(token_hash)
+        .map_err(|e| ApiError::Internal(anyhow::anyhow!("Invalid stored token hash: {}", e)))?;
+
+    Argon2::default()
+        .verify_password(token_secret.as_bytes(), &parsed)
+        .map_err(|_| ApiError::Unauthorized)
+}
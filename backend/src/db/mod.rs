@@ -2,6 +2,9 @@
 //! 
 //! This module re-exports database types and provides helper functions.
 
+use chrono::NaiveDate;
+use uuid::Uuid;
+
 // Re-export commonly used types
 pub use sqlx::PgPool;
 
@@ -13,3 +16,26 @@ pub async fn health_check(pool: &PgPool) -> bool {
         .is_ok()
 }
 
+/// Today's date in the user's time zone
+pub async fn local_today(pool: &PgPool, user_id: Uuid) -> Result<NaiveDate, sqlx::Error> {
+    let (today,): (NaiveDate,) = sqlx::query_as(
+        "SELECT (NOW() AT TIME ZONE timezone)::date FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(today)
+}
+
+/// Whether `name` is an IANA time zone known to the database
+pub async fn is_valid_timezone(pool: &PgPool, name: &str) -> Result<bool, sqlx::Error> {
+    let (valid,): (bool,) = sqlx::query_as(
+        "SELECT EXISTS(SELECT 1 FROM pg_timezone_names WHERE name = $1)",
+    )
+    .bind(name)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(valid)
+}
@@ -0,0 +1,255 @@
+//! Email outbox job
+//!
+//! Sends queued emails through the configured mailer. A failed send is
+//! retried with exponential backoff until it runs out of attempts, after
+//! which it's marked `failed` and left for inspection.
+
+use std::{sync::Arc, time::Duration};
+
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiResult,
+    mail::{EmailMessage, Mailer},
+};
+
+/// Emails claimed per tick
+const BATCH_SIZE: i64 = 50;
+
+/// Email outbox job configuration
+#[derive(Debug, Clone)]
+pub struct EmailOutboxConfig {
+    /// How often to look for queued emails
+    pub interval: Duration,
+    /// Sends attempted before an email is given up on
+    pub max_attempts: i32,
+}
+
+impl EmailOutboxConfig {
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("MAIL_OUTBOX_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let max_attempts = std::env::var("MAIL_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+
+        Self {
+            interval: Duration::from_secs(interval_secs),
+            max_attempts,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct QueuedEmail {
+    id: Uuid,
+    recipient: String,
+    subject: String,
+    text_body: String,
+    html_body: String,
+    attempts: i32,
+}
+
+/// Run the job forever; errors are logged and retried on the next tick
+pub async fn run(db: PgPool, config: EmailOutboxConfig, mailer: Arc<dyn Mailer>) {
+    let mut ticker = tokio::time::interval(config.interval);
+
+    loop {
+        ticker.tick().await;
+
+        match deliver_queued_emails(&db, mailer.as_ref(), &config).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Sent {} emails", count),
+            Err(e) => tracing::error!("Email outbox job failed: {:?}", e),
+        }
+    }
+}
+
+/// Send a batch of due emails, returning how many went out
+pub async fn deliver_queued_emails(db: &PgPool, mailer: &dyn Mailer, config: &EmailOutboxConfig) -> ApiResult<usize> {
+    // Lease the batch so other instances skip it; if this one dies mid-send
+    // the emails become due again once the lease runs out
+    let emails = sqlx::query_as::<_, QueuedEmail>(
+        r#"UPDATE email_outbox SET next_attempt_at = NOW() + INTERVAL '10 minutes'
+           WHERE id IN (
+               SELECT id FROM email_outbox
+               WHERE status = 'pending' AND next_attempt_at <= NOW()
+               ORDER BY next_attempt_at
+               LIMIT $1
+               FOR UPDATE SKIP LOCKED
+           )
+           RETURNING id, recipient, subject, text_body, html_body, attempts"#,
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(db)
+    .await?;
+
+    let mut sent = 0;
+    for email in emails {
+        let message = EmailMessage {
+            to: email.recipient,
+            subject: email.subject,
+            text_body: email.text_body,
+            html_body: email.html_body,
+        };
+        let attempts = email.attempts + 1;
+
+        match mailer.send(&message).await {
+            Ok(()) => {
+                sqlx::query(
+                    r#"UPDATE email_outbox SET status = 'sent', attempts = $2, sent_at = NOW(), last_error = NULL
+                       WHERE id = $1"#,
+                )
+                .bind(email.id)
+                .bind(attempts)
+                .execute(db)
+                .await?;
+
+                sent += 1;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to send email {} (attempt {}): {:?}", email.id, attempts, e);
+
+                // 1, 2, 4, 8... minutes between attempts, capped at a day
+                let backoff_secs = (60i64 << (attempts - 1).clamp(0, 10)).min(86_400);
+                sqlx::query(
+                    r#"UPDATE email_outbox SET
+                       status = CASE WHEN $2 >= $3 THEN 'failed'::email_status ELSE status END,
+                       attempts = $2,
+                       last_error = $4,
+                       next_attempt_at = NOW() + make_interval(secs => $5)
+                       WHERE id = $1"#,
+                )
+                .bind(email.id)
+                .bind(attempts)
+                .bind(config.max_attempts)
+                .bind(e.to_string())
+                .bind(backoff_secs as f64)
+                .execute(db)
+                .await?;
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::mail::{self, MemoryMailer, Template};
+
+    const CONFIG: EmailOutboxConfig = EmailOutboxConfig { interval: Duration::from_secs(30), max_attempts: 3 };
+
+    async fn queue(db: &PgPool) -> Uuid {
+        let mut conn = db.acquire().await.unwrap();
+        mail::enqueue(&mut conn, None, "leaving@example.com", &Template::AccountDeleted).await.unwrap()
+    }
+
+    #[derive(Debug, FromRow)]
+    struct Outbox {
+        status: String,
+        attempts: i32,
+        last_error: Option<String>,
+        /// Seconds until the next attempt
+        retry_in: f64,
+        sent_at: Option<DateTime<Utc>>,
+    }
+
+    async fn outbox(db: &PgPool, id: Uuid) -> Outbox {
+        sqlx::query_as(
+            r#"SELECT status::text AS status, attempts, last_error,
+                      EXTRACT(EPOCH FROM next_attempt_at - NOW())::float8 AS retry_in, sent_at
+               FROM email_outbox WHERE id = $1"#,
+        )
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    /// Skip the backoff so the email is due again
+    async fn make_due(db: &PgPool, id: Uuid) {
+        sqlx::query("UPDATE email_outbox SET next_attempt_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn queued_emails_are_sent_once(db: PgPool) {
+        let id = queue(&db).await;
+        let mailer = MemoryMailer::new();
+
+        assert_eq!(deliver_queued_emails(&db, &mailer, &CONFIG).await.unwrap(), 1);
+        assert_eq!(deliver_queued_emails(&db, &mailer, &CONFIG).await.unwrap(), 0);
+
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "leaving@example.com");
+
+        let email = outbox(&db, id).await;
+        assert_eq!(email.status, "sent");
+        assert_eq!(email.attempts, 1);
+        assert!(email.sent_at.is_some());
+    }
+
+    #[sqlx::test]
+    async fn failed_sends_back_off_and_then_succeed(db: PgPool) {
+        let id = queue(&db).await;
+
+        assert_eq!(deliver_queued_emails(&db, &MemoryMailer::failing(), &CONFIG).await.unwrap(), 0);
+        let email = outbox(&db, id).await;
+        assert_eq!(email.status, "pending");
+        assert_eq!(email.attempts, 1);
+        assert_eq!(email.last_error.as_deref(), Some("mail server unavailable"));
+        assert!((55.0..=60.0).contains(&email.retry_in));
+
+        // Not due again until the backoff has passed
+        let mailer = MemoryMailer::new();
+        assert_eq!(deliver_queued_emails(&db, &mailer, &CONFIG).await.unwrap(), 0);
+
+        make_due(&db, id).await;
+        assert_eq!(deliver_queued_emails(&db, &MemoryMailer::failing(), &CONFIG).await.unwrap(), 0);
+        let email = outbox(&db, id).await;
+        assert_eq!(email.attempts, 2);
+        assert!((115.0..=120.0).contains(&email.retry_in));
+
+        make_due(&db, id).await;
+        assert_eq!(deliver_queued_emails(&db, &mailer, &CONFIG).await.unwrap(), 1);
+        let email = outbox(&db, id).await;
+        assert_eq!(email.status, "sent");
+        assert_eq!(email.attempts, 3);
+        assert_eq!(email.last_error, None);
+        assert_eq!(mailer.sent().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn emails_fail_after_max_attempts(db: PgPool) {
+        let id = queue(&db).await;
+        let failing = MemoryMailer::failing();
+
+        for _ in 0..CONFIG.max_attempts {
+            make_due(&db, id).await;
+            deliver_queued_emails(&db, &failing, &CONFIG).await.unwrap();
+        }
+
+        let email = outbox(&db, id).await;
+        assert_eq!(email.status, "failed");
+        assert_eq!(email.attempts, 3);
+
+        // Failed emails are left alone
+        make_due(&db, id).await;
+        let mailer = MemoryMailer::new();
+        assert_eq!(deliver_queued_emails(&db, &mailer, &CONFIG).await.unwrap(), 0);
+        assert!(mailer.sent().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+//! Goal deadline job
+//!
+//! Periodically moves active goals whose deadline has passed in the owner's
+//! time zone to `Achieved` or `Failed`, depending on their final progress.
+
+use std::time::Duration;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    analytics::progress,
+    error::ApiResult,
+    models::{Goal, GoalStatus},
+};
+
+/// Goal deadline job configuration
+#[derive(Debug, Clone)]
+pub struct GoalDeadlineConfig {
+    /// How often to scan for expired goals
+    pub interval: Duration,
+    /// Final progress (in percent) at or above which a goal is achieved
+    pub achieved_percentage: f64,
+}
+
+impl GoalDeadlineConfig {
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("GOAL_DEADLINE_CHECK_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+
+        let achieved_percentage = std::env::var("GOAL_ACHIEVED_PERCENTAGE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(progress::DEFAULT_ON_TRACK_PERCENTAGE);
+
+        Self {
+            interval: Duration::from_secs(interval_secs),
+            achieved_percentage,
+        }
+    }
+}
+
+/// Run the job forever; errors are logged and retried on the next tick
+pub async fn run(db: PgPool, config: GoalDeadlineConfig) {
+    let mut ticker = tokio::time::interval(config.interval);
+
+    loop {
+        ticker.tick().await;
+
+        match process_expired_goals(&db, &config).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Closed {} goals past their deadline", count),
+            Err(e) => tracing::error!("Goal deadline job failed: {:?}", e),
+        }
+    }
+}
+
+/// Close every active goal whose deadline has passed, returning how many
+/// were updated
+pub async fn process_expired_goals(db: &PgPool, config: &GoalDeadlineConfig) -> ApiResult<usize> {
+    let goals = sqlx::query_as::<_, Goal>(
+        r#"SELECT g.id, g.user_id, g.name, g.description, g.deadline,
+           g.status, g.is_shared, g.created_at, g.updated_at
+           FROM goals g
+           JOIN users u ON u.id = g.user_id
+           WHERE g.status = 'active' AND g.deadline < (NOW() AT TIME ZONE u.timezone)::date"#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut closed = 0;
+    for goal in goals {
+        // One goal failing shouldn't hold up the rest; it's retried next tick
+        match close_goal(db, &goal, config).await {
+            Ok(true) => closed += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Failed to close goal {}: {:?}", goal.id, e),
+        }
+    }
+
+    Ok(closed)
+}
+
+/// Mark one expired goal achieved or failed by its final progress. Returns
+/// false when the goal was changed by its owner since the scan.
+async fn close_goal(db: &PgPool, goal: &Goal, config: &GoalDeadlineConfig) -> ApiResult<bool> {
+    let progress = progress::load_goal_progress(db, goal, config.achieved_percentage).await?;
+    let status = if progress.percentage >= config.achieved_percentage {
+        GoalStatus::Achieved
+    } else {
+        GoalStatus::Failed
+    };
+
+    let mut tx = db.begin().await?;
+
+    let result = sqlx::query(
+        "UPDATE goals SET status = $2, updated_at = NOW() WHERE id = $1 AND status = 'active'",
+    )
+    .bind(goal.id)
+    .bind(&status)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if goal.is_shared {
+        let outcome = if status == GoalStatus::Achieved { "achieved" } else { "missed" };
+        sqlx::query(
+            r#"INSERT INTO shared_activities (id, shared_goal_id, user_id, activity_type, message, created_at)
+               SELECT $1, sg.id, $2, 'goal_progress', $3, NOW()
+               FROM shared_goals sg WHERE sg.goal_id = $4"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(goal.user_id)
+        .bind(format!(
+            "Goal {} with {:.0}% completion",
+            outcome, progress.percentage
+        ))
+        .bind(goal.id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::test_support::{check_in, create_goal, create_habit, create_user};
+
+    const CONFIG: GoalDeadlineConfig = GoalDeadlineConfig {
+        interval: Duration::from_secs(3600),
+        achieved_percentage: 80.0,
+    };
+
+    /// A goal that ran the three days up to yesterday, linked to `habit_id`
+    async fn expired_goal(db: &PgPool, user_id: Uuid, name: &str, habit_id: Uuid) -> Uuid {
+        let goal_id = create_goal(db, user_id, name, &[habit_id]).await;
+        sqlx::query("UPDATE goals SET created_at = NOW() - INTERVAL '3 days', deadline = CURRENT_DATE - 1 WHERE id = $1")
+            .bind(goal_id)
+            .execute(db)
+            .await
+            .unwrap();
+        goal_id
+    }
+
+    async fn status(db: &PgPool, goal_id: Uuid) -> GoalStatus {
+        let (status,): (GoalStatus,) = sqlx::query_as("SELECT status FROM goals WHERE id = $1")
+            .bind(goal_id)
+            .fetch_one(db)
+            .await
+            .unwrap();
+        status
+    }
+
+    #[sqlx::test]
+    async fn expired_goals_close_by_their_final_progress(db: PgPool) {
+        let user = create_user(&db, "deadlines@example.com").await;
+        let kept = create_habit(&db, user.user_id, "Read", 10).await;
+        let skipped = create_habit(&db, user.user_id, "Run", 10).await;
+        for days_ago in 1..=3 {
+            check_in(&db, user.user_id, kept, days_ago).await;
+        }
+
+        let achieved = expired_goal(&db, user.user_id, "Read daily", kept).await;
+        let failed = expired_goal(&db, user.user_id, "Run daily", skipped).await;
+        let running = create_goal(&db, user.user_id, "Still going", &[skipped]).await;
+
+        assert_eq!(process_expired_goals(&db, &CONFIG).await.unwrap(), 2);
+        assert_eq!(status(&db, achieved).await, GoalStatus::Achieved);
+        assert_eq!(status(&db, failed).await, GoalStatus::Failed);
+        assert_eq!(status(&db, running).await, GoalStatus::Active);
+
+        // Closed goals aren't picked up again
+        assert_eq!(process_expired_goals(&db, &CONFIG).await.unwrap(), 0);
+    }
+
+    #[sqlx::test]
+    async fn goals_changed_since_the_scan_are_left_alone(db: PgPool) {
+        let user = create_user(&db, "deadlines@example.com").await;
+        let habit_id = create_habit(&db, user.user_id, "Read", 10).await;
+        let goal_id = expired_goal(&db, user.user_id, "Read daily", habit_id).await;
+
+        let goal = sqlx::query_as::<_, Goal>(
+            r#"SELECT id, user_id, name, description, deadline, status, is_shared, created_at, updated_at
+               FROM goals WHERE id = $1"#,
+        )
+        .bind(goal_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        // The owner gives up on it after the scan
+        sqlx::query("UPDATE goals SET status = 'abandoned' WHERE id = $1")
+            .bind(goal_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        assert!(!close_goal(&db, &goal, &CONFIG).await.unwrap());
+        assert_eq!(status(&db, goal_id).await, GoalStatus::Abandoned);
+    }
+}
@@ -0,0 +1,56 @@
+//! Invite expiry job
+//!
+//! Marks pending goal invites as `Expired` once their `expires_at` has passed.
+
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::error::ApiResult;
+
+/// Invite expiry job configuration
+#[derive(Debug, Clone)]
+pub struct InviteExpiryConfig {
+    /// How often to sweep for stale invites
+    pub interval: Duration,
+}
+
+impl InviteExpiryConfig {
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("INVITE_EXPIRY_CHECK_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+
+        Self {
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+}
+
+/// Run the job forever; errors are logged and retried on the next tick
+pub async fn run(db: PgPool, config: InviteExpiryConfig) {
+    let mut ticker = tokio::time::interval(config.interval);
+
+    loop {
+        ticker.tick().await;
+
+        match expire_stale_invites(&db).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Expired {} goal invites", count),
+            Err(e) => tracing::error!("Invite expiry job failed: {:?}", e),
+        }
+    }
+}
+
+/// Expire every pending invite past its deadline, returning how many were
+/// updated
+pub async fn expire_stale_invites(db: &PgPool) -> ApiResult<u64> {
+    let result = sqlx::query(
+        "UPDATE goal_invites SET status = 'expired' WHERE status = 'pending' AND expires_at <= NOW()",
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
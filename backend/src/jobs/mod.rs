@@ -0,0 +1,7 @@
+//! Background jobs started alongside the HTTP server
+
+pub mod email_outbox;
+pub mod goal_deadlines;
+pub mod invite_expiry;
+pub mod reminders;
+pub mod weekly_digest;
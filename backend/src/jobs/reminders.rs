@@ -0,0 +1,391 @@
+//! Reminder delivery job
+//!
+//! Works out when each enabled reminder should next fire and, once it's due,
+//! hands a notification to the configured sink. Habits that already have a
+//! check-in for the day, or whose owner turned reminders off, are skipped.
+//! Reminder times are wall-clock times in the habit owner's time zone.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use rand::Rng;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiResult,
+    models::ReminderType,
+    notifications::{Notification, NotificationSink},
+};
+
+/// Reminder job configuration
+#[derive(Debug, Clone)]
+pub struct ReminderConfig {
+    /// How often to look for due reminders
+    pub interval: Duration,
+}
+
+impl ReminderConfig {
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("REMINDER_CHECK_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+
+        Self {
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct ScheduledReminder {
+    id: Uuid,
+    habit_id: Uuid,
+    user_id: Uuid,
+    habit_name: String,
+    reminder_type: ReminderType,
+    interval_hours: Option<i32>,
+    daily_time: Option<NaiveTime>,
+    random_window_start: Option<NaiveTime>,
+    random_window_end: Option<NaiveTime>,
+    next_fire_at: Option<DateTime<Utc>>,
+    timezone: String,
+    /// The scan time in the owner's time zone
+    local_now: NaiveDateTime,
+}
+
+/// When a reminder fires next
+#[derive(Debug, Clone, Copy)]
+enum FireTime {
+    At(DateTime<Utc>),
+    /// Wall-clock time in the owner's time zone
+    Local(NaiveDateTime),
+}
+
+impl ScheduledReminder {
+    /// First fire time strictly after `now`, or `None` if the reminder's
+    /// configuration is incomplete
+    fn next_fire_time(&self, now: DateTime<Utc>) -> Option<FireTime> {
+        let after = self.local_now;
+
+        match self.reminder_type {
+            ReminderType::Interval => {
+                let hours = self.interval_hours.filter(|h| *h > 0)?;
+                Some(FireTime::At(now + TimeDelta::hours(hours.into())))
+            }
+            ReminderType::Daily => {
+                let time = self.daily_time?;
+                let today = after.date().and_time(time);
+                if today > after {
+                    Some(FireTime::Local(today))
+                } else {
+                    Some(FireTime::Local(today + TimeDelta::days(1)))
+                }
+            }
+            ReminderType::Random => {
+                let start = self.random_window_start?;
+                let end = self.random_window_end?;
+
+                // Once a day: today's window if it hasn't opened yet, else tomorrow's
+                let mut day = after.date();
+                if window(day, start, end).0 <= after {
+                    day = day.checked_add_days(Days::new(1))?;
+                }
+
+                let (open, close) = window(day, start, end);
+                let seconds = (close - open).num_seconds().max(0);
+                Some(FireTime::Local(open + TimeDelta::seconds(rand::thread_rng().gen_range(0..=seconds))))
+            }
+        }
+    }
+}
+
+/// The random window on `day`
+fn window(day: NaiveDate, start: NaiveTime, end: NaiveTime) -> (NaiveDateTime, NaiveDateTime) {
+    (day.and_time(start), day.and_time(end))
+}
+
+/// Run the job forever; errors are logged and retried on the next tick
+pub async fn run(db: PgPool, config: ReminderConfig, sink: Arc<dyn NotificationSink>) {
+    let mut ticker = tokio::time::interval(config.interval);
+
+    loop {
+        ticker.tick().await;
+
+        match process_due_reminders(&db, sink.as_ref(), Utc::now()).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Sent {} habit reminders", count),
+            Err(e) => tracing::error!("Reminder job failed: {:?}", e),
+        }
+    }
+}
+
+/// Send every reminder due at `now` and schedule its next fire time,
+/// returning how many notifications were sent
+pub async fn process_due_reminders(db: &PgPool, sink: &dyn NotificationSink, now: DateTime<Utc>) -> ApiResult<usize> {
+    let reminders = sqlx::query_as::<_, ScheduledReminder>(
+        r#"SELECT r.id, r.habit_id, h.user_id, h.name AS habit_name,
+                  r.reminder_type,
+                  r.interval_hours, r.daily_time, r.random_window_start, r.random_window_end,
+                  r.next_fire_at, u.timezone, ($1 AT TIME ZONE u.timezone) AS local_now
+           FROM habit_reminders r
+           JOIN habits h ON h.id = r.habit_id
+           JOIN users u ON u.id = h.user_id
+           WHERE r.enabled AND NOT h.archived AND u.notify_reminders
+             AND (r.next_fire_at IS NULL OR r.next_fire_at <= $1)"#,
+    )
+    .bind(now)
+    .fetch_all(db)
+    .await?;
+
+    let mut sent = 0;
+    for reminder in reminders {
+        let (fire_at, local_fire_at) = match reminder.next_fire_time(now) {
+            Some(FireTime::At(at)) => (Some(at), None),
+            Some(FireTime::Local(local)) => (None, Some(local)),
+            None => (None, None),
+        };
+
+        // Claim the reminder so other instances don't send it too
+        let claimed = sqlx::query(
+            r#"UPDATE habit_reminders
+               SET next_fire_at = COALESCE($2, $3::timestamp AT TIME ZONE $4)
+               WHERE id = $1 AND next_fire_at IS NOT DISTINCT FROM $5"#,
+        )
+        .bind(reminder.id)
+        .bind(fire_at)
+        .bind(local_fire_at)
+        .bind(&reminder.timezone)
+        .bind(reminder.next_fire_at)
+        .execute(db)
+        .await?;
+
+        // Newly configured reminders are only scheduled, not sent
+        let Some(fire_at) = reminder.next_fire_at else { continue };
+        if claimed.rows_affected() == 0 {
+            continue;
+        }
+
+        let (checked_in,): (bool,) = sqlx::query_as(
+            r#"SELECT EXISTS(SELECT 1 FROM check_ins
+                             WHERE habit_id = $1 AND effective_date = ($2 AT TIME ZONE $3)::date)"#,
+        )
+        .bind(reminder.habit_id)
+        .bind(fire_at)
+        .bind(&reminder.timezone)
+        .fetch_one(db)
+        .await?;
+
+        if checked_in {
+            continue;
+        }
+
+        let notification = Notification {
+            user_id: reminder.user_id,
+            habit_id: reminder.habit_id,
+            reminder_id: reminder.id,
+            title: reminder.habit_name,
+            body: "Time to check in".to_string(),
+            scheduled_for: fire_at,
+        };
+
+        if let Err(e) = sink.send(&notification).await {
+            tracing::warn!("Failed to deliver reminder {}: {:?}", reminder.id, e);
+            continue;
+        }
+
+        sqlx::query("UPDATE habit_reminders SET last_sent_at = $2 WHERE id = $1")
+            .bind(reminder.id)
+            .bind(now)
+            .execute(db)
+            .await?;
+
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{
+        notifications::MemorySink,
+        test_support::{create_habit, create_user},
+    };
+
+    fn at(hour: u32, minute: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, day, hour, minute, 0).unwrap()
+    }
+
+    /// A daily 09:00 reminder for a user in New York (UTC-4 in June)
+    async fn daily_reminder(db: &PgPool) -> (Uuid, Uuid) {
+        let user = create_user(db, "early@example.com").await;
+        sqlx::query("UPDATE users SET timezone = 'America/New_York' WHERE id = $1")
+            .bind(user.user_id)
+            .execute(db)
+            .await
+            .unwrap();
+
+        let habit_id = create_habit(db, user.user_id, "Stretch", 10).await;
+        let reminder_id = Uuid::new_v4();
+        sqlx::query(
+            r#"INSERT INTO habit_reminders (id, habit_id, enabled, reminder_type, daily_time)
+               VALUES ($1, $2, true, 'daily', '09:00')"#,
+        )
+        .bind(reminder_id)
+        .bind(habit_id)
+        .execute(db)
+        .await
+        .unwrap();
+
+        (reminder_id, habit_id)
+    }
+
+    async fn next_fire_at(db: &PgPool, reminder_id: Uuid) -> Option<DateTime<Utc>> {
+        let (next,): (Option<DateTime<Utc>>,) = sqlx::query_as("SELECT next_fire_at FROM habit_reminders WHERE id = $1")
+            .bind(reminder_id)
+            .fetch_one(db)
+            .await
+            .unwrap();
+        next
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn local(hour: u32, minute: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap().and_time(time(hour, minute))
+    }
+
+    /// A reminder scanned at 08:00 local time on June 2nd
+    fn reminder(reminder_type: ReminderType) -> ScheduledReminder {
+        ScheduledReminder {
+            id: Uuid::new_v4(),
+            habit_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            habit_name: "Stretch".to_string(),
+            reminder_type,
+            interval_hours: None,
+            daily_time: None,
+            random_window_start: None,
+            random_window_end: None,
+            next_fire_at: None,
+            timezone: "America/New_York".to_string(),
+            local_now: local(8, 0, 2),
+        }
+    }
+
+    fn fires_at_local(reminder: &ScheduledReminder) -> NaiveDateTime {
+        match reminder.next_fire_time(at(12, 0, 2)) {
+            Some(FireTime::Local(local)) => local,
+            other => panic!("expected a local fire time, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn interval_reminders_fire_hours_from_now() {
+        let mut interval = reminder(ReminderType::Interval);
+        assert!(interval.next_fire_time(at(12, 0, 2)).is_none());
+
+        interval.interval_hours = Some(3);
+        assert!(matches!(interval.next_fire_time(at(12, 0, 2)), Some(FireTime::At(t)) if t == at(15, 0, 2)));
+    }
+
+    #[test]
+    fn daily_reminders_fire_at_the_next_local_time() {
+        let mut daily = reminder(ReminderType::Daily);
+        assert!(daily.next_fire_time(at(12, 0, 2)).is_none());
+
+        daily.daily_time = Some(time(9, 0));
+        assert_eq!(fires_at_local(&daily), local(9, 0, 2));
+
+        daily.daily_time = Some(time(8, 0));
+        assert_eq!(fires_at_local(&daily), local(8, 0, 3));
+
+        daily.daily_time = Some(time(7, 30));
+        assert_eq!(fires_at_local(&daily), local(7, 30, 3));
+    }
+
+    #[test]
+    fn random_reminders_fire_inside_the_next_unopened_window() {
+        let mut random = reminder(ReminderType::Random);
+        random.random_window_start = Some(time(10, 0));
+        assert!(random.next_fire_time(at(12, 0, 2)).is_none());
+
+        random.random_window_end = Some(time(12, 0));
+        for _ in 0..20 {
+            let fire_at = fires_at_local(&random);
+            assert!((local(10, 0, 2)..=local(12, 0, 2)).contains(&fire_at));
+        }
+
+        // Today's window has already opened
+        random.random_window_start = Some(time(7, 0));
+        for _ in 0..20 {
+            let fire_at = fires_at_local(&random);
+            assert!((local(7, 0, 3)..=local(12, 0, 3)).contains(&fire_at));
+        }
+    }
+
+    #[sqlx::test]
+    async fn due_reminders_are_sent_once_at_local_time(db: PgPool) {
+        let (reminder_id, habit_id) = daily_reminder(&db).await;
+        let sink = MemorySink::new();
+
+        // A new reminder is only scheduled
+        assert_eq!(process_due_reminders(&db, &sink, at(12, 0, 2)).await.unwrap(), 0);
+        assert_eq!(next_fire_at(&db, reminder_id).await, Some(at(13, 0, 2)));
+
+        assert_eq!(process_due_reminders(&db, &sink, at(12, 59, 2)).await.unwrap(), 0);
+        assert_eq!(process_due_reminders(&db, &sink, at(13, 0, 2)).await.unwrap(), 1);
+        assert_eq!(process_due_reminders(&db, &sink, at(13, 1, 2)).await.unwrap(), 0);
+        assert_eq!(next_fire_at(&db, reminder_id).await, Some(at(13, 0, 3)));
+
+        let sent = sink.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].habit_id, habit_id);
+        assert_eq!(sent[0].title, "Stretch");
+        assert_eq!(sent[0].scheduled_for, at(13, 0, 2));
+    }
+
+    #[sqlx::test]
+    async fn reminders_skip_days_already_checked_in(db: PgPool) {
+        let (reminder_id, habit_id) = daily_reminder(&db).await;
+        let sink = MemorySink::new();
+        process_due_reminders(&db, &sink, at(12, 0, 2)).await.unwrap();
+
+        sqlx::query(
+            r#"INSERT INTO check_ins (id, habit_id, user_id, value, effective_date, created_at)
+               SELECT $1, id, user_id, 1, '2025-06-02', NOW() FROM habits WHERE id = $2"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(habit_id)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(process_due_reminders(&db, &sink, at(13, 0, 2)).await.unwrap(), 0);
+        assert!(sink.sent().is_empty());
+        assert_eq!(next_fire_at(&db, reminder_id).await, Some(at(13, 0, 3)));
+    }
+
+    #[sqlx::test]
+    async fn reminders_stay_quiet_when_turned_off(db: PgPool) {
+        let (reminder_id, _) = daily_reminder(&db).await;
+        let sink = MemorySink::new();
+        process_due_reminders(&db, &sink, at(12, 0, 2)).await.unwrap();
+
+        sqlx::query("UPDATE users SET notify_reminders = false")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        assert_eq!(process_due_reminders(&db, &sink, at(13, 0, 2)).await.unwrap(), 0);
+        assert!(sink.sent().is_empty());
+        assert_eq!(next_fire_at(&db, reminder_id).await, Some(at(13, 0, 2)));
+    }
+}
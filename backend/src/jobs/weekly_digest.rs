@@ -0,0 +1,119 @@
+//! Weekly digest job
+//!
+//! Queues a summary email for each user who wants email, on the first day
+//! of their week once it's morning in their time zone. Users without any
+//! active habits or goals are skipped.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiResult,
+    mail::{self, templates::WeeklyDigest, Template},
+};
+
+/// Weekly digest job configuration
+#[derive(Debug, Clone)]
+pub struct WeeklyDigestConfig {
+    /// How often to look for users due a digest
+    pub interval: Duration,
+}
+
+impl WeeklyDigestConfig {
+    pub fn from_env() -> Self {
+        let interval_secs = std::env::var("DIGEST_CHECK_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+
+        Self {
+            interval: Duration::from_secs(interval_secs),
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct DigestRow {
+    id: Uuid,
+    email: String,
+    name: Option<String>,
+    last_digest_at: Option<DateTime<Utc>>,
+    check_ins: i64,
+    active_habits: i64,
+    active_goals: i64,
+    goals_achieved: i64,
+}
+
+/// Run the job forever; errors are logged and retried on the next tick
+pub async fn run(db: PgPool, config: WeeklyDigestConfig) {
+    let mut ticker = tokio::time::interval(config.interval);
+
+    loop {
+        ticker.tick().await;
+
+        match queue_weekly_digests(&db).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Queued {} weekly digests", count),
+            Err(e) => tracing::error!("Weekly digest job failed: {:?}", e),
+        }
+    }
+}
+
+/// Queue a digest for every user due one, returning how many were queued
+pub async fn queue_weekly_digests(db: &PgPool) -> ApiResult<usize> {
+    let due = sqlx::query_as::<_, DigestRow>(
+        r#"SELECT u.id, u.email, u.name, u.last_digest_at,
+                  (SELECT COUNT(*) FROM check_ins c
+                   WHERE c.user_id = u.id
+                     AND c.effective_date > (NOW() AT TIME ZONE u.timezone)::date - 7) AS check_ins,
+                  (SELECT COUNT(*) FROM habits h WHERE h.user_id = u.id AND NOT h.archived) AS active_habits,
+                  (SELECT COUNT(*) FROM goals g WHERE g.user_id = u.id AND g.status = 'active') AS active_goals,
+                  (SELECT COUNT(*) FROM goals g
+                   WHERE g.user_id = u.id AND g.status = 'achieved'
+                     AND g.updated_at > NOW() - INTERVAL '7 days') AS goals_achieved
+           FROM users u
+           WHERE u.notify_email
+             AND EXTRACT(ISODOW FROM NOW() AT TIME ZONE u.timezone) = u.week_start
+             AND EXTRACT(HOUR FROM NOW() AT TIME ZONE u.timezone) >= 8
+             AND (u.last_digest_at IS NULL OR u.last_digest_at < NOW() - INTERVAL '6 days')
+             AND (EXISTS (SELECT 1 FROM habits h WHERE h.user_id = u.id AND NOT h.archived)
+                  OR EXISTS (SELECT 1 FROM goals g WHERE g.user_id = u.id AND g.status = 'active'))"#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut queued = 0;
+    for row in due {
+        let mut tx = db.begin().await?;
+
+        // Claim the user so other instances don't queue a second digest
+        let claimed = sqlx::query(
+            "UPDATE users SET last_digest_at = NOW() WHERE id = $1 AND last_digest_at IS NOT DISTINCT FROM $2",
+        )
+        .bind(row.id)
+        .bind(row.last_digest_at)
+        .execute(&mut *tx)
+        .await?;
+
+        if claimed.rows_affected() == 0 {
+            continue;
+        }
+
+        let digest = WeeklyDigest {
+            name: row.name,
+            check_ins: row.check_ins,
+            active_habits: row.active_habits,
+            active_goals: row.active_goals,
+            goals_achieved: row.goals_achieved,
+        };
+        mail::enqueue(&mut tx, Some(row.id), &row.email, &Template::WeeklyDigest(&digest)).await?;
+
+        tx.commit().await?;
+        queued += 1;
+    }
+
+    Ok(queued)
+}
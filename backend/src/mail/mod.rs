@@ -0,0 +1,193 @@
+//! Outbound email
+//!
+//! Handlers never talk to a mail server directly. They render a `Template`
+//! and `enqueue` it in the `email_outbox` table, usually inside the same
+//! transaction as the change that triggered it. The outbox job then hands
+//! due messages to the configured `Mailer` and retries failed sends.
+
+pub mod templates;
+
+use std::{path::PathBuf, sync::Arc};
+#[cfg(test)]
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::error::ApiResult;
+pub use templates::Template;
+
+/// A rendered message ready to send
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()>;
+}
+
+/// Queue a templated email for delivery by the outbox job. `user_id` links
+/// the message to the account it concerns, if any.
+pub async fn enqueue(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    to: &str,
+    template: &Template<'_>,
+) -> ApiResult<Uuid> {
+    let rendered = template.render();
+    let id = Uuid::new_v4();
+
+    sqlx::query(
+        r#"INSERT INTO email_outbox (id, user_id, recipient, template, subject, text_body, html_body,
+                                     status, attempts, next_attempt_at, created_at)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, 'pending', 0, NOW(), NOW())"#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(to)
+    .bind(template.name())
+    .bind(&rendered.subject)
+    .bind(&rendered.text)
+    .bind(&rendered.html)
+    .execute(conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Pick a mailer from the environment: SMTP when `SMTP_HOST` is set,
+/// otherwise `.eml` files under `MAIL_FILE_DIR`
+pub fn mailer_from_env() -> anyhow::Result<Arc<dyn Mailer>> {
+    let from: Mailbox = std::env::var("MAIL_FROM")
+        .unwrap_or_else(|_| "BetterBe <no-reply@localhost>".to_string())
+        .parse()?;
+
+    match std::env::var("SMTP_HOST") {
+        Ok(host) if !host.is_empty() => Ok(Arc::new(SmtpMailer::from_env(&host, from)?)),
+        _ => {
+            let dir = std::env::var("MAIL_FILE_DIR").unwrap_or_else(|_| "./data/mail".to_string());
+            Ok(Arc::new(FileMailer::new(dir, from)))
+        }
+    }
+}
+
+fn build_message(from: &Mailbox, message: &EmailMessage) -> anyhow::Result<Message> {
+    Ok(Message::builder()
+        .from(from.clone())
+        .to(message.to.parse()?)
+        .subject(&message.subject)
+        .multipart(MultiPart::alternative_plain_html(
+            message.text_body.clone(),
+            message.html_body.clone(),
+        ))?)
+}
+
+/// Sends through an SMTP relay
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Configured by `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD` and
+    /// `SMTP_TLS` (`starttls`, `tls` or `none`)
+    fn from_env(host: &str, from: Mailbox) -> anyhow::Result<Self> {
+        let tls = std::env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+        let mut builder = match tls.as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            other => anyhow::bail!("Unknown SMTP_TLS mode: {}", other),
+        };
+
+        if let Some(port) = std::env::var("SMTP_PORT").ok().and_then(|v| v.parse().ok()) {
+            builder = builder.port(port);
+        }
+
+        if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()> {
+        self.transport.send(build_message(&self.from, message)?).await?;
+        Ok(())
+    }
+}
+
+/// Writes each message as an `.eml` file, for development
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>, from: Mailbox) -> Self {
+        Self { dir: dir.into(), from }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()> {
+        let email = build_message(&self.from, message)?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.dir.join(format!("{}.eml", Uuid::new_v4())), email.formatted()).await?;
+        Ok(())
+    }
+}
+
+/// Keeps messages in memory, for tests
+#[cfg(test)]
+#[derive(Default, Clone)]
+pub struct MemoryMailer {
+    sent: Arc<Mutex<Vec<EmailMessage>>>,
+    failing: bool,
+}
+
+#[cfg(test)]
+impl MemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A mailer whose every send fails
+    pub fn failing() -> Self {
+        Self { failing: true, ..Self::default() }
+    }
+
+    pub fn sent(&self) -> Vec<EmailMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()> {
+        if self.failing {
+            anyhow::bail!("mail server unavailable");
+        }
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Email templates
+//!
+//! Every template is a subject, a few paragraphs and an optional call to
+//! action, laid out the same way in the plain-text and HTML parts.
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+
+use crate::models::AuthProvider;
+
+/// Numbers for a user's weekly summary
+#[derive(Debug, Clone)]
+pub struct WeeklyDigest {
+    pub name: Option<String>,
+    pub check_ins: i64,
+    pub active_habits: i64,
+    pub active_goals: i64,
+    pub goals_achieved: i64,
+}
+
+pub enum Template<'a> {
+    GoalInvite {
+        inviter_name: Option<&'a str>,
+        goal_name: &'a str,
+        expires_at: DateTime<Utc>,
+    },
+    WeeklyDigest(&'a WeeklyDigest),
+    IdentityLinked {
+        provider: &'a AuthProvider,
+        email: &'a str,
+    },
+    IdentityUnlinked {
+        provider: &'a AuthProvider,
+    },
+    AccountDeleted,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+impl Template<'_> {
+    /// Stored with each outbox entry
+    pub fn name(&self) -> &'static str {
+        match self {
+            Template::GoalInvite { .. } => "goal_invite",
+            Template::WeeklyDigest(_) => "weekly_digest",
+            Template::IdentityLinked { .. } => "identity_linked",
+            Template::IdentityUnlinked { .. } => "identity_unlinked",
+            Template::AccountDeleted => "account_deleted",
+        }
+    }
+
+    pub fn render(&self) -> RenderedEmail {
+        let (subject, paragraphs, action) = match self {
+            Template::GoalInvite { inviter_name, goal_name, expires_at } => {
+                let inviter = inviter_name.unwrap_or("Someone");
+                (
+                    format!("{} invited you to \"{}\"", inviter, goal_name),
+                    vec![
+                        format!("{} invited you to work towards \"{}\" together on BetterBe.", inviter, goal_name),
+                        format!(
+                            "Sign in with this email address to accept. The invite expires on {}.",
+                            expires_at.format("%B %-d, %Y")
+                        ),
+                    ],
+                    Some(("View invite", format!("{}/invites", app_url()))),
+                )
+            }
+            Template::WeeklyDigest(digest) => (
+                "Your week on BetterBe".to_string(),
+                vec![
+                    format!("Hi {},", digest.name.as_deref().unwrap_or("there")),
+                    format!(
+                        "This week you checked in {} times across {} active habits.",
+                        digest.check_ins, digest.active_habits
+                    ),
+                    match digest.goals_achieved {
+                        0 => format!("You have {} goals in progress.", digest.active_goals),
+                        achieved => format!(
+                            "You achieved {} goals and have {} more in progress.",
+                            achieved, digest.active_goals
+                        ),
+                    },
+                ],
+                Some(("Open BetterBe", app_url().to_string())),
+            ),
+            Template::IdentityLinked { provider, email } => (
+                format!("{} sign-in added to your account", provider_label(provider)),
+                vec![
+                    format!(
+                        "{} ({}) can now be used to sign in to your BetterBe account.",
+                        provider_label(provider),
+                        email
+                    ),
+                    "If you didn't do this, remove it from your account settings.".to_string(),
+                ],
+                Some(("Account settings", format!("{}/settings", app_url()))),
+            ),
+            Template::IdentityUnlinked { provider } => (
+                format!("{} sign-in removed from your account", provider_label(provider)),
+                vec![
+                    format!(
+                        "{} can no longer be used to sign in to your BetterBe account.",
+                        provider_label(provider)
+                    ),
+                    "If you didn't do this, sign in and review your account settings.".to_string(),
+                ],
+                Some(("Account settings", format!("{}/settings", app_url()))),
+            ),
+            Template::AccountDeleted => (
+                "Your BetterBe account was deleted".to_string(),
+                vec![
+                    "Your BetterBe account and its data have been deleted.".to_string(),
+                    "Thanks for using BetterBe.".to_string(),
+                ],
+                None,
+            ),
+        };
+
+        layout(subject, &paragraphs, action)
+    }
+}
+
+fn layout(subject: String, paragraphs: &[String], action: Option<(&str, String)>) -> RenderedEmail {
+    let mut text = paragraphs.join("\n\n");
+    let mut html = paragraphs
+        .iter()
+        .map(|p| format!("<p>{}</p>", escape_html(p)))
+        .collect::<String>();
+
+    if let Some((label, url)) = action {
+        text.push_str(&format!("\n\n{}: {}", label, url));
+        html.push_str(&format!(
+            r#"<p><a href="{}">{}</a></p>"#,
+            escape_html(&url),
+            escape_html(label)
+        ));
+    }
+
+    RenderedEmail {
+        html: format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title></head><body>{}</body></html>",
+            escape_html(&subject),
+            html
+        ),
+        subject,
+        text,
+    }
+}
+
+/// Base URL of the web app, from `APP_URL`
+fn app_url() -> &'static str {
+    static APP_URL: OnceLock<String> = OnceLock::new();
+    APP_URL.get_or_init(|| {
+        std::env::var("APP_URL")
+            .unwrap_or_else(|_| "http://localhost:5173".to_string())
+            .trim_end_matches('/')
+            .to_string()
+    })
+}
+
+fn provider_label(provider: &AuthProvider) -> &'static str {
+    match provider {
+        AuthProvider::Google => "Google",
+        AuthProvider::Apple => "Apple",
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -2,16 +2,26 @@
 //! 
 //! A Rust backend for habit tracking with social auth and sharing features.
 
+mod activity;
+mod analytics;
 mod api;
 mod auth;
 mod db;
 mod error;
+mod jobs;
+mod mail;
 mod models;
+mod notifications;
+mod shared_goals;
+mod storage;
+#[cfg(test)]
+mod test_support;
 
 use axum::{Router, Extension};
 use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
-use tower_http::cors::{CorsLayer, Any};
+use axum::http::HeaderValue;
+use tower_http::cors::{AllowHeaders, AllowMethods, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -44,6 +54,30 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Database migrations completed");
 
+    // Start background jobs
+    tokio::spawn(jobs::goal_deadlines::run(
+        pool.clone(),
+        jobs::goal_deadlines::GoalDeadlineConfig::from_env(),
+    ));
+    tokio::spawn(jobs::invite_expiry::run(
+        pool.clone(),
+        jobs::invite_expiry::InviteExpiryConfig::from_env(),
+    ));
+    tokio::spawn(jobs::reminders::run(
+        pool.clone(),
+        jobs::reminders::ReminderConfig::from_env(),
+        notifications::sink_from_env(),
+    ));
+    tokio::spawn(jobs::weekly_digest::run(
+        pool.clone(),
+        jobs::weekly_digest::WeeklyDigestConfig::from_env(),
+    ));
+    tokio::spawn(jobs::email_outbox::run(
+        pool.clone(),
+        jobs::email_outbox::EmailOutboxConfig::from_env(),
+        mail::mailer_from_env()?,
+    ));
+
     // Build OAuth clients
     let oauth_clients = auth::oauth::OAuthClients::new()?;
 
@@ -52,17 +86,21 @@ async fn main() -> anyhow::Result<()> {
         db: pool,
         oauth: oauth_clients,
         jwt_secret: std::env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+        blobs: storage::from_env(),
     };
 
-    // CORS configuration
+    // CORS configuration. Only the web app may call the API from a browser,
+    // with credentials so its OAuth callback carries the state cookie.
     let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_origin(frontend_origins()?)
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(true);
 
     // Build router
     let app = Router::new()
         .route("/health", axum::routing::get(health_check))
+        .route("/blobs/*key", axum::routing::get(storage::serve_blob))
         .nest("/api", api::routes())
         .nest("/auth", auth::routes())
         .layer(cors)
@@ -79,6 +117,17 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Web app origins from `FRONTEND_ORIGINS`, comma-separated
+fn frontend_origins() -> anyhow::Result<Vec<HeaderValue>> {
+    std::env::var("FRONTEND_ORIGINS")
+        .unwrap_or_else(|_| "http://localhost:5173".to_string())
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| HeaderValue::from_str(origin.trim_end_matches('/')).map_err(Into::into))
+        .collect()
+}
+
 /// Health check endpoint for Docker/K8s
 async fn health_check() -> &'static str {
     "OK"
@@ -90,4 +139,5 @@ pub struct AppState {
     pub db: sqlx::PgPool,
     pub oauth: auth::oauth::OAuthClients,
     pub jwt_secret: String,
+    pub blobs: std::sync::Arc<dyn storage::BlobStore>,
 }
@@ -57,3 +57,84 @@ pub struct LinkHabitRequest {
     pub weight: Option<f32>,
 }
 
+
+/// Weighted progress of a goal across its linked habits
+#[derive(Debug, Clone, Serialize)]
+pub struct GoalProgress {
+    pub goal_id: Uuid,
+    pub start_date: NaiveDate,
+    pub deadline: NaiveDate,
+    pub days_remaining: i64,
+    /// Share of all scheduled periods up to the deadline completed so far
+    pub percentage: f64,
+    /// Final percentage if the current pace holds until the deadline
+    pub projected_percentage: f64,
+    pub on_track: bool,
+    pub habits: Vec<GoalHabitProgress>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GoalHabitProgress {
+    pub habit_id: Uuid,
+    /// Who tracks the habit; differs from the goal's owner for copies made
+    /// by shared goal participants
+    pub user_id: Uuid,
+    pub habit_name: String,
+    pub weight: f32,
+    pub completed_periods: i32,
+    pub elapsed_periods: i32,
+    pub total_periods: i32,
+    pub percentage: f64,
+    pub projected_percentage: f64,
+}
+
+/// Read-only link to a goal's progress for people without an account
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GoalPublicLink {
+    pub id: Uuid,
+    pub goal_id: Uuid,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublicLinkResponse {
+    /// Goes in `/api/public/goals/:token`
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Signed into public link tokens
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicLinkClaims {
+    pub sub: Uuid, // Link ID
+    pub aud: String,
+    pub iat: i64,
+}
+
+/// What a public link shows: no habit names, notes or participants
+#[derive(Debug, Serialize)]
+pub struct PublicGoalSnapshot {
+    pub name: String,
+    pub status: GoalStatus,
+    pub start_date: NaiveDate,
+    pub deadline: NaiveDate,
+    pub days_remaining: i64,
+    pub percentage: f64,
+    pub projected_percentage: f64,
+    pub on_track: bool,
+    pub habit_count: i32,
+    /// From the goal's start up to today or the deadline
+    pub days: Vec<GoalDay>,
+    /// Whether the signed-in caller owns the goal
+    pub is_owner: bool,
+}
+
+/// One cell of a goal's day grid
+#[derive(Debug, Clone, Serialize)]
+pub struct GoalDay {
+    pub date: NaiveDate,
+    /// Linked habits completed that day
+    pub completed: i32,
+}
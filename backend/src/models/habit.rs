@@ -1,6 +1,6 @@
 //! Habit models
 
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -20,6 +20,15 @@ pub enum TargetDirection {
     Exactly,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "schedule_type", rename_all = "snake_case")]
+pub enum ScheduleType {
+    Daily,
+    Weekdays,
+    TimesPerWeek,
+    EveryNDays,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Habit {
     pub id: Uuid,
@@ -30,7 +39,15 @@ pub struct Habit {
     pub unit: Option<String>,
     pub target_value: Option<i32>,
     pub target_direction: TargetDirection,
+    pub schedule_type: ScheduleType,
+    pub schedule_weekdays: Option<Vec<i32>>,
+    pub schedule_times_per_week: Option<i32>,
+    pub schedule_interval_days: Option<i32>,
     pub archived: bool,
+    /// Shared goal this habit was copied for, if any
+    pub shared_goal_id: Option<Uuid>,
+    /// The shared goal's habit it was copied from
+    pub source_habit_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -43,6 +60,10 @@ pub struct CreateHabitRequest {
     pub unit: Option<String>,
     pub target_value: Option<i32>,
     pub target_direction: Option<TargetDirection>,
+    pub schedule_type: Option<ScheduleType>,
+    pub schedule_weekdays: Option<Vec<i32>>,
+    pub schedule_times_per_week: Option<i32>,
+    pub schedule_interval_days: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,6 +73,11 @@ pub struct UpdateHabitRequest {
     pub unit: Option<String>,
     pub target_value: Option<i32>,
     pub target_direction: Option<TargetDirection>,
+    /// When set, replaces the whole schedule (other schedule fields included)
+    pub schedule_type: Option<ScheduleType>,
+    pub schedule_weekdays: Option<Vec<i32>>,
+    pub schedule_times_per_week: Option<i32>,
+    pub schedule_interval_days: Option<i32>,
     pub archived: Option<bool>,
 }
 
@@ -80,6 +106,22 @@ pub struct UpdateCheckInRequest {
     pub note: Option<String>,
 }
 
+/// Streak and completion statistics computed from check-ins
+#[derive(Debug, Clone, Serialize)]
+pub struct HabitStats {
+    pub habit_id: Uuid,
+    pub current_streak: i32,
+    pub longest_streak: i32,
+    pub schedule_type: ScheduleType,
+    /// Satisfied schedule periods (days, or weeks for `TimesPerWeek`)
+    pub completed_periods: i32,
+    /// Elapsed schedule periods; an unfinished current period is excluded
+    pub total_periods: i32,
+    pub completion_rate: f64,
+    pub last_completed_date: Option<NaiveDate>,
+    pub is_active_today: bool,
+}
+
 /// Reminder settings
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq)]
 #[sqlx(type_name = "reminder_type", rename_all = "lowercase")]
@@ -96,10 +138,69 @@ pub struct HabitReminder {
     pub enabled: bool,
     pub reminder_type: ReminderType,
     pub interval_hours: Option<i32>,
-    pub daily_time: Option<String>,
-    pub random_window_start: Option<String>,
-    pub random_window_end: Option<String>,
+    pub daily_time: Option<NaiveTime>,
+    pub random_window_start: Option<NaiveTime>,
+    pub random_window_end: Option<NaiveTime>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Create or replace a reminder. Times are wall-clock times in the user's
+/// time zone, as "HH:MM" or "HH:MM:SS".
+#[derive(Debug, Deserialize)]
+pub struct UpsertReminderRequest {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub reminder_type: ReminderType,
+    pub interval_hours: Option<i32>,
+    pub daily_time: Option<NaiveTime>,
+    pub random_window_start: Option<NaiveTime>,
+    pub random_window_end: Option<NaiveTime>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl UpsertReminderRequest {
+    /// Check that the fields required by `reminder_type` are set and
+    /// consistent. Fields belonging to other reminder types must be unset.
+    pub fn validate(&self) -> Result<(), String> {
+        let has_window = self.random_window_start.is_some() || self.random_window_end.is_some();
+
+        match self.reminder_type {
+            ReminderType::Interval => {
+                match self.interval_hours {
+                    Some(hours) if (1..=24).contains(&hours) => {}
+                    Some(_) => return Err("interval_hours must be between 1 and 24".to_string()),
+                    None => return Err("Interval reminders require interval_hours".to_string()),
+                }
+                if self.daily_time.is_some() || has_window {
+                    return Err("Interval reminders only take interval_hours".to_string());
+                }
+            }
+            ReminderType::Daily => {
+                if self.daily_time.is_none() {
+                    return Err("Daily reminders require daily_time".to_string());
+                }
+                if self.interval_hours.is_some() || has_window {
+                    return Err("Daily reminders only take daily_time".to_string());
+                }
+            }
+            ReminderType::Random => {
+                let (Some(start), Some(end)) = (self.random_window_start, self.random_window_end) else {
+                    return Err("Random reminders require random_window_start and random_window_end".to_string());
+                };
+                if end <= start {
+                    return Err("random_window_end must be after random_window_start".to_string());
+                }
+                if self.interval_hours.is_some() || self.daily_time.is_some() {
+                    return Err("Random reminders only take a random window".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
@@ -4,13 +4,23 @@ mod user;
 mod habit;
 mod goal;
 mod sharing;
+mod sync;
 
 pub use user::*;
 pub use habit::*;
 pub use goal::*;
 pub use sharing::*;
+pub use sync::*;
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc, NaiveDate};
 
+
+/// One page of a list, in the order requested
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
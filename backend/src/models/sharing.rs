@@ -1,6 +1,6 @@
 //! Sharing models for collaborative goals
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -20,6 +20,7 @@ pub enum InviteStatus {
     Accepted,
     Declined,
     Expired,
+    Revoked,
 }
 
 /// A shared goal between multiple users
@@ -29,10 +30,30 @@ pub struct SharedGoal {
     pub goal_id: Uuid,
     pub created_by: Uuid,
     pub invite_code: String,
+    #[sqlx(flatten)]
+    pub invite_code_settings: InviteCodeSettings,
     pub max_participants: i32,
+    /// Role given to people joining by code or invite
+    pub join_role: ShareRole,
     pub created_at: DateTime<Utc>,
 }
 
+/// When a shared goal's invite code can be used to join
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InviteCodeSettings {
+    /// False when joining by code is turned off
+    #[sqlx(rename = "invite_code_enabled")]
+    pub enabled: bool,
+    #[sqlx(rename = "invite_code_expires_at")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Joins allowed with the current code; unlimited when absent
+    #[sqlx(rename = "invite_code_max_uses")]
+    pub max_uses: Option<i32>,
+    /// Joins with the current code so far
+    #[sqlx(rename = "invite_code_uses")]
+    pub uses: i32,
+}
+
 /// Participant in a shared goal
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct GoalParticipant {
@@ -40,6 +61,7 @@ pub struct GoalParticipant {
     pub shared_goal_id: Uuid,
     pub user_id: Uuid,
     pub role: ShareRole,
+    pub leaderboard_opt_out: bool,
     pub joined_at: DateTime<Utc>,
 }
 
@@ -61,6 +83,32 @@ pub struct GoalInvite {
 pub struct CreateSharedGoalRequest {
     pub goal_id: Uuid,
     pub max_participants: Option<i32>,
+    /// Defaults to `Collaborator`
+    pub join_role: Option<ShareRole>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSharedGoalRequest {
+    pub max_participants: Option<i32>,
+    pub join_role: Option<ShareRole>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateParticipantRequest {
+    pub role: ShareRole,
+}
+
+/// Replaces the invite code's settings; omitted limits are removed
+#[derive(Debug, Deserialize)]
+pub struct UpdateInviteCodeRequest {
+    pub enabled: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferOwnershipRequest {
+    pub user_id: Uuid,
 }
 
 #[derive(Debug, Serialize)]
@@ -68,6 +116,9 @@ pub struct SharedGoalResponse {
     pub id: Uuid,
     pub goal: super::Goal,
     pub invite_code: String,
+    pub invite_code_settings: InviteCodeSettings,
+    pub max_participants: i32,
+    pub join_role: ShareRole,
     pub participants: Vec<ParticipantInfo>,
     pub created_at: DateTime<Utc>,
 }
@@ -86,9 +137,32 @@ pub struct InviteUserRequest {
     pub email: String,
 }
 
+/// An invite as shown to the invitee or the goal's participants
+#[derive(Debug, Serialize, FromRow)]
+pub struct InviteDetails {
+    pub id: Uuid,
+    pub shared_goal_id: Uuid,
+    pub goal_name: String,
+    pub inviter_id: Uuid,
+    pub inviter_name: Option<String>,
+    pub invitee_email: String,
+    pub status: InviteStatus,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct JoinByCodeRequest {
     pub invite_code: String,
+    /// Copy the goal's habits into your account; defaults to true unless
+    /// joining as a viewer
+    pub copy_habits: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AcceptInviteRequest {
+    /// Same as `JoinByCodeRequest::copy_habits`
+    pub copy_habits: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -123,11 +197,96 @@ pub enum ActivityType {
 #[derive(Debug, Serialize)]
 pub struct ActivityFeedItem {
     pub id: Uuid,
+    pub user_id: Uuid,
     pub user_name: Option<String>,
     pub user_avatar: Option<String>,
     pub activity_type: ActivityType,
     pub habit_name: Option<String>,
     pub message: Option<String>,
+    pub reactions: Vec<ReactionCount>,
     pub created_at: DateTime<Utc>,
 }
 
+/// How many participants reacted to a feed item with one emoji
+#[derive(Debug, Serialize)]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: i64,
+    /// Whether the current user is one of them
+    pub reacted: bool,
+}
+
+/// Emoji reaction on a feed item
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ActivityReaction {
+    pub id: Uuid,
+    pub activity_id: Uuid,
+    pub user_id: Uuid,
+    pub emoji: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EncourageRequest {
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReactionRequest {
+    pub emoji: String,
+}
+
+/// Span a leaderboard covers, ending today in each participant's time zone
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardWindow {
+    /// The last 7 days
+    #[default]
+    Week,
+    /// The last 30 days
+    Month,
+    /// Since the goal was created
+    Goal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Leaderboard {
+    pub shared_goal_id: Uuid,
+    pub window: LeaderboardWindow,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// How one participant is doing on their habits linked to the goal
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntry {
+    /// `None` for participants who opted out or have no linked habits
+    pub rank: Option<i32>,
+    pub user_id: Uuid,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub linked_habits: i32,
+    pub completed_periods: i32,
+    pub elapsed_periods: i32,
+    /// Weighted share of elapsed periods in the window that were satisfied
+    pub completion_rate: f64,
+    /// Best current streak across the linked habits
+    pub current_streak: i32,
+    /// Longest streak inside the window
+    pub longest_streak: i32,
+    /// Check-ins in the window that met the habit's target
+    pub check_ins: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    #[serde(default)]
+    pub window: LeaderboardWindow,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardRankingRequest {
+    /// False to be left out of the ranking
+    pub ranked: bool,
+}
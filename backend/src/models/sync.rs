@@ -0,0 +1,14 @@
+//! Sync models
+
+use serde::{Deserialize, Serialize};
+
+/// Kinds of records tracked by cloud sync
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "sync_entity", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SyncEntity {
+    Habit,
+    CheckIn,
+    Goal,
+    GoalHabit,
+}
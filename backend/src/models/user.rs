@@ -14,6 +14,8 @@ pub struct User {
     pub provider: AuthProvider,
     pub provider_id: String,
     pub cloud_sync_enabled: bool,
+    #[sqlx(flatten)]
+    pub preferences: UserPreferences,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -26,6 +28,31 @@ pub enum AuthProvider {
     // Instagram OAuth is deprecated for new apps, using Apple instead
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserPreferences {
+    /// IANA time zone, e.g. "Europe/Paris"
+    pub timezone: String,
+    /// First day of the week, 1 = Monday ... 7 = Sunday
+    pub week_start: i16,
+    /// BCP 47 language tag, e.g. "en-GB"
+    pub locale: String,
+    pub notify_reminders: bool,
+    pub notify_shared_activity: bool,
+    pub notify_email: bool,
+}
+
+/// A provider identity that can sign in to a user
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: AuthProvider,
+    pub provider_id: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserProfile {
     pub id: Uuid,
@@ -33,6 +60,7 @@ pub struct UserProfile {
     pub name: Option<String>,
     pub avatar_url: Option<String>,
     pub cloud_sync_enabled: bool,
+    pub preferences: UserPreferences,
 }
 
 impl From<User> for UserProfile {
@@ -43,6 +71,7 @@ impl From<User> for UserProfile {
             name: user.name,
             avatar_url: user.avatar_url,
             cloud_sync_enabled: user.cloud_sync_enabled,
+            preferences: user.preferences,
         }
     }
 }
@@ -51,6 +80,18 @@ impl From<User> for UserProfile {
 pub struct UpdateUserRequest {
     pub name: Option<String>,
     pub cloud_sync_enabled: Option<bool>,
+    #[serde(default)]
+    pub preferences: UpdatePreferencesRequest,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdatePreferencesRequest {
+    pub timezone: Option<String>,
+    pub week_start: Option<i16>,
+    pub locale: Option<String>,
+    pub notify_reminders: Option<bool>,
+    pub notify_shared_activity: Option<bool>,
+    pub notify_email: Option<bool>,
 }
 
 /// JWT Claims structure
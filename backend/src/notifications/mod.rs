@@ -0,0 +1,109 @@
+//! Notification delivery
+//!
+//! Background jobs hand notifications to a `NotificationSink`; where they
+//! end up (logs, a webhook, a push service) depends on the configured sink.
+
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// A reminder to check in on a habit
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub user_id: Uuid,
+    pub habit_id: Uuid,
+    pub reminder_id: Uuid,
+    pub title: String,
+    pub body: String,
+    pub scheduled_for: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+/// Pick a sink from the environment: a webhook when `NOTIFICATION_WEBHOOK_URL`
+/// is set, otherwise the log
+pub fn sink_from_env() -> Arc<dyn NotificationSink> {
+    match std::env::var("NOTIFICATION_WEBHOOK_URL") {
+        Ok(url) if !url.is_empty() => Arc::new(WebhookSink::new(url)),
+        _ => Arc::new(LogSink),
+    }
+}
+
+/// Writes notifications to the log
+pub struct LogSink;
+
+#[async_trait]
+impl NotificationSink for LogSink {
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        tracing::info!(
+            "Notification for user {}: {} - {}",
+            notification.user_id,
+            notification.title,
+            notification.body
+        );
+        Ok(())
+    }
+}
+
+/// POSTs each notification as JSON to a URL
+pub struct WebhookSink {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.http
+            .post(&self.url)
+            .json(notification)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Keeps notifications in memory, for tests
+#[cfg(test)]
+#[derive(Default, Clone)]
+pub struct MemorySink {
+    sent: Arc<Mutex<Vec<Notification>>>,
+}
+
+#[cfg(test)]
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sent(&self) -> Vec<Notification> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl NotificationSink for MemorySink {
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push(notification.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Shared goal ownership and habit copies
+//!
+//! Ownership changes hands both when an owner gives it away and when they
+//! delete their account, and participants' habits are copied both when they
+//! join and on request. Both paths go through here so they can't drift.
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+
+/// What becomes of the previous owner after a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviousOwner {
+    /// Stays on as a collaborator, keeping their linked habits
+    Stays,
+    /// Is deleting their account. Their linked habits are copied to the new
+    /// owner first, since the originals and their check-ins go with them.
+    Leaves,
+}
+
+/// Hand a shared goal to another participant. The goal moves to the new
+/// owner's account and public links published by the previous owner are
+/// revoked. The caller must hold the shared goal's row lock.
+pub async fn transfer_ownership(
+    conn: &mut PgConnection,
+    shared_goal_id: Uuid,
+    previous_owner: Uuid,
+    new_owner: Uuid,
+    previous: PreviousOwner,
+) -> ApiResult<()> {
+    let (goal_id,): (Uuid,) = sqlx::query_as("SELECT goal_id FROM shared_goals WHERE id = $1")
+        .bind(shared_goal_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if previous == PreviousOwner::Leaves {
+        copy_linked_habits(conn, shared_goal_id, new_owner).await?;
+
+        // Pending invites would otherwise be deleted with their inviter
+        sqlx::query("UPDATE goal_invites SET inviter_id = $3 WHERE shared_goal_id = $1 AND inviter_id = $2")
+            .bind(shared_goal_id)
+            .bind(previous_owner)
+            .bind(new_owner)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query(
+        r#"INSERT INTO sync_tombstones (id, user_id, entity_type, server_id, deleted_at)
+           VALUES ($1, $2, 'goal', $3, NOW())"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(previous_owner)
+    .bind(goal_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE goals SET user_id = $2, updated_at = NOW() WHERE id = $1")
+        .bind(goal_id)
+        .bind(new_owner)
+        .execute(&mut *conn)
+        .await?;
+
+    // Public links were published by the previous owner
+    sqlx::query("UPDATE goal_public_links SET revoked_at = NOW() WHERE goal_id = $1 AND revoked_at IS NULL")
+        .bind(goal_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query("UPDATE shared_goals SET created_by = $2 WHERE id = $1")
+        .bind(shared_goal_id)
+        .bind(new_owner)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        r#"UPDATE goal_participants
+           SET role = CASE WHEN user_id = $2 THEN 'owner'::share_role ELSE 'collaborator'::share_role END
+           WHERE shared_goal_id = $1 AND user_id IN ($2, $3)"#,
+    )
+    .bind(shared_goal_id)
+    .bind(new_owner)
+    .bind(previous_owner)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Copy the goal's habits into the user's account, bound to the goal and
+/// linked with the same weight. Any habit linked to the goal can be the
+/// source, preferring the owner's, so habits are still found after an
+/// ownership transfer. Copies point at the original habit, so a habit the
+/// user already has a copy of, or owns, is skipped however it's reached.
+pub async fn copy_linked_habits(conn: &mut PgConnection, shared_goal_id: Uuid, user_id: Uuid) -> ApiResult<u64> {
+    let result = sqlx::query(
+        r#"WITH sources AS (
+               SELECT DISTINCT ON (COALESCE(h.source_habit_id, h.id))
+                      h.*, COALESCE(h.source_habit_id, h.id) AS root_habit_id, gh.goal_id, gh.weight
+               FROM shared_goals sg
+               JOIN goals g ON g.id = sg.goal_id
+               JOIN goal_habits gh ON gh.goal_id = g.id
+               JOIN habits h ON h.id = gh.habit_id
+               WHERE sg.id = $1 AND h.user_id <> $2 AND NOT h.archived
+                 AND NOT EXISTS (
+                     SELECT 1 FROM habits c
+                     WHERE c.user_id = $2
+                       AND (c.id = COALESCE(h.source_habit_id, h.id)
+                            OR (c.shared_goal_id = $1 AND c.source_habit_id = COALESCE(h.source_habit_id, h.id)))
+                 )
+               ORDER BY COALESCE(h.source_habit_id, h.id), h.user_id = g.user_id DESC, h.created_at ASC
+           ),
+           copies AS (
+               INSERT INTO habits (id, user_id, name, description, habit_type, unit, target_value, target_direction,
+                                   schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                                   archived, shared_goal_id, source_habit_id, created_at, updated_at)
+               SELECT gen_random_uuid(), $2, name, description, habit_type, unit, target_value, target_direction,
+                      schedule_type, schedule_weekdays, schedule_times_per_week, schedule_interval_days,
+                      false, $1, root_habit_id, NOW(), NOW()
+               FROM sources
+               RETURNING id, source_habit_id
+           )
+           INSERT INTO goal_habits (id, goal_id, habit_id, weight)
+           SELECT gen_random_uuid(), s.goal_id, c.id, s.weight
+           FROM copies c
+           JOIN sources s ON s.root_habit_id = c.source_habit_id"#,
+    )
+    .bind(shared_goal_id)
+    .bind(user_id)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Before the user's habits are deleted, make one copy of each the new
+/// original for the others in the same shared goal, preferring the goal
+/// owner's. Otherwise the copies lose their common source and copying
+/// again would duplicate them.
+pub async fn reroot_copies(conn: &mut PgConnection, user_id: Uuid) -> ApiResult<()> {
+    sqlx::query(
+        r#"WITH new_roots AS (
+               SELECT DISTINCT ON (c.shared_goal_id, c.source_habit_id)
+                      c.shared_goal_id, c.source_habit_id AS old_root, c.id AS new_root
+               FROM habits c
+               JOIN habits o ON o.id = c.source_habit_id AND o.user_id = $1
+               JOIN shared_goals sg ON sg.id = c.shared_goal_id
+               JOIN goals g ON g.id = sg.goal_id
+               WHERE c.user_id <> $1
+               ORDER BY c.shared_goal_id, c.source_habit_id, c.user_id = g.user_id DESC, c.created_at ASC
+           )
+           UPDATE habits c
+           SET source_habit_id = CASE WHEN c.id = r.new_root THEN NULL ELSE r.new_root END
+           FROM new_roots r
+           WHERE c.shared_goal_id = r.shared_goal_id AND c.source_habit_id = r.old_root"#,
+    )
+    .bind(user_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
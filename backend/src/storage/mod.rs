@@ -0,0 +1,137 @@
+//! Blob storage for user uploads
+//!
+//! Uploads go through a `BlobStore` so the backing storage can change without
+//! touching handlers. Blobs are served back under `/blobs/<key>`.
+
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::Path,
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+
+use crate::{error::{ApiError, ApiResult}, AppState};
+
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: Bytes) -> anyhow::Result<()>;
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+    /// Public URL the blob is served from
+    fn url(&self, key: &str) -> String;
+}
+
+/// Build the blob store from `BLOB_STORAGE_DIR` and `PUBLIC_BASE_URL`
+pub fn from_env() -> Arc<dyn BlobStore> {
+    let root = std::env::var("BLOB_STORAGE_DIR").unwrap_or_else(|_| "./data/blobs".to_string());
+    let public_base_url = std::env::var("PUBLIC_BASE_URL")
+        .unwrap_or_else(|_| "http://localhost:3000".to_string());
+
+    Arc::new(LocalBlobStore::new(root, public_base_url))
+}
+
+/// Stores blobs as files under a root directory
+pub struct LocalBlobStore {
+    root: PathBuf,
+    public_base_url: String,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>, public_base_url: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            public_base_url: public_base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        if !is_valid_key(key) {
+            anyhow::bail!("Invalid blob key: {}", key);
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, bytes: Bytes) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/blobs/{}", self.public_base_url, key)
+    }
+}
+
+/// Keys are relative paths made of `[A-Za-z0-9._-]` segments
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        })
+}
+
+/// Image formats accepted for uploads, detected from the file's contents
+pub fn detect_image(bytes: &[u8]) -> Option<(&'static str, &'static str)> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(("png", "image/png"))
+    } else if bytes.starts_with(b"\xFF\xD8\xFF") {
+        Some(("jpg", "image/jpeg"))
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(("gif", "image/gif"))
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some(("webp", "image/webp"))
+    } else {
+        None
+    }
+}
+
+/// Serve a stored blob
+pub async fn serve_blob(
+    Extension(state): Extension<AppState>,
+    Path(key): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    if !is_valid_key(&key) {
+        return Err(ApiError::NotFound);
+    }
+
+    let bytes = state.blobs.get(&key).await?.ok_or(ApiError::NotFound)?;
+    let content_type = detect_image(&bytes)
+        .map(|(_, content_type)| content_type)
+        .unwrap_or("application/octet-stream");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        bytes,
+    ))
+}
@@ -25,6 +25,11 @@ export const authError = writable<string | null>(null);
  */
 export const isAuthenticated = derived(currentUser, $user => $user !== null);
 
+/**
+ * Session key holding the nonce issued for a pending Apple sign-in
+ */
+const APPLE_NONCE_KEY = 'appleNonce';
+
 /**
  * Sign in with Google
  */
@@ -44,12 +49,12 @@ export async function signInWithGoogle(): Promise<void> {
 /**
  * Handle Google OAuth callback
  */
-export async function handleGoogleCallback(code: string): Promise<void> {
+export async function handleGoogleCallback(code: string, state: string): Promise<void> {
     authLoading.set(true);
     authError.set(null);
 
     try {
-        const response = await remoteAuth.handleGoogleCallback(code);
+        const response = await remoteAuth.handleGoogleCallback(code, state);
         setAuthState(response);
     } catch (e) {
         authError.set(e instanceof Error ? e.message : 'Failed to complete sign in');
@@ -67,6 +72,8 @@ export async function signInWithApple(): Promise<void> {
 
     try {
         const config = await remoteAuth.initiateAppleAuth();
+        // The SDK must pass config.nonce to Apple; the callback sends it back
+        sessionStorage.setItem(APPLE_NONCE_KEY, config.nonce);
         // Apple Sign In requires client-side SDK
         // This will be handled by the native app or web SDK
         console.log('Apple auth config:', config);
@@ -84,11 +91,17 @@ export async function handleAppleCallback(code: string, idToken: string, user?:
     authError.set(null);
 
     try {
-        const response = await remoteAuth.handleAppleCallback(code, idToken, user);
+        const nonce = sessionStorage.getItem(APPLE_NONCE_KEY);
+        if (!nonce) {
+            throw new Error('Apple sign-in was not started in this browser');
+        }
+        const response = await remoteAuth.handleAppleCallback(code, idToken, nonce, user);
         setAuthState(response);
     } catch (e) {
         authError.set(e instanceof Error ? e.message : 'Failed to complete sign in');
     } finally {
+        // Nonces are single-use either way
+        sessionStorage.removeItem(APPLE_NONCE_KEY);
         authLoading.set(false);
     }
 }
@@ -82,6 +82,26 @@ class ApiClient {
         return response.json();
     }
 
+    /**
+     * Fetch every page of a paginated list endpoint
+     */
+    async fetchAll<T>(endpoint: string): Promise<T[]> {
+        const separator = endpoint.includes('?') ? '&' : '?';
+        const items: T[] = [];
+        let cursor: string | null = null;
+
+        do {
+            const url: string = cursor
+                ? `${endpoint}${separator}cursor=${encodeURIComponent(cursor)}`
+                : endpoint;
+            const page: { items: T[]; next_cursor: string | null } = await this.fetch(url);
+            items.push(...page.items);
+            cursor = page.next_cursor;
+        } while (cursor);
+
+        return items;
+    }
+
     private async tryRefreshToken(): Promise<boolean> {
         try {
             const response = await fetch(`${API_BASE}/auth/refresh`, {
@@ -113,7 +133,7 @@ export class RemoteDataLayer implements DataLayer {
     // ============ Habits ============
 
     async getActiveHabits(): Promise<Habit[]> {
-        const habits = await api.fetch<any[]>('/api/habits');
+        const habits = await api.fetchAll<any>('/api/habits');
         return habits.map(mapHabitFromApi);
     }
 
@@ -158,7 +178,7 @@ export class RemoteDataLayer implements DataLayer {
     }
 
     async getCheckInsForHabit(habitId: string): Promise<CheckIn[]> {
-        const checkins = await api.fetch<any[]>(`/api/checkins?habit_id=${habitId}`);
+        const checkins = await api.fetchAll<any>(`/api/checkins?habit_id=${habitId}`);
         return checkins.map(mapCheckInFromApi);
     }
 
@@ -192,8 +212,8 @@ export class RemoteDataLayer implements DataLayer {
     // ============ Goals ============
 
     async getActiveGoals(): Promise<Goal[]> {
-        const goals = await api.fetch<any[]>('/api/goals');
-        return goals.filter(g => g.status === 'active').map(mapGoalFromApi);
+        const goals = await api.fetchAll<any>('/api/goals?status=Active');
+        return goals.map(mapGoalFromApi);
     }
 
     async getGoal(id: string): Promise<Goal | undefined> {
@@ -277,25 +297,23 @@ export class RemoteDataLayer implements DataLayer {
     }
 
     async upsertReminder(input: Omit<HabitReminder, 'id' | 'createdAt' | 'updatedAt'>): Promise<HabitReminder> {
-        const reminder = await api.fetch<any>(`/api/habits/${input.habitId}/reminder`, {
+        // The app keeps one reminder per habit, so it replaces the whole list
+        const [reminder] = await api.fetch<any[]>(`/api/habits/${input.habitId}/reminder`, {
             method: 'PUT',
-            body: JSON.stringify({
+            body: JSON.stringify([{
                 enabled: input.enabled,
                 reminder_type: input.type,
                 interval_hours: input.intervalHours,
                 daily_time: input.dailyTime,
                 random_window_start: input.randomWindowStart,
                 random_window_end: input.randomWindowEnd,
-            }),
+            }]),
         });
         return mapReminderFromApi(reminder);
     }
 
     async deleteReminder(habitId: string): Promise<void> {
-        await api.fetch(`/api/habits/${habitId}/reminder`, {
-            method: 'PUT',
-            body: JSON.stringify({ enabled: false }),
-        });
+        await api.fetch(`/api/habits/${habitId}/reminder`, { method: 'DELETE' });
     }
 }
 
@@ -308,22 +326,31 @@ export class RemoteAuthLayer implements AuthLayer {
         return `${API_BASE}/auth/google`;
     }
 
-    async handleGoogleCallback(code: string): Promise<AuthResponse> {
-        const response = await fetch(`${API_BASE}/auth/google/callback?code=${code}`);
+    async handleGoogleCallback(code: string, state: string): Promise<AuthResponse> {
+        // The state cookie set by /auth/google proves this browser started the sign-in
+        const params = new URLSearchParams({ code, state });
+        const response = await fetch(`${API_BASE}/auth/google/callback?${params}`, {
+            credentials: 'include',
+        });
         const data = await response.json();
         api.setTokens(data.access_token, data.refresh_token);
         return mapAuthResponse(data);
     }
 
-    async initiateAppleAuth(): Promise<{ clientId: string; redirectUri: string }> {
-        return api.fetch('/auth/apple');
+    async initiateAppleAuth(): Promise<{ clientId: string; redirectUri: string; nonce: string }> {
+        const config = await api.fetch<any>('/auth/apple');
+        return {
+            clientId: config.client_id,
+            redirectUri: config.redirect_uri,
+            nonce: config.nonce,
+        };
     }
 
-    async handleAppleCallback(code: string, idToken: string, user?: string): Promise<AuthResponse> {
+    async handleAppleCallback(code: string, idToken: string, nonce: string, user?: string): Promise<AuthResponse> {
         const response = await fetch(`${API_BASE}/auth/apple/callback`, {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
-            body: JSON.stringify({ code, id_token: idToken, user }),
+            body: JSON.stringify({ code, id_token: idToken, nonce, user }),
         });
         const data = await response.json();
         api.setTokens(data.access_token, data.refresh_token);
@@ -376,7 +403,7 @@ export class RemoteAuthLayer implements AuthLayer {
  */
 export class RemoteSharingLayer implements SharingLayer {
     async getSharedGoals(): Promise<SharedGoal[]> {
-        const goals = await api.fetch<any[]>('/api/sharing/goals');
+        const goals = await api.fetchAll<any>('/api/sharing/goals');
         return goals.map(mapSharedGoal);
     }
 
@@ -417,8 +444,8 @@ export class RemoteSharingLayer implements SharingLayer {
     }
 
     async getActivityFeed(sharedGoalId: string): Promise<ActivityFeedItem[]> {
-        const activities = await api.fetch<any[]>(`/api/sharing/goals/${sharedGoalId}/activity`);
-        return activities.map(a => ({
+        const page = await api.fetch<{ items: any[] }>(`/api/sharing/goals/${sharedGoalId}/activity`);
+        return page.items.map(a => ({
             id: a.id,
             userName: a.user_name,
             userAvatar: a.user_avatar,
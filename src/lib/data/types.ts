@@ -115,9 +115,9 @@ export interface DataLayer {
 export interface AuthLayer {
     // OAuth
     initiateGoogleAuth(): Promise<string>; // Returns redirect URL
-    handleGoogleCallback(code: string): Promise<AuthResponse>;
-    initiateAppleAuth(): Promise<{ clientId: string; redirectUri: string }>;
-    handleAppleCallback(code: string, idToken: string, user?: string): Promise<AuthResponse>;
+    handleGoogleCallback(code: string, state: string): Promise<AuthResponse>;
+    initiateAppleAuth(): Promise<{ clientId: string; redirectUri: string; nonce: string }>;
+    handleAppleCallback(code: string, idToken: string, nonce: string, user?: string): Promise<AuthResponse>;
     
     // Token management
     refreshToken(refreshToken: string): Promise<AuthResponse>;